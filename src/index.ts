@@ -1,4 +1,9 @@
 (async function() {
   const wasm = await import("tora-breakout-wasm");
-  wasm.start(ASSET_URL);
+  // 外すときはgame.destroy()でイベントリスナーと描画ループを片付ける
+  const game = wasm.start("#myCanvas", {
+    assetUrl: ASSET_URL,
+    manifest: ASSET_MANIFEST
+  });
+  window.addEventListener("pagehide", () => game.destroy(), { once: true });
 })();
@@ -1,2 +1,3 @@
 // typings/custom.d.ts
 declare const ASSET_URL: string;
+declare const ASSET_MANIFEST: string;
@@ -0,0 +1,75 @@
+//! ブロックの画像を内容のハッシュ入りの名前でコピーし、ゲームが読むアセットマニフェストを書き出す
+//! 中身が変わった画像だけ名前が変わるので、長くキャッシュさせても古い画像が残らない
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tora_breakout_wasm::assets::{AssetEntry, AssetManifest};
+
+/// ファイル名に入れるハッシュの長さ(16進の桁数)
+pub const HASH_LENGTH: usize = 8;
+
+/// 論理名の拡張子の前にハッシュを入れる "imgs/26_angel_1_1.png" -> "imgs/26_angel_1_1.0123abcd.png"
+pub fn hashed_path(name: &str, digest: &[u8; 32]) -> String {
+    let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    let hash = &hash[..HASH_LENGTH];
+    let file_start = name.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match name[file_start..].rfind('.') {
+        Some(i) => format!(
+            "{}.{}{}",
+            &name[..file_start + i],
+            hash,
+            &name[file_start + i..]
+        ),
+        None => format!("{}.{}", name, hash),
+    }
+}
+
+/// Subresource Integrityの形式 "sha256-<base64>"
+pub fn integrity(digest: &[u8; 32]) -> String {
+    format!("sha256-{}", STANDARD.encode(digest))
+}
+
+pub fn asset_entry(name: &str, bytes: &[u8]) -> AssetEntry {
+    let digest: [u8; 32] = Sha256::digest(bytes).into();
+    AssetEntry {
+        path: hashed_path(name, &digest),
+        size: bytes.len() as u64,
+        integrity: integrity(&digest),
+    }
+}
+
+/// imgsの中のPNGをout/imgs/にハッシュ入りの名前でコピーし、そのマニフェストを返す
+/// 論理名はget_imageが読むパスと同じ"imgs/{ファイル名}"
+pub fn build_manifest(imgs: &Path, out: &Path) -> Result<AssetManifest, String> {
+    let error = |path: &Path, e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let mut files: Vec<PathBuf> = fs::read_dir(imgs)
+        .map_err(|e| error(imgs, &e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|e| e == "png"))
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(error(imgs, &"no PNG images found"));
+    }
+    let mut manifest = AssetManifest::new();
+    for file in files {
+        let bytes = fs::read(&file).map_err(|e| error(&file, &e))?;
+        let file_name = file.file_name().unwrap().to_string_lossy();
+        let name = format!("imgs/{}", file_name);
+        let entry = asset_entry(&name, &bytes);
+        let target = out.join(&entry.path);
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir).map_err(|e| error(dir, &e))?;
+        }
+        fs::write(&target, &bytes).map_err(|e| error(&target, &e))?;
+        manifest.insert(name, entry);
+    }
+    Ok(manifest)
+}
+
+pub fn write_manifest(manifest: &AssetManifest, path: &Path) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+    fs::write(path, json + "\n").map_err(|e| format!("{}: {}", path.display(), e))
+}
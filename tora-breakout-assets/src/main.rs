@@ -0,0 +1,41 @@
+//! tora-breakout-assets [options]
+//! ブロックの画像をハッシュ入りの名前でコピーして、アセットマニフェストを書き出す
+use clap::Parser;
+use std::path::PathBuf;
+use std::process;
+use tora_breakout_assets::{build_manifest, write_manifest};
+
+/// Copies the brick images to content-hashed file names and writes an asset manifest
+/// that maps each image to its hashed path, size and SHA-256 integrity value.
+/// Serve OUT next to the game and pass the manifest path as the `manifest` config key.
+#[derive(Parser)]
+#[command(name = "tora-breakout-assets")]
+struct Args {
+    /// directory of the brick images
+    #[arg(long, value_name = "DIR", default_value = "imgs")]
+    imgs: PathBuf,
+    /// output directory
+    #[arg(long, value_name = "DIR", default_value = "dist")]
+    out: PathBuf,
+    /// manifest file name in OUT
+    #[arg(long, value_name = "NAME", default_value = "assets.json")]
+    manifest: String,
+}
+
+fn main() {
+    let args = Args::parse();
+    let manifest = build_manifest(&args.imgs, &args.out).unwrap_or_else(|e| fail(&e));
+    let manifest_path = args.out.join(&args.manifest);
+    write_manifest(&manifest, &manifest_path).unwrap_or_else(|e| fail(&e));
+    println!(
+        "hashed {} image(s) into {}, wrote {}",
+        manifest.len(),
+        args.out.join("imgs").display(),
+        manifest_path.display()
+    );
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
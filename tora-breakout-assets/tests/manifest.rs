@@ -0,0 +1,84 @@
+//! ハッシュ入りの名前とintegrityの値が中身から決まり、書き出したマニフェストをゲーム側で引けることを確認する
+
+use std::fs;
+use tora_breakout_assets::{asset_entry, build_manifest, hashed_path, integrity, write_manifest};
+use tora_breakout_test_support::{imgs, temp_dir};
+use tora_breakout_wasm::assets::{image_name, AssetManifest};
+
+#[test]
+fn entry_names_the_file_by_its_sha256_and_records_the_integrity() {
+    // 空のファイルのSHA-256はe3b0c442...
+    let entry = asset_entry("imgs/26_angel_1_1.png", b"");
+    assert_eq!(entry.path, "imgs/26_angel_1_1.e3b0c442.png");
+    assert_eq!(entry.size, 0);
+    assert_eq!(
+        entry.integrity,
+        "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+    );
+    let entry = asset_entry("imgs/26_angel_1_1.png", b"abc");
+    assert_eq!(entry.path, "imgs/26_angel_1_1.ba7816bf.png");
+    assert_eq!(
+        entry.integrity,
+        "sha256-ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0="
+    );
+}
+
+#[test]
+fn hashed_path_changes_only_with_the_content() {
+    let a = asset_entry("imgs/26_angel_1_1.png", b"one");
+    let b = asset_entry("imgs/26_angel_1_1.png", b"one");
+    let c = asset_entry("imgs/26_angel_1_1.png", b"two");
+    assert_eq!(a, b);
+    assert_ne!(a.path, c.path);
+    assert_ne!(a.integrity, c.integrity);
+    assert_eq!(a.size, 3);
+}
+
+#[test]
+fn hash_goes_before_the_extension_of_the_file_name() {
+    let digest = [0xab; 32];
+    assert_eq!(
+        hashed_path("imgs/26_angel_1_1.png", &digest),
+        "imgs/26_angel_1_1.abababab.png"
+    );
+    assert_eq!(
+        hashed_path("imgs.d/atlas", &digest),
+        "imgs.d/atlas.abababab"
+    );
+    assert_eq!(
+        integrity(&digest),
+        "sha256-q6urq6urq6urq6urq6urq6urq6urq6urq6urq6urq6s="
+    );
+}
+
+#[test]
+fn written_manifest_resolves_the_images_the_game_loads() {
+    let dir = temp_dir();
+    let out = dir.path();
+    let manifest = build_manifest(&imgs(), out).unwrap();
+    let path = out.join("assets.json");
+    write_manifest(&manifest, &path).unwrap();
+
+    let loaded = AssetManifest::from_json(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(loaded, manifest);
+    for set in ["26_angel", "27_devil"].iter() {
+        let name = image_name(set, 9, 23);
+        let entry = loaded.get(&name).unwrap();
+        let original = fs::read(imgs().join(format!("{}_24_10.png", set))).unwrap();
+        let copied = fs::read(out.join(loaded.resolve(&name))).unwrap();
+        assert_ne!(loaded.resolve(&name), name);
+        assert_eq!(copied, original);
+        assert_eq!(entry.size, original.len() as u64);
+        assert_eq!(entry, &asset_entry(&name, &original));
+    }
+    // 載っていない名前はそのまま
+    assert_eq!(loaded.resolve("imgs/unknown.png"), "imgs/unknown.png");
+}
+
+#[test]
+fn manifest_with_another_version_is_rejected() {
+    assert!(AssetManifest::from_json(r#"{"version": 2, "assets": {}}"#).is_err());
+    assert!(AssetManifest::from_json(r#"{"version": 1, "assets": {}}"#)
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,268 @@
+//! レベルのJSONファイルを検証する
+//! ゲームに読み込ませる前に、盤面の大きさや画像、クリアできるかどうかを確認する
+mod source;
+
+pub use crate::source::Position;
+
+use crate::source::SourceMap;
+use std::fmt;
+use std::path::Path;
+use tora_breakout_wasm::assets::image_file_name;
+use tora_breakout_wasm::bricks::BrickKind;
+use tora_breakout_wasm::consts::{BRICK_COLUMN_COUNT, BRICK_ROW_COUNT, BRICK_WIDTH, FIELD_WIDTH};
+use tora_breakout_wasm::level::{Level, EMPTY_SYMBOL};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    // ゲームは動くが意図と違っていそうなもの
+    Warning,
+}
+
+/// 1件の指摘
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub position: Position,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(position: Position, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            position,
+            message,
+        }
+    }
+
+    fn warning(position: Position, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            position,
+            message,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// "行:列: error: メッセージ" 前にファイル名を付けて出す
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.position.line, self.position.column, severity, self.message
+        )
+    }
+}
+
+/// textはレベルのJSON、imgsはブロックの画像を置いているディレクトリ
+pub fn validate(text: &str, imgs: &Path) -> Vec<Diagnostic> {
+    let level: Level = match serde_json::from_str(text) {
+        Ok(level) => level,
+        Err(e) => {
+            // serde_jsonのメッセージの後ろの位置は自分で出すので外す
+            let message = e.to_string();
+            let message = match message.rfind(" at line ") {
+                Some(i) => message[..i].to_string(),
+                None => message,
+            };
+            let position = Position {
+                line: e.line().max(1),
+                column: e.column().max(1),
+            };
+            return vec![Diagnostic::error(
+                position,
+                format!("invalid level: {}", message),
+            )];
+        }
+    };
+    let source = SourceMap::new(text);
+    let mut diagnostics = Vec::new();
+    if level.get_name().is_empty() {
+        diagnostics.push(Diagnostic::warning(
+            source.key("name"),
+            "level name is empty, exported files will be named \".json\"".to_string(),
+        ));
+    }
+    check_grid(&level, &source, &mut diagnostics);
+    check_image_set(
+        &level,
+        level.get_cover(),
+        true,
+        imgs,
+        &source,
+        &mut diagnostics,
+    );
+    check_image_set(
+        &level,
+        level.get_hidden(),
+        false,
+        imgs,
+        &source,
+        &mut diagnostics,
+    );
+    check_breakable(&level, &source, &mut diagnostics);
+    check_reachable(&level, &source, &mut diagnostics);
+    diagnostics
+}
+
+/// 盤面がプレイフィールドに収まっていて、知らない文字がないこと
+fn check_grid(level: &Level, source: &SourceMap, diagnostics: &mut Vec<Diagnostic>) {
+    let rows = level.get_rows();
+    if rows.len() > BRICK_ROW_COUNT {
+        diagnostics.push(Diagnostic::error(
+            source.row(BRICK_ROW_COUNT),
+            format!(
+                "level has {} rows but at most {} fit in the playfield",
+                rows.len(),
+                BRICK_ROW_COUNT
+            ),
+        ));
+    }
+    let widest = level.get_column_count();
+    for (r, row) in rows.iter().enumerate() {
+        let columns = row.chars().count();
+        let width = columns as f64 * BRICK_WIDTH;
+        if width > FIELD_WIDTH as f64 {
+            diagnostics.push(Diagnostic::error(
+                source.row(r),
+                format!(
+                    "row {} is {}px wide ({} x {}px) but the playfield is {}px",
+                    r + 1,
+                    width,
+                    columns,
+                    BRICK_WIDTH,
+                    FIELD_WIDTH
+                ),
+            ));
+        } else if columns < widest {
+            diagnostics.push(Diagnostic::warning(
+                source.row(r),
+                format!(
+                    "row {} has {} cells but the widest row has {}, the rest are left empty",
+                    r + 1,
+                    columns,
+                    widest
+                ),
+            ));
+        }
+        for (c, symbol) in row.chars().enumerate() {
+            if symbol != EMPTY_SYMBOL && BrickKind::from_symbol(symbol).is_none() {
+                diagnostics.push(Diagnostic::error(
+                    source.cell(r, c),
+                    format!(
+                        "unknown brick '{}', expected {}, {}, {} or {}",
+                        symbol,
+                        BrickKind::Normal.get_symbol(),
+                        BrickKind::Hard.get_symbol(),
+                        BrickKind::Unbreakable.get_symbol(),
+                        EMPTY_SYMBOL
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+/// ゲームが読み込む画像が全部imgs/にあること
+/// 表の絵はブロックのあるマス、裏の絵は壊れないブロック以外の全部のマスで使う
+fn check_image_set(
+    level: &Level,
+    set: &str,
+    cover: bool,
+    imgs: &Path,
+    source: &SourceMap,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let key = if cover { "cover" } else { "hidden" };
+    let mut missing = Vec::new();
+    let mut found = false;
+    for row in 0..BRICK_ROW_COUNT {
+        for col in 0..BRICK_COLUMN_COUNT {
+            let kind = level.get_cell(col, row);
+            let used = if cover {
+                kind.is_some()
+            } else {
+                kind.map(|k| k.is_breakable()).unwrap_or(true)
+            };
+            if !used {
+                continue;
+            }
+            let file_name = image_file_name(set, col, row);
+            if imgs.join(&file_name).is_file() {
+                found = true;
+            } else {
+                missing.push(file_name);
+            }
+        }
+    }
+    if missing.is_empty() {
+        return;
+    }
+    let message = if !found {
+        format!(
+            "{} image set '{}' does not exist in {}",
+            key,
+            set,
+            imgs.display()
+        )
+    } else if missing.len() == 1 {
+        format!(
+            "{} image set '{}' is missing {}",
+            key,
+            set,
+            imgs.join(&missing[0]).display()
+        )
+    } else {
+        format!(
+            "{} image set '{}' is missing {} and {} more",
+            key,
+            set,
+            imgs.join(&missing[0]).display(),
+            missing.len() - 1
+        )
+    };
+    diagnostics.push(Diagnostic::error(source.key(key), message));
+}
+
+/// 壊せるブロックがなければクリアできない
+fn check_breakable(level: &Level, source: &SourceMap, diagnostics: &mut Vec<Diagnostic>) {
+    let breakable = cells(level).any(|(_, _, kind)| kind.is_breakable());
+    if !breakable {
+        diagnostics.push(Diagnostic::error(
+            source.key("rows"),
+            "level has no breakable bricks, so it can never be cleared".to_string(),
+        ));
+    }
+}
+
+/// 壊れないブロックに囲まれていてボールが届かないブロックがないこと
+fn check_reachable(level: &Level, source: &SourceMap, diagnostics: &mut Vec<Diagnostic>) {
+    for (col, row) in level.get_unreachable_bricks() {
+        diagnostics.push(Diagnostic::error(
+            source.cell(row, col),
+            format!(
+                "brick at row {}, column {} is enclosed by unbreakable bricks and can never be hit",
+                row + 1,
+                col + 1
+            ),
+        ));
+    }
+}
+
+/// プレイフィールドに収まるマスのブロック 行ごとに上から
+fn cells(level: &Level) -> impl Iterator<Item = (usize, usize, BrickKind)> + '_ {
+    (0..BRICK_ROW_COUNT).flat_map(move |row| {
+        (0..BRICK_COLUMN_COUNT)
+            .filter_map(move |col| level.get_cell(col, row).map(|kind| (col, row, kind)))
+    })
+}
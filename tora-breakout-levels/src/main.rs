@@ -0,0 +1,61 @@
+//! tora-breakout-levels [--imgs DIR] FILE...
+//! レベルのJSONファイルを検証して、問題があれば"ファイル:行:列: error: メッセージ"の形で出す
+//! エラーが1つでもあれば終了コードは1、ファイルが読めないなど使い方の間違いは2
+use clap::Parser;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use tora_breakout_levels::validate;
+
+/// Validates tora-breakout level files.
+#[derive(Parser)]
+#[command(name = "tora-breakout-levels")]
+struct Args {
+    /// directory with the brick images
+    #[arg(long, value_name = "DIR", default_value = "imgs", value_parser = directory)]
+    imgs: PathBuf,
+    /// level files to check
+    #[arg(value_name = "FILE", required = true)]
+    files: Vec<PathBuf>,
+}
+
+fn directory(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Err(format!("{} is not a directory", value))
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut errors = 0;
+    let mut warnings = 0;
+    for file in args.files.iter() {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("{}: {}", file.display(), e);
+                process::exit(2);
+            }
+        };
+        for diagnostic in validate(&text, &args.imgs) {
+            eprintln!("{}:{}", file.display(), diagnostic);
+            if diagnostic.is_error() {
+                errors += 1;
+            } else {
+                warnings += 1;
+            }
+        }
+    }
+    eprintln!(
+        "checked {} file(s): {} error(s), {} warning(s)",
+        args.files.len(),
+        errors,
+        warnings
+    );
+    if errors > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,139 @@
+/// ファイルの中の位置 行も列も1始まり
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub const START: Position = Position { line: 1, column: 1 };
+}
+
+/// JSONの中の文字列リテラル offsetは開きの"のバイト位置、depthはそこでの{と[の深さ
+struct StringLiteral {
+    offset: usize,
+    value: String,
+    depth: usize,
+}
+
+/// レベルのJSONのどこに何が書かれているか
+/// serde_jsonは位置を覚えていないので、エラーを出す場所は文字列リテラルを拾い直して探す
+pub struct SourceMap<'a> {
+    text: &'a str,
+    strings: Vec<StringLiteral>,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(text: &'a str) -> SourceMap<'a> {
+        SourceMap {
+            text,
+            strings: scan_strings(text),
+        }
+    }
+
+    /// トップレベルのキーの位置 見つからなければファイルの先頭
+    pub fn key(&self, key: &str) -> Position {
+        self.key_index(key)
+            .map(|i| self.position(self.strings[i].offset))
+            .unwrap_or(Position::START)
+    }
+
+    /// rowsのindex番目の文字列の位置
+    pub fn row(&self, index: usize) -> Position {
+        self.row_literal(index)
+            .map(|s| self.position(s.offset))
+            .unwrap_or_else(|| self.key("rows"))
+    }
+
+    /// rowsのrow番目の文字列のcol番目の文字の位置
+    pub fn cell(&self, row: usize, col: usize) -> Position {
+        match self.row_literal(row) {
+            Some(s) => {
+                let position = self.position(s.offset);
+                Position {
+                    line: position.line,
+                    column: position.column + 1 + col,
+                }
+            }
+            None => self.key("rows"),
+        }
+    }
+
+    fn key_index(&self, key: &str) -> Option<usize> {
+        self.strings
+            .iter()
+            .position(|s| s.depth == 1 && s.value == key && self.next_char(s.offset) == Some(':'))
+    }
+
+    /// rowsのキーの後、次のトップレベルの文字列までにある配列の中の文字列
+    fn row_literal(&self, index: usize) -> Option<&StringLiteral> {
+        let start = self.key_index("rows")? + 1;
+        self.strings[start..]
+            .iter()
+            .take_while(|s| s.depth > 1)
+            .filter(|s| s.depth == 2)
+            .nth(index)
+    }
+
+    /// 文字列リテラルの閉じの"の後の空白でない文字
+    fn next_char(&self, offset: usize) -> Option<char> {
+        let rest = &self.text[offset + 1..];
+        let mut escaped = false;
+        let mut chars = rest.char_indices();
+        for (i, c) in chars.by_ref() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                return rest[i + 1..].chars().find(|c| !c.is_whitespace());
+            }
+        }
+        None
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let before = &self.text[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        Position {
+            line,
+            column: self.text[line_start..offset].chars().count() + 1,
+        }
+    }
+}
+
+fn scan_strings(text: &str) -> Vec<StringLiteral> {
+    let mut strings = Vec::new();
+    let mut depth: usize = 0;
+    let mut chars = text.char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth = depth.saturating_sub(1),
+            '"' => {
+                let mut value = String::new();
+                let mut escaped = false;
+                for (_, c) in chars.by_ref() {
+                    if escaped {
+                        value.push(c);
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '"' {
+                        break;
+                    } else {
+                        value.push(c);
+                    }
+                }
+                strings.push(StringLiteral {
+                    offset,
+                    value,
+                    depth,
+                });
+            }
+            _ => {}
+        }
+    }
+    strings
+}
@@ -0,0 +1,136 @@
+//! 小さなレベルのJSONを検証して、出てくる指摘と位置を確認する
+
+use tora_breakout_levels::{validate, Diagnostic, Position};
+use tora_breakout_test_support::imgs;
+
+fn errors(text: &str) -> Vec<Diagnostic> {
+    validate(text, &imgs())
+        .into_iter()
+        .filter(|d| d.is_error())
+        .collect()
+}
+
+#[test]
+fn accepts_a_valid_level() {
+    let text = r##"{
+  "name": "valid",
+  "cover": "27_devil",
+  "hidden": "26_angel",
+  "reveal": "Flip",
+  "rows": ["NNNNNNNNNN", "H#H#H#H#H#", ".........."]
+}"##;
+    assert_eq!(validate(text, &imgs()), Vec::new());
+}
+
+#[test]
+fn accepts_the_default_layout() {
+    let text =
+        r#"{ "name": "default", "cover": "27_devil", "hidden": "26_angel", "reveal": "Wipe" }"#;
+    assert_eq!(validate(text, &imgs()), Vec::new());
+}
+
+#[test]
+fn reports_syntax_errors_with_position() {
+    let text = "{\n  \"name\": \"broken\",\n  \"cover\": 27\n}";
+    let errors = errors(text);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].position,
+        Position {
+            line: 3,
+            column: 13
+        }
+    );
+    assert!(errors[0].message.starts_with("invalid level: invalid type"));
+}
+
+#[test]
+fn reports_rows_wider_than_the_playfield() {
+    let text = r#"{
+  "name": "wide",
+  "cover": "27_devil",
+  "hidden": "26_angel",
+  "reveal": "Flip",
+  "rows": [
+    "NNNNNNNNNN",
+    "NNNNNNNNNNN"
+  ]
+}"#;
+    let errors = errors(text);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].position, Position { line: 8, column: 5 });
+    assert_eq!(
+        errors[0].message,
+        "row 2 is 528px wide (11 x 48px) but the playfield is 480px"
+    );
+}
+
+#[test]
+fn reports_unknown_bricks_and_missing_image_sets() {
+    let text = r#"{
+  "name": "typo",
+  "cover": "28_cat",
+  "hidden": "26_angel",
+  "reveal": "Flip",
+  "rows": ["NNxN"]
+}"#;
+    let errors = errors(text);
+    assert_eq!(errors.len(), 2);
+    assert_eq!(
+        errors[0].position,
+        Position {
+            line: 6,
+            column: 15
+        }
+    );
+    assert!(errors[0].message.starts_with("unknown brick 'x'"));
+    assert_eq!(errors[1].position, Position { line: 3, column: 3 });
+    assert!(errors[1]
+        .message
+        .starts_with("cover image set '28_cat' does not exist"));
+}
+
+#[test]
+fn reports_levels_that_cannot_be_cleared() {
+    let text = r##"{
+  "name": "solid",
+  "cover": "27_devil",
+  "hidden": "26_angel",
+  "reveal": "Flip",
+  "rows": [".##.", "#..#"]
+}"##;
+    let errors = errors(text);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].message,
+        "level has no breakable bricks, so it can never be cleared"
+    );
+}
+
+#[test]
+fn reports_bricks_enclosed_by_unbreakable_bricks() {
+    let text = r##"{
+  "name": "vault",
+  "cover": "27_devil",
+  "hidden": "26_angel",
+  "reveal": "Flip",
+  "rows": [
+    "NNN###NNNN",
+    "NNN#H#NNNN",
+    "NNN###NNNN"
+  ]
+}"##;
+    let errors = errors(text);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(
+        errors[0].position,
+        Position {
+            line: 8,
+            column: 10
+        }
+    );
+    assert_eq!(
+        errors[0].message,
+        "brick at row 2, column 5 is enclosed by unbreakable bricks and can never be hit"
+    );
+}
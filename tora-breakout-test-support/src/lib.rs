@@ -0,0 +1,16 @@
+//! ツールのクレートのテストで共通に使うもの
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// リポジトリのimgs/ ゲームが読むブロックの画像が入っている
+pub fn imgs() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../imgs")
+}
+
+/// テストごとに別の一時ディレクトリ 捨てると中身ごと消える
+pub fn temp_dir() -> TempDir {
+    tempfile::Builder::new()
+        .prefix("tora-breakout-")
+        .tempdir()
+        .expect("failed to create a temporary directory")
+}
@@ -0,0 +1,246 @@
+//! 1枚の絵をブロックの大きさのタイルに切り分ける
+//! タイルはget_imageが読む{set}_{行}_{列}.pngの名前で書き出すか、1枚のアトラスとJSONのマニフェストにまとめる
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tora_breakout_wasm::assets::image_file_name;
+use tora_breakout_wasm::consts::{BRICK_COLUMN_COUNT, BRICK_HEIGHT, BRICK_ROW_COUNT, BRICK_WIDTH};
+use tora_breakout_wasm::software_renderer::RgbaImage;
+
+/// 切り分け方 既定はゲームの盤面と同じ10列 x 24行、48 x 20px
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+impl Grid {
+    pub fn get_width(&self) -> u32 {
+        self.columns * self.tile_width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.rows * self.tile_height
+    }
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid {
+            columns: BRICK_COLUMN_COUNT as u32,
+            rows: BRICK_ROW_COUNT as u32,
+            tile_width: BRICK_WIDTH as u32,
+            tile_height: BRICK_HEIGHT as u32,
+        }
+    }
+}
+
+/// 絵のセットの名前はファイル名の先頭になるので、英数字と_と-だけにする
+pub fn check_set_name(set: &str) -> Result<(), String> {
+    if set.is_empty() {
+        return Err("set name is empty".to_string());
+    }
+    match set
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '_' || *c == '-'))
+    {
+        Some(c) => Err(format!(
+            "set name '{}' contains '{}', use only letters, digits, '_' and '-'",
+            set, c
+        )),
+        None => Ok(()),
+    }
+}
+
+/// 縦横比を保って盤面がちょうど埋まる大きさにし、はみ出した分は真ん中に合わせて切り落とす
+pub fn resize_to_fill(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    let source_width = image.get_width() as f64;
+    let source_height = image.get_height() as f64;
+    let scale = (width as f64 / source_width).max(height as f64 / source_height);
+    // 出力の1ピクセルが覆う元の絵の幅
+    let step = 1.0 / scale;
+    let left = (source_width - width as f64 * step) / 2.0;
+    let top = (source_height - height as f64 * step) / 2.0;
+    let mut resized = RgbaImage::new(width, height);
+    for y in 0..height {
+        let y0 = top + y as f64 * step;
+        for x in 0..width {
+            let x0 = left + x as f64 * step;
+            resized.set_pixel(x, y, average(image, x0, y0, x0 + step, y0 + step));
+        }
+    }
+    resized
+}
+
+/// 元の絵の(x0, y0)-(x1, y1)の平均の色 ピクセルは重なっている面積で、色はさらにアルファで重み付けする
+fn average(image: &RgbaImage, x0: f64, y0: f64, x1: f64, y1: f64) -> [u8; 4] {
+    let mut sum = [0.0; 4];
+    let mut area = 0.0;
+    let rows = y0.floor().max(0.0) as u32..(y1.ceil() as u32).min(image.get_height());
+    for py in rows {
+        let dy = y1.min(py as f64 + 1.0) - y0.max(py as f64);
+        let columns = x0.floor().max(0.0) as u32..(x1.ceil() as u32).min(image.get_width());
+        for px in columns {
+            let dx = x1.min(px as f64 + 1.0) - x0.max(px as f64);
+            let weight = dx * dy;
+            if weight <= 0.0 {
+                continue;
+            }
+            let pixel = image.get_pixel(px, py);
+            let alpha = pixel[3] as f64 * weight;
+            for i in 0..3 {
+                sum[i] += pixel[i] as f64 * alpha;
+            }
+            sum[3] += alpha;
+            area += weight;
+        }
+    }
+    if sum[3] <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+    [
+        (sum[0] / sum[3]).round() as u8,
+        (sum[1] / sum[3]).round() as u8,
+        (sum[2] / sum[3]).round() as u8,
+        (sum[3] / area).round() as u8,
+    ]
+}
+
+pub fn crop(image: &RgbaImage, x: u32, y: u32, width: u32, height: u32) -> RgbaImage {
+    let mut cropped = RgbaImage::new(width, height);
+    for dy in 0..height {
+        for dx in 0..width {
+            cropped.set_pixel(dx, dy, image.get_pixel(x + dx, y + dy));
+        }
+    }
+    cropped
+}
+
+/// 絵を盤面の大きさに合わせてから切り分ける (列, 行, タイル)を行ごとに上から返す
+pub fn slice(image: &RgbaImage, grid: &Grid) -> Vec<(u32, u32, RgbaImage)> {
+    let picture = resize_to_fill(image, grid.get_width(), grid.get_height());
+    let mut tiles = Vec::new();
+    for row in 0..grid.rows {
+        for col in 0..grid.columns {
+            let tile = crop(
+                &picture,
+                col * grid.tile_width,
+                row * grid.tile_height,
+                grid.tile_width,
+                grid.tile_height,
+            );
+            tiles.push((col, row, tile));
+        }
+    }
+    tiles
+}
+
+/// タイルを1枚ずつoutに書き出して、書いたファイルを返す
+pub fn write_tiles(
+    image: &RgbaImage,
+    set: &str,
+    grid: &Grid,
+    out: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    check_set_name(set)?;
+    fs::create_dir_all(out).map_err(|e| format!("{}: {}", out.display(), e))?;
+    let mut written = Vec::new();
+    for (col, row, tile) in slice(image, grid) {
+        let path = out.join(image_file_name(set, col as usize, row as usize));
+        write_png(&path, &tile)?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// 盤面の大きさにした絵を{set}.pngに、各タイルの位置を{set}.jsonに書き出す
+pub fn write_atlas(
+    image: &RgbaImage,
+    set: &str,
+    grid: &Grid,
+    out: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    check_set_name(set)?;
+    fs::create_dir_all(out).map_err(|e| format!("{}: {}", out.display(), e))?;
+    // タイルは隙間なく並べるので、盤面の大きさにした絵がそのままアトラスになる
+    let picture = resize_to_fill(image, grid.get_width(), grid.get_height());
+    let image_path = out.join(format!("{}.png", set));
+    write_png(&image_path, &picture)?;
+    let manifest_path = out.join(format!("{}.json", set));
+    let manifest =
+        serde_json::to_string_pretty(&atlas_manifest(set, grid)).map_err(|e| e.to_string())?;
+    fs::write(&manifest_path, manifest + "\n")
+        .map_err(|e| format!("{}: {}", manifest_path.display(), e))?;
+    Ok(vec![image_path, manifest_path])
+}
+
+/// アトラスのマニフェスト タイルのnameはget_imageが読むファイル名と同じ
+pub fn atlas_manifest(set: &str, grid: &Grid) -> serde_json::Value {
+    let mut tiles = Vec::new();
+    for row in 0..grid.rows {
+        for col in 0..grid.columns {
+            tiles.push(serde_json::json!({
+                "name": image_file_name(set, col as usize, row as usize),
+                "row": row + 1,
+                "col": col + 1,
+                "x": col * grid.tile_width,
+                "y": row * grid.tile_height,
+                "width": grid.tile_width,
+                "height": grid.tile_height,
+            }));
+        }
+    }
+    serde_json::json!({
+        "set": set,
+        "image": format!("{}.png", set),
+        "columns": grid.columns,
+        "rows": grid.rows,
+        "tileWidth": grid.tile_width,
+        "tileHeight": grid.tile_height,
+        "tiles": tiles,
+    })
+}
+
+/// グレースケールやパレットのPNGもRGBA8にして読む
+pub fn read_png(path: &Path) -> Result<RgbaImage, String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::open(path).map_err(|e| error(&e))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| error(&e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| error(&e))?;
+    buf.truncate(info.buffer_size());
+    let pixels: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks(3)
+            .flat_map(|p| vec![p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks(2)
+            .flat_map(|p| vec![p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&g| vec![g, g, g, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(error(&"indexed colors could not be expanded"));
+        }
+    };
+    RgbaImage::from_pixels(info.width, info.height, pixels)
+        .ok_or_else(|| error(&"unexpected image size"))
+}
+
+pub fn write_png(path: &Path, image: &RgbaImage) -> Result<(), String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::create(path).map_err(|e| error(&e))?;
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(file), image.get_width(), image.get_height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+    writer
+        .write_image_data(image.get_pixels())
+        .map_err(|e| error(&e))
+}
@@ -0,0 +1,95 @@
+//! tora-breakout-tiles [options] SOURCE SET
+//! 1枚のPNGを盤面の大きさに合わせて、ブロックのタイル{SET}_{行}_{列}.pngに切り分ける
+use clap::Parser;
+use std::path::PathBuf;
+use std::process;
+use tora_breakout_tiles::{read_png, write_atlas, write_tiles, Grid};
+use tora_breakout_wasm::assets::image_file_name;
+
+/// Slices the PNG picture SOURCE into brick tiles named SET_ROW_COL.png.
+/// The picture is scaled to cover the whole grid and centered; the overflow is cropped.
+#[derive(Parser)]
+#[command(name = "tora-breakout-tiles")]
+struct Args {
+    /// PNG picture to slice
+    #[arg(value_name = "SOURCE")]
+    source: PathBuf,
+    /// name of the picture set, e.g. 28_cat
+    #[arg(value_name = "SET")]
+    set: String,
+    /// output directory
+    #[arg(long, value_name = "DIR", default_value = "imgs")]
+    out: PathBuf,
+    /// number of columns
+    #[arg(long, value_name = "N", default_value_t = Grid::default().columns, value_parser = positive)]
+    columns: u32,
+    /// number of rows
+    #[arg(long, value_name = "N", default_value_t = Grid::default().rows, value_parser = positive)]
+    rows: u32,
+    /// tile width
+    #[arg(long, value_name = "PX", default_value_t = Grid::default().tile_width, value_parser = positive)]
+    tile_width: u32,
+    /// tile height
+    #[arg(long, value_name = "PX", default_value_t = Grid::default().tile_height, value_parser = positive)]
+    tile_height: u32,
+    /// write one SET.png atlas and a SET.json manifest instead of tiles
+    #[arg(long)]
+    atlas: bool,
+    /// overwrite an existing set
+    #[arg(long)]
+    force: bool,
+}
+
+fn positive(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err("expected a positive number".to_string()),
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let grid = Grid {
+        columns: args.columns,
+        rows: args.rows,
+        tile_width: args.tile_width,
+        tile_height: args.tile_height,
+    };
+    let (out, set) = (&args.out, &args.set);
+
+    // 既にある絵を間違えて上書きしないようにする
+    let existing = if args.atlas {
+        out.join(format!("{}.json", set))
+    } else {
+        out.join(image_file_name(set, 0, 0))
+    };
+    if existing.exists() && !args.force {
+        fail(&format!(
+            "{} already exists, use --force to overwrite the set",
+            existing.display()
+        ));
+    }
+
+    let image = read_png(&args.source).unwrap_or_else(|e| fail(&e));
+    let written = if args.atlas {
+        write_atlas(&image, set, &grid, out)
+    } else {
+        write_tiles(&image, set, &grid, out)
+    }
+    .unwrap_or_else(|e| fail(&e));
+    println!(
+        "{}: {} x {} grid of {} x {}px tiles, wrote {} file(s) to {}",
+        set,
+        grid.columns,
+        grid.rows,
+        grid.tile_width,
+        grid.tile_height,
+        written.len(),
+        out.display()
+    );
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
@@ -0,0 +1,96 @@
+//! 切り分けたタイルがimgs/の既存のタイルやget_imageの名前の付け方と合うことを確認する
+
+use std::fs;
+use tora_breakout_test_support::{imgs, temp_dir};
+use tora_breakout_tiles::{
+    atlas_manifest, check_set_name, read_png, resize_to_fill, slice, write_atlas, Grid,
+};
+use tora_breakout_wasm::assets::image_file_name;
+use tora_breakout_wasm::software_renderer::RgbaImage;
+
+#[test]
+fn slicing_a_reassembled_set_gives_back_the_same_tiles() {
+    let grid = Grid::default();
+    let mut picture = RgbaImage::new(grid.get_width(), grid.get_height());
+    for row in 0..grid.rows {
+        for col in 0..grid.columns {
+            let tile =
+                read_png(&imgs().join(image_file_name("26_angel", col as usize, row as usize)))
+                    .unwrap();
+            for y in 0..grid.tile_height {
+                for x in 0..grid.tile_width {
+                    picture.set_pixel(
+                        col * grid.tile_width + x,
+                        row * grid.tile_height + y,
+                        tile.get_pixel(x, y),
+                    );
+                }
+            }
+        }
+    }
+    let tiles = slice(&picture, &grid);
+    assert_eq!(tiles.len(), 240);
+    for (col, row, tile) in tiles {
+        let expected =
+            read_png(&imgs().join(image_file_name("26_angel", col as usize, row as usize)))
+                .unwrap();
+        assert_eq!(tile, expected, "tile at column {}, row {}", col, row);
+    }
+}
+
+#[test]
+fn resize_to_fill_keeps_the_aspect_ratio_and_crops_the_center() {
+    // 左右の端だけ赤い4 x 2の絵を2 x 2にすると、真ん中の白いところだけが残る
+    let mut image = RgbaImage::new(4, 2);
+    for y in 0..2 {
+        image.set_pixel(0, y, [255, 0, 0, 255]);
+        image.set_pixel(1, y, [255, 255, 255, 255]);
+        image.set_pixel(2, y, [255, 255, 255, 255]);
+        image.set_pixel(3, y, [255, 0, 0, 255]);
+    }
+    let resized = resize_to_fill(&image, 2, 2);
+    for y in 0..2 {
+        for x in 0..2 {
+            assert_eq!(resized.get_pixel(x, y), [255, 255, 255, 255]);
+        }
+    }
+    // 縮めるときは覆っている範囲の平均の色になる
+    let resized = resize_to_fill(&image, 2, 1);
+    assert_eq!(resized.get_pixel(0, 0), [255, 128, 128, 255]);
+}
+
+#[test]
+fn atlas_manifest_lists_every_tile() {
+    let grid = Grid {
+        columns: 3,
+        rows: 2,
+        tile_width: 48,
+        tile_height: 20,
+    };
+    let dir = temp_dir();
+    let out = dir.path();
+    let written = write_atlas(&RgbaImage::new(10, 10), "99_test", &grid, out).unwrap();
+    assert_eq!(
+        written,
+        vec![out.join("99_test.png"), out.join("99_test.json")]
+    );
+    let atlas = read_png(&out.join("99_test.png")).unwrap();
+    assert_eq!((atlas.get_width(), atlas.get_height()), (144, 40));
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(out.join("99_test.json")).unwrap()).unwrap();
+    assert_eq!(manifest, atlas_manifest("99_test", &grid));
+    let tiles = manifest["tiles"].as_array().unwrap();
+    assert_eq!(tiles.len(), 6);
+    assert_eq!(tiles[5]["name"], "99_test_2_3.png");
+    assert_eq!(
+        (tiles[5]["x"].as_u64(), tiles[5]["y"].as_u64()),
+        (Some(96), Some(20))
+    );
+}
+
+#[test]
+fn set_names_must_be_safe_file_names() {
+    assert!(check_set_name("28_cat").is_ok());
+    assert!(check_set_name("").is_err());
+    assert!(check_set_name("../cat").is_err());
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+/// アセットマニフェストのファイル形式の版
+pub const ASSET_MANIFEST_VERSION: u32 = 1;
+
+/// 1つのアセットの実際の置き場所 pathは内容のハッシュ入りのファイル名で、assetUrlからの相対パス
+/// integrityはSubresource Integrityの形式("sha256-<base64>")で、ブラウザが読み込んだ中身と照らし合わせる
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AssetEntry {
+    pub path: String,
+    pub size: u64,
+    pub integrity: String,
+}
+
+impl AssetEntry {
+    /// 読み込んだ中身の大きさがマニフェストと合っているか nameはエラーに出す論理名
+    pub fn check_size(&self, name: &str, size: u64) -> Result<(), String> {
+        if size != self.size {
+            return Err(format!(
+                "{}: expected {} bytes but got {}",
+                name, self.size, size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 論理名("imgs/27_devil_1_1.png")からハッシュ入りのファイルを引く表 tora-breakout-assetsで作る
+/// 中身が変わるとファイル名も変わるので、ブラウザに古い画像が残っていても使われない
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AssetManifest {
+    version: u32,
+    assets: BTreeMap<String, AssetEntry>,
+}
+
+impl AssetManifest {
+    pub fn new() -> AssetManifest {
+        AssetManifest::default()
+    }
+
+    pub fn from_json(json: &str) -> Result<AssetManifest, String> {
+        let manifest: AssetManifest =
+            serde_json::from_str(json).map_err(|e| format!("invalid asset manifest: {}", e))?;
+        if manifest.version != ASSET_MANIFEST_VERSION {
+            return Err(format!(
+                "unsupported asset manifest version {}, expected {}",
+                manifest.version, ASSET_MANIFEST_VERSION
+            ));
+        }
+        Ok(manifest)
+    }
+
+    pub fn insert(&mut self, name: String, entry: AssetEntry) {
+        self.assets.insert(name, entry);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AssetEntry> {
+        self.assets.get(name)
+    }
+
+    /// 読み込むときのパス 載っていないものは論理名のまま
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.get(name).map(|e| e.path.as_str()).unwrap_or(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+}
+
+impl Default for AssetManifest {
+    fn default() -> AssetManifest {
+        AssetManifest {
+            version: ASSET_MANIFEST_VERSION,
+            assets: BTreeMap::new(),
+        }
+    }
+}
+
+/// セルの画像のファイル名 colとrowは0始まりのマスの位置で、ファイル名は{set}_{行}_{列}.pngの1始まり
+/// 引数は列、行の順だが、ファイル名では行が先に来る
+pub fn image_file_name(set: &str, col: usize, row: usize) -> String {
+    format!("{}_{}_{}.png", set, row + 1, col + 1)
+}
+
+/// セルの画像の論理名 imgs/の下のimage_file_name
+pub fn image_name(set: &str, col: usize, row: usize) -> String {
+    format!("imgs/{}", image_file_name(set, col, row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64) -> AssetEntry {
+        AssetEntry {
+            path: "imgs/26_angel_1_1.0123abcd.png".to_string(),
+            size,
+            integrity: "sha256-AAAA".to_string(),
+        }
+    }
+
+    #[test]
+    fn size_mismatch_is_reported_with_the_name() {
+        assert_eq!(entry(120).check_size("imgs/26_angel_1_1.png", 120), Ok(()));
+        assert_eq!(
+            entry(120).check_size("imgs/26_angel_1_1.png", 80),
+            Err("imgs/26_angel_1_1.png: expected 120 bytes but got 80".to_string())
+        );
+    }
+
+    #[test]
+    fn image_names_put_the_one_based_row_first() {
+        assert_eq!(image_file_name("26_angel", 0, 0), "26_angel_1_1.png");
+        assert_eq!(image_file_name("26_angel", 9, 23), "26_angel_24_10.png");
+        assert_eq!(image_name("27_devil", 2, 5), "imgs/27_devil_6_3.png");
+    }
+
+    #[test]
+    fn unknown_names_resolve_to_themselves() {
+        let mut manifest = AssetManifest::new();
+        manifest.insert("imgs/26_angel_1_1.png".to_string(), entry(120));
+        assert_eq!(
+            manifest.resolve("imgs/26_angel_1_1.png"),
+            "imgs/26_angel_1_1.0123abcd.png"
+        );
+        assert_eq!(
+            manifest.resolve("imgs/26_angel_1_2.png"),
+            "imgs/26_angel_1_2.png"
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use crate::consts::{BRICK_ROW_COUNT, MUSIC_VOLUME};
+use crate::events::GameEvent;
+use crate::music::{builtin_tracks, Sequencer};
+use crate::settings::Settings;
+use std::cell::{Cell, Ref, RefCell};
+use wasm_bindgen::JsValue;
+use web_sys::{console, AudioContext, AudioNode, GainNode, OscillatorType};
+
+/// 波形 OscillatorTypeをそのまま持つとテストや比較がしにくいので分ける
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wave {
+    Sine,
+    Square,
+    Triangle,
+    Sawtooth,
+}
+
+impl Wave {
+    pub fn from_name(name: &str) -> Option<Wave> {
+        match name {
+            "sine" => Some(Wave::Sine),
+            "square" => Some(Wave::Square),
+            "triangle" => Some(Wave::Triangle),
+            "sawtooth" => Some(Wave::Sawtooth),
+            _ => None,
+        }
+    }
+
+    fn to_oscillator_type(self) -> OscillatorType {
+        match self {
+            Wave::Sine => OscillatorType::Sine,
+            Wave::Square => OscillatorType::Square,
+            Wave::Triangle => OscillatorType::Triangle,
+            Wave::Sawtooth => OscillatorType::Sawtooth,
+        }
+    }
+}
+
+/// 1音 周波数をfrequencyからend_frequencyへ滑らせ、音量はattackで立ち上げて指数的に減衰させる
+/// 時間はすべて秒で、startは鳴らし始めからのずらし
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    wave: Wave,
+    frequency: f64,
+    end_frequency: f64,
+    start: f64,
+    attack: f64,
+    duration: f64,
+    volume: f64,
+}
+
+impl Tone {
+    pub fn new(wave: Wave, frequency: f64, duration: f64, volume: f64) -> Tone {
+        Tone {
+            wave,
+            frequency,
+            end_frequency: frequency,
+            start: 0.0,
+            attack: 0.005,
+            duration,
+            volume,
+        }
+    }
+
+    fn slide_to(mut self, end_frequency: f64) -> Tone {
+        self.end_frequency = end_frequency;
+        self
+    }
+
+    fn delay(mut self, start: f64) -> Tone {
+        self.start = start;
+        self
+    }
+}
+
+/// 半音n個分上げた周波数
+fn semitones(base: f64, n: f64) -> f64 {
+    base * 2f64.powf(n / 12.0)
+}
+
+/// イベントごとの効果音
+pub fn tones_for(event: &GameEvent) -> Vec<Tone> {
+    match event {
+        GameEvent::PaddleHit { .. } => vec![Tone::new(Wave::Square, 330.0, 0.08, 0.3)],
+        GameEvent::WallHit { .. } => vec![Tone::new(Wave::Sine, 220.0, 0.05, 0.25)],
+        GameEvent::BrickBroken { row, .. } => {
+            // 上の段ほど高い 一番下の段が440Hzで、一番上の段はその2オクターブ上
+            let from_bottom = (BRICK_ROW_COUNT - 1 - (*row).min(BRICK_ROW_COUNT - 1)) as f64;
+            let frequency = semitones(440.0, from_bottom * 24.0 / (BRICK_ROW_COUNT - 1) as f64);
+            vec![Tone::new(Wave::Square, frequency, 0.1, 0.25).slide_to(frequency * 1.5)]
+        }
+        GameEvent::LifeLost => vec![Tone::new(Wave::Sawtooth, 440.0, 0.6, 0.35).slide_to(55.0)],
+        GameEvent::StageCleared => [0.0, 4.0, 7.0, 12.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| {
+                Tone::new(Wave::Triangle, semitones(523.25, n), 0.25, 0.35).delay(i as f64 * 0.12)
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// toneをstart(AudioContextの時刻)に鳴らすよう予約する
+pub fn schedule_tone(
+    context: &AudioContext,
+    destination: &AudioNode,
+    tone: &Tone,
+    start: f64,
+) -> Result<(), JsValue> {
+    let start = start + tone.start;
+    let end = start + tone.duration;
+    let oscillator = context.create_oscillator()?;
+    oscillator.set_type(tone.wave.to_oscillator_type());
+    let frequency = oscillator.frequency();
+    frequency.set_value_at_time(tone.frequency as f32, start)?;
+    if tone.end_frequency != tone.frequency {
+        frequency.exponential_ramp_to_value_at_time(tone.end_frequency as f32, end)?;
+    }
+    // 音量の包絡線 exponentialは0にできないので小さい値まで落とす
+    let envelope = context.create_gain()?;
+    let gain = envelope.gain();
+    gain.set_value_at_time(0.0001, start)?;
+    gain.exponential_ramp_to_value_at_time(tone.volume as f32, start + tone.attack)?;
+    gain.exponential_ramp_to_value_at_time(0.0001, end)?;
+    oscillator.connect_with_audio_node(&envelope)?;
+    envelope.connect_with_audio_node(destination)?;
+    oscillator.start_with_when(start)?;
+    oscillator.stop_with_when(end)?;
+    Ok(())
+}
+
+struct Output {
+    context: AudioContext,
+    // 効果音もBGMも最後はここを通る 音量とミュートはここで掛ける
+    master: GainNode,
+    music: GainNode,
+}
+
+impl Output {
+    /// AudioContextを作れない環境ではNone
+    fn open(volume: f32) -> Option<Output> {
+        let context = AudioContext::new().ok()?;
+        let master = context.create_gain().ok()?;
+        master.gain().set_value(volume);
+        master
+            .connect_with_audio_node(&context.destination())
+            .ok()?;
+        let music = context.create_gain().ok()?;
+        music.gain().set_value(MUSIC_VOLUME as f32);
+        music.connect_with_audio_node(&master).ok()?;
+        Some(Output {
+            context,
+            master,
+            music,
+        })
+    }
+}
+
+/// Web Audioで効果音とBGMを鳴らす
+/// AudioContextを作れない環境では何もしない
+pub struct Audio {
+    // 初めて有効にしたときに作る
+    output: RefCell<Option<Output>>,
+    sequencer: RefCell<Sequencer>,
+    // falseなら何も鳴らさない 並べて表示するときなどに使う
+    enabled: Cell<bool>,
+    // 設定の音量 ミュートなら0
+    volume: Cell<f32>,
+}
+
+impl Audio {
+    pub fn new(settings: &Settings) -> Audio {
+        let audio = Audio::silent(settings);
+        audio.set_enabled(true);
+        audio
+    }
+
+    /// 音を出さないゲーム用 set_enabled(true)するまでAudioContextを作らない
+    pub fn silent(settings: &Settings) -> Audio {
+        // 壊れていればBGMなしで続ける 組み込みの曲が読めることはテストで確認している
+        let tracks = builtin_tracks().unwrap_or_else(|e| {
+            console::error_1(&JsValue::from_str(&e));
+            Vec::new()
+        });
+        let audio = Audio {
+            output: RefCell::new(None),
+            sequencer: RefCell::new(Sequencer::new(tracks)),
+            enabled: Cell::new(false),
+            volume: Cell::new(0.0),
+        };
+        audio.apply_settings(settings);
+        audio
+    }
+
+    /// 無効にしている間はAudioContextを止めておく
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+        let mut output = self.output.borrow_mut();
+        match output.as_ref() {
+            Some(output) if enabled => {
+                let _ = output.context.resume();
+            }
+            Some(output) => {
+                let _ = output.context.suspend();
+            }
+            None if enabled => *output = Output::open(self.volume.get()),
+            None => {}
+        }
+    }
+
+    /// 無効にしているときはNone
+    fn output(&self) -> Option<Ref<'_, Output>> {
+        if !self.enabled.get() {
+            return None;
+        }
+        Ref::filter_map(self.output.borrow(), |output| output.as_ref()).ok()
+    }
+
+    /// ブラウザはユーザー操作があるまで音を止めているので、クリックやキー入力のときに呼ぶ
+    pub fn resume(&self) {
+        if let Some(output) = self.output() {
+            let _ = output.context.resume();
+        }
+    }
+
+    /// ゲームを片付けるときに呼ぶ AudioContextはページごとに数の上限がある
+    pub fn close(&self) {
+        self.enabled.set(false);
+        if let Some(output) = self.output.borrow_mut().take() {
+            let _ = output.context.close();
+        }
+    }
+
+    pub fn apply_settings(&self, settings: &Settings) {
+        let volume = if settings.get_muted() {
+            0.0
+        } else {
+            settings.get_volume()
+        };
+        self.volume.set(volume as f32);
+        if let Some(output) = self.output.borrow().as_ref() {
+            output.master.gain().set_value(volume as f32);
+        }
+    }
+
+    pub fn handle(&self, event: &GameEvent) {
+        let output = match self.output() {
+            Some(output) => output,
+            None => return,
+        };
+        let now = output.context.current_time();
+        for tone in tones_for(event) {
+            let _ = schedule_tone(&output.context, &output.master, &tone, now);
+        }
+    }
+
+    /// 毎フレーム呼ぶ trackは鳴らしたい曲の名前 違う曲ならクロスフェードで切り替える
+    pub fn update_music(&self, track: &str, intensity: f64) {
+        let output = match self.output() {
+            Some(output) => output,
+            None => return,
+        };
+        let mut sequencer = self.sequencer.borrow_mut();
+        sequencer.play(&output.context, &output.music, track);
+        sequencer.update(&output.context, intensity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::BrickKind;
+
+    fn broken_tone(row: usize) -> Tone {
+        let tones = tones_for(&GameEvent::BrickBroken {
+            col: 0,
+            row,
+            x: 0.0,
+            y: 0.0,
+            kind: BrickKind::Normal,
+        });
+        assert_eq!(tones.len(), 1);
+        tones[0]
+    }
+
+    #[test]
+    fn brick_pitch_spans_two_octaves_from_the_bottom_row() {
+        let bottom = broken_tone(BRICK_ROW_COUNT - 1);
+        let top = broken_tone(0);
+        assert!((bottom.frequency - 440.0).abs() < 1e-9);
+        assert!((top.frequency - 1760.0).abs() < 1e-9);
+        // 範囲外の行は一番下の段と同じ
+        assert_eq!(broken_tone(BRICK_ROW_COUNT + 5), bottom);
+    }
+
+    #[test]
+    fn brick_pitch_rises_row_by_row_and_slides_up() {
+        let mut previous = 0.0;
+        for row in (0..BRICK_ROW_COUNT).rev() {
+            let tone = broken_tone(row);
+            assert!(tone.frequency > previous, "row {}", row);
+            assert!((tone.end_frequency - tone.frequency * 1.5).abs() < 1e-9);
+            previous = tone.frequency;
+        }
+    }
+
+    #[test]
+    fn stage_clear_plays_an_arpeggio() {
+        let tones = tones_for(&GameEvent::StageCleared);
+        assert_eq!(tones.len(), 4);
+        assert!((tones[3].frequency - 2.0 * tones[0].frequency).abs() < 1e-9);
+        assert!(tones.windows(2).all(|w| w[0].start < w[1].start));
+    }
+}
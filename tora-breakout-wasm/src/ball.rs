@@ -1,7 +1,36 @@
-use crate::consts::{BALL_RADIUS, INIT_SPEED};
-use std::f64;
+use crate::consts::{
+    BALL_RADIUS, BALL_TRAIL_CAPACITY, BALL_TRAIL_MIN_LENGTH, INIT_SPEED, MAX_SPEED,
+};
+use crate::renderer::{Renderer, TextAlign};
 use wasm_bindgen::prelude::*;
 
+/// 直近のボールの位置を覚えておくリングバッファ
+#[derive(Debug, Clone, Copy, Default)]
+struct Trail {
+    positions: [(f64, f64); BALL_TRAIL_CAPACITY],
+    // 次に書き込む位置
+    head: usize,
+    len: usize,
+}
+
+impl Trail {
+    fn push(&mut self, x: f64, y: f64) {
+        self.positions[self.head] = (x, y);
+        self.head = (self.head + 1) % BALL_TRAIL_CAPACITY;
+        self.len = (self.len + 1).min(BALL_TRAIL_CAPACITY);
+    }
+
+    /// 新しい方からi番目(0が最新)
+    fn get(&self, i: usize) -> (f64, f64) {
+        self.positions[(self.head + BALL_TRAIL_CAPACITY - 1 - i) % BALL_TRAIL_CAPACITY]
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
+
 #[wasm_bindgen]
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct Ball {
@@ -10,6 +39,8 @@ pub struct Ball {
     x: f64,
     y: f64,
     speed: usize,
+    #[serde(skip)]
+    trail: Trail,
 }
 
 #[wasm_bindgen]
@@ -21,6 +52,7 @@ impl Ball {
             x: x,
             y: y,
             speed: INIT_SPEED,
+            trail: Trail::default(),
         }
     }
 
@@ -65,20 +97,169 @@ impl Ball {
     }
 
     pub fn add_speed(&mut self) {
-        if self.speed >= 80 {
+        if self.speed >= MAX_SPEED {
             return;
         }
         self.speed += 1;
     }
 
-    pub fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d) {
-        ctx.begin_path();
-        ctx.set_stroke_style(&"black".into());
-        ctx.set_line_width(0.3);
-        ctx.arc(self.x, self.y, BALL_RADIUS, 0.0, f64::consts::PI * 2.0)
-            .unwrap();
-        ctx.set_fill_style(&"rgb(255, 255, 255)".into());
-        ctx.fill();
-        ctx.close_path();
+    /// 今の位置を軌跡に足す 1フレームに1回呼ぶ
+    pub fn record_trail(&mut self) {
+        self.trail.push(self.x, self.y);
+    }
+
+    pub fn clear_trail(&mut self) {
+        self.trail.clear();
+    }
+
+    /// 初速を0.0、最高速を1.0とした速さ
+    pub fn get_speed_ratio(&self) -> f64 {
+        (self.speed - INIT_SPEED.min(self.speed)) as f64 / (MAX_SPEED - INIT_SPEED) as f64
+    }
+}
+
+// wasm_bindgenなimplにはジェネリックなメソッドを置けないので分ける
+impl Ball {
+    pub fn draw<R: Renderer>(&self, renderer: &mut R) {
+        self.draw_trail(renderer);
+        renderer.fill_circle(self.x, self.y, BALL_RADIUS, "rgb(255, 255, 255)");
+    }
+
+    /// 速いほど長く赤くなる軌跡 古い位置ほど薄く小さくする
+    fn draw_trail<R: Renderer>(&self, renderer: &mut R) {
+        let ratio = self.get_speed_ratio();
+        let length = BALL_TRAIL_MIN_LENGTH
+            + ((BALL_TRAIL_CAPACITY - BALL_TRAIL_MIN_LENGTH) as f64 * ratio).round() as usize;
+        let length = length.min(self.trail.len);
+        if length == 0 {
+            return;
+        }
+        let color = speed_color(ratio);
+        renderer.save();
+        for i in (0..length).rev() {
+            let (x, y) = self.trail.get(i);
+            let fade = 1.0 - i as f64 / length as f64;
+            renderer.set_global_alpha(0.5 * fade);
+            renderer.fill_circle(x, y, BALL_RADIUS * fade, &color);
+        }
+        renderer.restore();
+    }
+
+    /// HUDの速さゲージ 右下に出す
+    pub fn draw_speed_gauge<R: Renderer>(&self, renderer: &mut R, width: f64, height: f64) {
+        let ratio = self.get_speed_ratio();
+        let gauge_width = 62.0;
+        let left = width - gauge_width - 8.0;
+        let top = height - 24.0;
+        renderer.fill_text(
+            "Speed",
+            left - 6.0,
+            top + 8.0,
+            "12px Arial",
+            "rgb(0, 149, 208)",
+            TextAlign::Right,
+        );
+        renderer.fill_rect(left, top, gauge_width, 8.0, "rgba(255, 255, 255, 0.2)");
+        renderer.fill_rect(left, top, gauge_width * ratio, 8.0, &speed_color(ratio));
+        renderer.stroke_rect(left, top, gauge_width, 8.0, "rgb(0, 149, 208)", 1.0);
+    }
+}
+
+/// 遅いときは白、速くなるほど橙から赤へ
+fn speed_color(ratio: f64) -> String {
+    let ratio = ratio.clamp(0.0, 1.0);
+    let g = 255.0 - 215.0 * ratio;
+    let b = 255.0 * (1.0 - ratio * 2.0).max(0.0);
+    format!("rgb(255, {}, {})", g.round(), b.round())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_renderer::{DrawCommand, RecordingRenderer};
+
+    #[test]
+    fn trail_keeps_the_newest_positions_when_it_wraps() {
+        let mut trail = Trail::default();
+        for i in 0..BALL_TRAIL_CAPACITY + 4 {
+            trail.push(i as f64, 0.0);
+        }
+        assert_eq!(trail.len, BALL_TRAIL_CAPACITY);
+        let newest = (BALL_TRAIL_CAPACITY + 3) as f64;
+        assert_eq!(trail.get(0), (newest, 0.0));
+        assert_eq!(trail.get(BALL_TRAIL_CAPACITY - 1), (4.0, 0.0));
+    }
+
+    #[test]
+    fn cleared_trail_starts_over() {
+        let mut trail = Trail::default();
+        for i in 0..5 {
+            trail.push(i as f64, 0.0);
+        }
+        trail.clear();
+        assert_eq!(trail.len, 0);
+        trail.push(10.0, 20.0);
+        assert_eq!(trail.len, 1);
+        assert_eq!(trail.get(0), (10.0, 20.0));
+    }
+
+    /// 軌跡を全部埋めたボールを描いて、軌跡の円の数を返す
+    fn drawn_trail_length(speed: usize) -> usize {
+        let mut ball = Ball::new(0.0, 0.0, 0.0, 0.0);
+        while ball.get_speed() < speed {
+            ball.add_speed();
+        }
+        for _ in 0..BALL_TRAIL_CAPACITY {
+            ball.record_trail();
+        }
+        let mut renderer = RecordingRenderer::new();
+        ball.draw(&mut renderer);
+        let circles = renderer
+            .get_commands()
+            .iter()
+            .filter(|c| matches!(c, DrawCommand::FillCircle { .. }))
+            .count();
+        // 最後の1つはボール本体
+        circles - 1
+    }
+
+    #[test]
+    fn trail_grows_with_speed() {
+        assert_eq!(drawn_trail_length(INIT_SPEED), BALL_TRAIL_MIN_LENGTH);
+        assert_eq!(drawn_trail_length(MAX_SPEED), BALL_TRAIL_CAPACITY);
+        let middle = drawn_trail_length((INIT_SPEED + MAX_SPEED) / 2);
+        assert!(middle > BALL_TRAIL_MIN_LENGTH && middle < BALL_TRAIL_CAPACITY);
+        let mut previous = 0;
+        for speed in INIT_SPEED..=MAX_SPEED {
+            let length = drawn_trail_length(speed);
+            assert!(length >= previous, "speed {}", speed);
+            previous = length;
+        }
+    }
+
+    #[test]
+    fn trail_is_not_longer_than_what_was_recorded() {
+        let mut ball = Ball::new(0.0, 0.0, 0.0, 0.0);
+        while ball.get_speed() < MAX_SPEED {
+            ball.add_speed();
+        }
+        ball.record_trail();
+        let mut renderer = RecordingRenderer::new();
+        ball.draw(&mut renderer);
+        let circles = renderer
+            .get_commands()
+            .iter()
+            .filter(|c| matches!(c, DrawCommand::FillCircle { .. }))
+            .count();
+        assert_eq!(circles, 2);
+    }
+
+    #[test]
+    fn speed_color_goes_from_white_to_red() {
+        assert_eq!(speed_color(0.0), "rgb(255, 255, 255)");
+        assert_eq!(speed_color(1.0), "rgb(255, 40, 0)");
+        // 範囲外は端の色
+        assert_eq!(speed_color(-1.0), speed_color(0.0));
+        assert_eq!(speed_color(2.0), speed_color(1.0));
     }
 }
@@ -1,36 +1,361 @@
+use crate::assets::{image_name, AssetEntry, AssetManifest};
+use crate::consts::{DEBRIS_SAMPLE_COLS, DEBRIS_SAMPLE_ROWS};
+use std::collections::HashMap;
+use std::future::Future;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Blob, ImageBitmap, Request, RequestInit, RequestMode, Response};
+use web_sys::{
+    console, Blob, CanvasRenderingContext2d, Document, HtmlCanvasElement, ImageBitmap, Request,
+    RequestCache, RequestInit, RequestMode, Response,
+};
 
-pub async fn get_image(
+/// セルの画像を読みに行く先
+/// マニフェストに載っていればハッシュ入りのパスと確かめる値を持ち、載っていなければ今まで通り{set}_{行}_{列}.pngを読む
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageRequest {
+    // エラーに出す論理名
+    pub name: String,
+    pub path: String,
+    pub entry: Option<AssetEntry>,
+}
+
+impl ImageRequest {
+    pub fn new(col: u32, row: u32, filename: &str, manifest: &AssetManifest) -> ImageRequest {
+        let name = image_name(filename, col as usize, row as usize);
+        ImageRequest {
+            path: manifest.resolve(&name).to_string(),
+            entry: manifest.get(&name).cloned(),
+            name,
+        }
+    }
+}
+
+/// マニフェストに載っている画像は中身が合っているか確かめる
+pub async fn get_image(url: &str, request: ImageRequest) -> Result<ImageBitmap, JsValue> {
+    let window = web_sys::window().unwrap();
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+    if let Some(entry) = &request.entry {
+        // ハッシュが合わなければブラウザがfetchを失敗させる
+        opts.set_integrity(&entry.integrity);
+    }
+    let fetch_request = Request::new_with_str_and_init(&format!("{}{}", url, request.path), &opts)?;
+    let resp: Response = JsFuture::from(window.fetch_with_request(&fetch_request))
+        .await?
+        .dyn_into()?;
+    if !resp.ok() {
+        return Err(JsValue::from_str(&format!(
+            "{}: HTTP {}",
+            request.path,
+            resp.status()
+        )));
+    }
+
+    // blobメソッドはResultを返すので?付けてしまう
+    let blob: Blob = JsFuture::from(resp.blob()?).await?.dyn_into()?;
+    if let Some(entry) = &request.entry {
+        entry
+            .check_size(&request.name, blob.size() as u64)
+            .map_err(|e| JsValue::from_str(&e))?;
+    }
+
+    let bitmap = JsFuture::from(window.create_image_bitmap_with_blob(&blob)?).await?;
+    bitmap.dyn_into()
+}
+
+/// セルの画像を読む 失敗したらコンソールに出してNoneを返し、そのセルは空のままにする
+pub async fn load_image(
     col: u32,
     row: u32,
     url: &str,
     filename: &str,
-) -> Result<ImageBitmap, JsValue> {
-    let window = web_sys::window().unwrap();
-    let mut opts = RequestInit::new();
-    opts.method("GET");
-    opts.mode(RequestMode::Cors);
-    let request = Request::new_with_str_and_init(
-        &format!("{}imgs/{}_{}_{}.png", url, filename, row + 1, col + 1),
-        &opts,
+    manifest: &AssetManifest,
+) -> Option<ImageBitmap> {
+    let request = ImageRequest::new(col, row, filename, manifest);
+    load_with(
+        request,
+        |request| get_image(url, request),
+        |e| console::error_1(&e),
     )
-    .unwrap();
-    let request_promise = window.fetch_with_request(&request);
+    .await
+}
 
-    let resp_value = JsFuture::from(request_promise).await?;
-    assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().unwrap();
+/// requestを1回だけ読む
+/// integrityや大きさが合わなかった画像を元のファイル名で読み直すと確かめた意味がなくなるので、失敗しても読み直さない
+async fn load_with<T, E, F>(
+    request: ImageRequest,
+    fetch: impl FnOnce(ImageRequest) -> F,
+    on_error: impl FnOnce(E),
+) -> Option<T>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    match fetch(request).await {
+        Ok(image) => Some(image),
+        Err(e) => {
+            on_error(e);
+            None
+        }
+    }
+}
 
-    // blobメソッドはResultを返すので?付けてしまう
-    let blob_value = JsFuture::from(resp.blob()?).await?;
-    assert!(blob_value.is_instance_of::<Blob>());
-    let blob: Blob = blob_value.dyn_into().unwrap();
-
-    let bitmap_value = JsFuture::from(window.create_image_bitmap_with_blob(&blob)?).await?;
-    assert!(bitmap_value.is_instance_of::<ImageBitmap>());
-    let bitmap: ImageBitmap = bitmap_value.dyn_into().unwrap();
-    Ok(bitmap)
+/// assetUrlからの相対パスでマニフェストを読む マニフェスト自体は毎回サーバーに確認する
+pub async fn get_asset_manifest(url: &str, path: &str) -> Result<AssetManifest, JsValue> {
+    let window = web_sys::window().unwrap();
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_cache(RequestCache::NoCache);
+    let request = Request::new_with_str_and_init(&format!("{}{}", url, path), &opts)?;
+    let resp: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await?
+        .dyn_into()?;
+    if !resp.ok() {
+        return Err(JsValue::from_str(&format!(
+            "{}: HTTP {}",
+            path,
+            resp.status()
+        )));
+    }
+    let text = JsFuture::from(resp.text()?).await?;
+    AssetManifest::from_json(&text.as_string().unwrap_or_default())
+        .map_err(|e| JsValue::from_str(&format!("{}: {}", path, e)))
+}
+
+pub fn image_key(col: usize, row: usize) -> String {
+    format!("{}-{}", col, row)
+}
+
+/// セルごとの画像 差し替えられたセルを覚えておき、ブロック層の部分再描画に使う
+#[derive(Debug, Clone)]
+pub struct BitmapContainer<I> {
+    images: HashMap<String, I>,
+    // replaceで差し替えられる前の画像 切り替えの演出が終わるまで持っておく
+    previous: HashMap<String, I>,
+    changed: Vec<(usize, usize)>,
+    replaced: Vec<(usize, usize)>,
+    // resetのたびに増える 読み込み中に前のレベルの画像が入らないように比べる
+    generation: u32,
+}
+
+impl<I> BitmapContainer<I> {
+    pub fn new() -> BitmapContainer<I> {
+        BitmapContainer {
+            images: HashMap::new(),
+            previous: HashMap::new(),
+            changed: Vec::new(),
+            replaced: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// 画像を全部捨てて世代を進める それより前に読み始めた画像はinsert_forで捨てられる
+    pub fn reset(&mut self) {
+        let generation = self.generation.wrapping_add(1);
+        *self = BitmapContainer::new();
+        self.generation = generation;
+    }
+
+    /// 読み始めたときと同じ世代のときだけinsertする
+    pub fn insert_for(&mut self, generation: u32, col: usize, row: usize, image: I) {
+        if generation == self.generation {
+            self.insert(col, row, image);
+        }
+    }
+
+    /// 読み始めたときと同じ世代のときだけreplaceする
+    pub fn replace_for(&mut self, generation: u32, col: usize, row: usize, image: I) {
+        if generation == self.generation {
+            self.replace(col, row, image);
+        }
+    }
+
+    pub fn insert(&mut self, col: usize, row: usize, image: I) {
+        self.images.insert(image_key(col, row), image);
+        self.changed.push((col, row));
+    }
+
+    /// 前の画像を残したまま差し替える
+    pub fn replace(&mut self, col: usize, row: usize, image: I) {
+        let key = image_key(col, row);
+        if let Some(old) = self.images.insert(key.clone(), image) {
+            self.previous.insert(key, old);
+        }
+        self.changed.push((col, row));
+        self.replaced.push((col, row));
+    }
+
+    pub fn get_previous(&self, col: usize, row: usize) -> Option<&I> {
+        self.previous.get(&image_key(col, row))
+    }
+
+    pub fn remove_previous(&mut self, col: usize, row: usize) {
+        self.previous.remove(&image_key(col, row));
+    }
+
+    /// 前回呼んでからreplaceされたセル
+    pub fn take_replaced(&mut self) -> Vec<(usize, usize)> {
+        std::mem::take(&mut self.replaced)
+    }
+
+    pub fn get(&self, col: usize, row: usize) -> Option<&I> {
+        self.images.get(&image_key(col, row))
+    }
+
+    /// 前回呼んでから画像が変わったセル
+    pub fn take_changed(&mut self) -> Vec<(usize, usize)> {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+impl<I> Default for BitmapContainer<I> {
+    fn default() -> BitmapContainer<I> {
+        BitmapContainer::new()
+    }
+}
+
+/// 画像を小さなキャンバスに縮小して描き、そのピクセルから代表色を拾う
+/// 破片の色に使う ブロックが壊れたときだけ呼ぶのでその都度読み出す
+pub struct ColorSampler {
+    context: CanvasRenderingContext2d,
+}
+
+impl ColorSampler {
+    pub fn new(document: &Document) -> Result<ColorSampler, JsValue> {
+        let canvas = document
+            .create_element("canvas")?
+            .dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_width(DEBRIS_SAMPLE_COLS);
+        canvas.set_height(DEBRIS_SAMPLE_ROWS);
+        let context = canvas
+            .get_context("2d")?
+            .ok_or_else(|| JsValue::from_str("2d context is not supported"))?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+        Ok(ColorSampler { context })
+    }
+
+    /// 透明なピクセルは除く 読み出せなければ空
+    pub fn sample(&self, image: &ImageBitmap) -> Vec<[u8; 3]> {
+        let width = DEBRIS_SAMPLE_COLS as f64;
+        let height = DEBRIS_SAMPLE_ROWS as f64;
+        self.context.clear_rect(0.0, 0.0, width, height);
+        if self
+            .context
+            .draw_image_with_image_bitmap_and_dw_and_dh(image, 0.0, 0.0, width, height)
+            .is_err()
+        {
+            return Vec::new();
+        }
+        let data = match self.context.get_image_data(0.0, 0.0, width, height) {
+            Ok(data) => data.data(),
+            Err(_) => return Vec::new(),
+        };
+        data.chunks(4)
+            .filter(|p| p[3] > 0)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// 待たずに終わるFutureだけを動かす
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        let mut future = pin!(future);
+        match future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+        {
+            Poll::Ready(value) => value,
+            Poll::Pending => panic!("future did not complete"),
+        }
+    }
+
+    fn manifest() -> AssetManifest {
+        let mut manifest = AssetManifest::new();
+        manifest.insert(
+            "imgs/27_devil_1_1.png".to_string(),
+            AssetEntry {
+                path: "imgs/27_devil_1_1.0123abcd.png".to_string(),
+                size: 100,
+                integrity: "sha256-AAAA".to_string(),
+            },
+        );
+        manifest
+    }
+
+    #[test]
+    fn image_in_the_manifest_is_read_by_its_hashed_path() {
+        let request = ImageRequest::new(0, 0, "27_devil", &manifest());
+        assert_eq!(request.name, "imgs/27_devil_1_1.png");
+        assert_eq!(request.path, "imgs/27_devil_1_1.0123abcd.png");
+        assert_eq!(request.entry.unwrap().integrity, "sha256-AAAA");
+
+        let request = ImageRequest::new(1, 0, "27_devil", &manifest());
+        assert_eq!(request.path, "imgs/27_devil_1_2.png");
+        assert_eq!(request.entry, None);
+    }
+
+    #[test]
+    fn failed_integrity_check_does_not_fall_back_to_the_plain_name() {
+        let fetched = RefCell::new(Vec::new());
+        let errors = RefCell::new(Vec::new());
+        let image: Option<()> = block_on(load_with(
+            ImageRequest::new(0, 0, "27_devil", &manifest()),
+            |request| {
+                fetched.borrow_mut().push(request.path);
+                async { Err("integrity mismatch") }
+            },
+            |e| errors.borrow_mut().push(e),
+        ));
+        assert_eq!(image, None);
+        assert_eq!(
+            fetched.into_inner(),
+            vec!["imgs/27_devil_1_1.0123abcd.png".to_string()]
+        );
+        assert_eq!(errors.into_inner(), vec!["integrity mismatch"]);
+    }
+
+    #[test]
+    fn loaded_image_is_returned() {
+        let image = block_on(load_with(
+            ImageRequest::new(0, 0, "27_devil", &manifest()),
+            |request| async move { Ok::<_, ()>(request.path) },
+            |_| panic!("no error"),
+        ));
+        assert_eq!(image, Some("imgs/27_devil_1_1.0123abcd.png".to_string()));
+    }
+
+    #[test]
+    fn images_loaded_before_a_reset_are_dropped() {
+        let mut images = BitmapContainer::new();
+        let old = images.get_generation();
+        images.insert_for(old, 0, 0, "old level");
+        images.reset();
+        assert_eq!(images.get(0, 0), None);
+        assert!(images.take_changed().is_empty());
+
+        // 前のレベルの読み込みが後から終わっても入らない
+        images.insert_for(old, 1, 0, "old level");
+        images.replace_for(old, 1, 0, "old level");
+        assert_eq!(images.get(1, 0), None);
+        assert!(images.take_replaced().is_empty());
+
+        let current = images.get_generation();
+        images.insert_for(current, 1, 0, "new level");
+        images.replace_for(current, 1, 0, "new hidden");
+        assert_eq!(images.get(1, 0), Some(&"new hidden"));
+        assert_eq!(images.get_previous(1, 0), Some(&"new level"));
+    }
 }
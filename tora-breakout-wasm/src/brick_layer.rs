@@ -0,0 +1,120 @@
+use crate::bitmap_container::BitmapContainer;
+use crate::canvas_renderer::CanvasRenderer;
+use crate::consts::{BRICK_HEIGHT, BRICK_WIDTH};
+use crate::renderer::Renderer;
+use crate::Container;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement, ImageBitmap};
+
+/// ブロックだけを描いておくオフスクリーンのキャンバス
+/// ブロックは当たったときしか変わらないので、変わったセルだけ描き直して毎フレームは転送するだけにする
+pub struct BrickLayer {
+    canvas: HtmlCanvasElement,
+    renderer: CanvasRenderer,
+    // 論理サイズ 描画バッファはこれのpixel_ratio倍
+    width: f64,
+    height: f64,
+    pixel_ratio: f64,
+    needs_full_redraw: bool,
+}
+
+impl BrickLayer {
+    pub fn new(document: &Document, width: u32, height: u32) -> Result<BrickLayer, JsValue> {
+        let canvas = document
+            .create_element("canvas")?
+            .dyn_into::<HtmlCanvasElement>()?;
+        canvas.set_width(width);
+        canvas.set_height(height);
+        let context = canvas
+            .get_context("2d")?
+            .ok_or_else(|| JsValue::from_str("2d context is not supported"))?
+            .dyn_into::<CanvasRenderingContext2d>()?;
+        Ok(BrickLayer {
+            canvas,
+            renderer: CanvasRenderer::new(context),
+            width: width as f64,
+            height: height as f64,
+            pixel_ratio: 1.0,
+            needs_full_redraw: true,
+        })
+    }
+
+    /// メインのキャンバスと同じ解像度にする 中身は次のupdateで全部描き直す
+    pub fn set_pixel_ratio(&mut self, pixel_ratio: f64) {
+        self.pixel_ratio = pixel_ratio;
+        self.canvas
+            .set_width((self.width * pixel_ratio).round() as u32);
+        self.canvas
+            .set_height((self.height * pixel_ratio).round() as u32);
+        let _ =
+            self.renderer
+                .get_context()
+                .set_transform(pixel_ratio, 0.0, 0.0, pixel_ratio, 0.0, 0.0);
+        self.invalidate();
+    }
+
+    /// 次のupdateで全部描き直す
+    pub fn invalidate(&mut self) {
+        self.needs_full_redraw = true;
+    }
+
+    /// 状態か画像が変わったセルだけ描き直す 描き直したセル数を返す
+    pub fn update(
+        &mut self,
+        bricks: &mut Container,
+        images: &mut BitmapContainer<ImageBitmap>,
+    ) -> usize {
+        let mut cells = bricks.take_dirty_cells();
+        cells.extend(images.take_changed());
+        if self.needs_full_redraw {
+            self.needs_full_redraw = false;
+            self.renderer.clear_rect(0.0, 0.0, self.width, self.height);
+            bricks.draw(images, &mut self.renderer);
+            return (0..bricks.get_col_len())
+                .map(|c| bricks.get_row_len(c))
+                .sum();
+        }
+        cells.sort_unstable();
+        cells.dedup();
+        for &(col, row) in cells.iter() {
+            self.redraw_cell(col, row, bricks, images);
+        }
+        cells.len()
+    }
+
+    fn redraw_cell(
+        &mut self,
+        col: usize,
+        row: usize,
+        bricks: &Container,
+        images: &BitmapContainer<ImageBitmap>,
+    ) {
+        let brick = bricks.get_brick(col, row);
+        self.renderer.save();
+        self.renderer
+            .clip_rect(brick.get_x(), brick.get_y(), BRICK_WIDTH, BRICK_HEIGHT);
+        self.renderer
+            .clear_rect(brick.get_x(), brick.get_y(), BRICK_WIDTH, BRICK_HEIGHT);
+        // 枠線は隣のセルにも少しはみ出しているので、周りのセルも一緒に描いて切り抜く
+        let last_col = bricks.get_col_len() - 1;
+        for c in col.saturating_sub(1)..=(col + 1).min(last_col) {
+            let last_row = bricks.get_row_len(c) - 1;
+            for r in row.saturating_sub(1)..=(row + 1).min(last_row) {
+                bricks.draw_cell(c, r, images, &mut self.renderer);
+            }
+        }
+        self.renderer.restore();
+    }
+
+    /// メインのキャンバスへ転送する ctxには論理座標の変換がかかっている前提
+    pub fn blit(&self, ctx: &CanvasRenderingContext2d) {
+        let _ = ctx.draw_image_with_html_canvas_element_and_dw_and_dh(
+            &self.canvas,
+            0.0,
+            0.0,
+            self.width,
+            self.height,
+        );
+    }
+}
@@ -1,8 +1,10 @@
 use crate::consts::{
     BRICK_HEIGHT, BRICK_OFFSET_LEFT, BRICK_OFFSET_TOP, BRICK_PADDING, BRICK_WIDTH,
+    HARD_BRICK_POINTS, NORMAL_BRICK_POINTS,
 };
+use crate::level::RevealEffect;
+use crate::renderer::Renderer;
 // use wasm_bindgen::prelude::*;
-use web_sys::ImageBitmap;
 
 // #[wasm_bindgen]
 #[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
@@ -12,6 +14,56 @@ pub enum BrickStatus {
     Dead,
 }
 
+/// ブロックの種類 種類ごとに耐久力と点数が違う
+/// Unbreakableは何回当てても壊れず、クリアの条件にも入らない
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(u8)]
+pub enum BrickKind {
+    Normal,
+    Hard,
+    Unbreakable,
+}
+
+impl BrickKind {
+    pub fn get_life(self) -> u32 {
+        match self {
+            BrickKind::Normal => 1,
+            BrickKind::Hard => 2,
+            BrickKind::Unbreakable => 0,
+        }
+    }
+
+    pub fn is_breakable(self) -> bool {
+        self != BrickKind::Unbreakable
+    }
+
+    pub fn get_points(self) -> u32 {
+        match self {
+            BrickKind::Normal => NORMAL_BRICK_POINTS,
+            BrickKind::Hard => HARD_BRICK_POINTS,
+            BrickKind::Unbreakable => 0,
+        }
+    }
+
+    /// レベルのレイアウトで使う1文字
+    pub fn get_symbol(self) -> char {
+        match self {
+            BrickKind::Normal => 'N',
+            BrickKind::Hard => 'H',
+            BrickKind::Unbreakable => '#',
+        }
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<BrickKind> {
+        match symbol {
+            'N' => Some(BrickKind::Normal),
+            'H' => Some(BrickKind::Hard),
+            '#' => Some(BrickKind::Unbreakable),
+            _ => None,
+        }
+    }
+}
+
 // #[wasm_bindgen]
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct Brick {
@@ -19,6 +71,7 @@ pub struct Brick {
     y: f64,
     status: BrickStatus,
     life: u32,
+    kind: BrickKind,
 }
 
 // TODO: wasm_bindgenなimplとasyncが絡むとエラーになるのでとりあえずコメント
@@ -29,7 +82,8 @@ impl Brick {
             x: x,
             y: y,
             status: status,
-            life: 1,
+            life: BrickKind::Normal.get_life(),
+            kind: BrickKind::Normal,
         }
     }
 
@@ -63,43 +117,175 @@ impl Brick {
         self.status
     }
 
+    pub fn get_kind(&self) -> BrickKind {
+        self.kind
+    }
+
+    pub fn set_kind(&mut self, kind: BrickKind) {
+        self.kind = kind;
+        self.life = kind.get_life();
+    }
+
     pub fn update_status(&mut self) -> BrickStatus {
-        self.life -= self.life;
+        if !self.kind.is_breakable() {
+            return self.status;
+        }
+        self.life = self.life.saturating_sub(1);
         if self.life == 0 {
             self.status = BrickStatus::Dead;
         }
         self.status
     }
 
-    pub fn draw(&mut self, bitmap: &ImageBitmap, ctx: &web_sys::CanvasRenderingContext2d) {
-        ctx.begin_path();
-        let _ = ctx.draw_image_with_image_bitmap_and_dw_and_dh(
-            bitmap,
-            self.x,
-            self.y,
-            BRICK_WIDTH,
-            BRICK_HEIGHT,
-        );
-        if self.status == BrickStatus::Live {
-            ctx.set_stroke_style(&"rgb(95,95,95)".into());
-            ctx.set_line_width(0.5);
-            ctx.stroke_rect(self.x, self.y, BRICK_WIDTH, BRICK_HEIGHT);
+    pub fn draw<R: Renderer>(&self, bitmap: &R::Image, renderer: &mut R) {
+        renderer.draw_image(bitmap, self.x, self.y, BRICK_WIDTH, BRICK_HEIGHT);
+        if !self.kind.is_breakable() {
+            // 壊れないブロックは暗くして太い枠で囲む
+            renderer.fill_rect(
+                self.x,
+                self.y,
+                BRICK_WIDTH,
+                BRICK_HEIGHT,
+                "rgba(0, 0, 0, 0.4)",
+            );
+            renderer.stroke_rect(
+                self.x + 1.0,
+                self.y + 1.0,
+                BRICK_WIDTH - 2.0,
+                BRICK_HEIGHT - 2.0,
+                "rgb(200, 200, 200)",
+                2.0,
+            );
+        } else if self.status == BrickStatus::Live && self.kind == BrickKind::Hard {
+            // 硬いブロックは太い金色の枠 一度当たったら白っぽくして枠を細くする
+            let damaged = self.life < self.kind.get_life();
+            if damaged {
+                renderer.fill_rect(
+                    self.x,
+                    self.y,
+                    BRICK_WIDTH,
+                    BRICK_HEIGHT,
+                    "rgba(255, 255, 255, 0.35)",
+                );
+            }
+            renderer.stroke_rect(
+                self.x + 1.0,
+                self.y + 1.0,
+                BRICK_WIDTH - 2.0,
+                BRICK_HEIGHT - 2.0,
+                "rgb(230, 170, 40)",
+                if damaged { 1.0 } else { 2.0 },
+            );
+        } else if self.status == BrickStatus::Live {
+            renderer.stroke_rect(
+                self.x,
+                self.y,
+                BRICK_WIDTH,
+                BRICK_HEIGHT,
+                "rgb(95,95,95)",
+                0.5,
+            );
         }
-        ctx.close_path();
     }
 
-    /// ブロックが破壊されたとき用のブロック描画メソッド
-    pub fn draw_with_break(
-        &mut self,
-        bitmap: &ImageBitmap,
-        ctx: &web_sys::CanvasRenderingContext2d,
+    /// 裏の絵に切り替わる途中を描く progressは0.0..1.0
+    pub fn draw_reveal<R: Renderer>(
+        &self,
+        previous: &R::Image,
+        bitmap: &R::Image,
+        effect: RevealEffect,
+        progress: f64,
+        renderer: &mut R,
     ) {
-        let _ = ctx.draw_image_with_image_bitmap_and_dw_and_dh(
-            bitmap,
-            self.x,
-            self.y,
-            BRICK_WIDTH,
-            BRICK_HEIGHT,
-        );
+        match effect {
+            RevealEffect::Flip => {
+                // 前半は表の絵を潰し、後半は裏の絵を広げる
+                let (image, scale) = if progress < 0.5 {
+                    (previous, 1.0 - progress * 2.0)
+                } else {
+                    (bitmap, progress * 2.0 - 1.0)
+                };
+                renderer.save();
+                renderer.translate(self.x + BRICK_WIDTH / 2.0, self.y);
+                renderer.scale(scale.max(0.0), 1.0);
+                renderer.draw_image(image, -BRICK_WIDTH / 2.0, 0.0, BRICK_WIDTH, BRICK_HEIGHT);
+                renderer.restore();
+            }
+            RevealEffect::Dissolve => {
+                renderer.draw_image(bitmap, self.x, self.y, BRICK_WIDTH, BRICK_HEIGHT);
+                renderer.save();
+                renderer.set_global_alpha(1.0 - progress);
+                renderer.draw_image(previous, self.x, self.y, BRICK_WIDTH, BRICK_HEIGHT);
+                renderer.restore();
+            }
+            RevealEffect::Wipe => {
+                let edge = BRICK_WIDTH * progress;
+                renderer.draw_image(bitmap, self.x, self.y, BRICK_WIDTH, BRICK_HEIGHT);
+                renderer.save();
+                renderer.clip_rect(self.x + edge, self.y, BRICK_WIDTH - edge, BRICK_HEIGHT);
+                renderer.draw_image(previous, self.x, self.y, BRICK_WIDTH, BRICK_HEIGHT);
+                renderer.restore();
+            }
+        }
+    }
+
+    /// ブロックが破壊されたとき用のブロック描画メソッド
+    pub fn draw_with_break<R: Renderer>(&self, bitmap: &R::Image, renderer: &mut R) {
+        renderer.draw_image(bitmap, self.x, self.y, BRICK_WIDTH, BRICK_HEIGHT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording_renderer::{DrawCommand, RecordingRenderer};
+
+    fn record(brick: &Brick) -> Vec<DrawCommand> {
+        let mut renderer = RecordingRenderer::new();
+        brick.draw(&"image".to_string(), &mut renderer);
+        renderer.get_commands().to_vec()
+    }
+
+    fn outline(commands: &[DrawCommand]) -> (String, f64) {
+        commands
+            .iter()
+            .find_map(|c| match c {
+                DrawCommand::StrokeRect {
+                    color, line_width, ..
+                } => Some((color.clone(), *line_width)),
+                _ => None,
+            })
+            .expect("no outline")
+    }
+
+    fn has_overlay(commands: &[DrawCommand]) -> bool {
+        commands
+            .iter()
+            .any(|c| matches!(c, DrawCommand::FillRect { .. }))
+    }
+
+    #[test]
+    fn hard_brick_has_its_own_outline() {
+        let mut normal = Brick::new(0.0, 0.0, BrickStatus::Live);
+        normal.set_kind(BrickKind::Normal);
+        let mut hard = normal;
+        hard.set_kind(BrickKind::Hard);
+
+        let normal = record(&normal);
+        let hard = record(&hard);
+        assert_ne!(outline(&normal), outline(&hard));
+        assert!(!has_overlay(&hard));
+    }
+
+    #[test]
+    fn damaged_hard_brick_is_drawn_differently() {
+        let mut brick = Brick::new(0.0, 0.0, BrickStatus::Live);
+        brick.set_kind(BrickKind::Hard);
+        let fresh = record(&brick);
+
+        assert_eq!(brick.update_status(), BrickStatus::Live);
+        let damaged = record(&brick);
+        assert!(has_overlay(&damaged));
+        assert_ne!(outline(&fresh), outline(&damaged));
     }
 }
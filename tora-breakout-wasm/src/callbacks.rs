@@ -0,0 +1,207 @@
+use crate::events::GameEvent;
+use crate::game_status::GameStatus;
+use js_sys::Function;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use web_sys::console;
+
+/// ページ側に知らせる出来事 JSには{ type: "brickBroken", col, row, score }のようなオブジェクトで渡す
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HostEvent {
+    BrickBroken { col: usize, row: usize, score: u32 },
+    LifeLost { lives: u32 },
+    StageCleared { score: u32 },
+    GameOver { score: u32 },
+}
+
+impl HostEvent {
+    /// on()で登録できる名前
+    pub const NAMES: [&'static str; 4] = ["brickBroken", "lifeLost", "stageCleared", "gameOver"];
+
+    /// statusはイベントを処理してスコアなどを更新した後のもの
+    pub fn from_game_event(event: &GameEvent, status: &GameStatus) -> Option<HostEvent> {
+        match *event {
+            GameEvent::BrickBroken { col, row, .. } => Some(HostEvent::BrickBroken {
+                col,
+                row,
+                score: status.get_score(),
+            }),
+            GameEvent::LifeLost => Some(HostEvent::LifeLost {
+                lives: status.get_lives(),
+            }),
+            GameEvent::StageCleared => Some(HostEvent::StageCleared {
+                score: status.get_score(),
+            }),
+            GameEvent::GameOver => Some(HostEvent::GameOver {
+                score: status.get_score(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            HostEvent::BrickBroken { .. } => "brickBroken",
+            HostEvent::LifeLost { .. } => "lifeLost",
+            HostEvent::StageCleared { .. } => "stageCleared",
+            HostEvent::GameOver { .. } => "gameOver",
+        }
+    }
+
+    fn to_js(self) -> Result<JsValue, JsValue> {
+        let json = serde_json::to_string(&self).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        js_sys::JSON::parse(&json)
+    }
+}
+
+/// GameHandle.on()で登録されたコールバック
+#[derive(Debug, Default)]
+pub struct Callbacks {
+    callbacks: Vec<(&'static str, Function)>,
+}
+
+impl Callbacks {
+    pub fn new() -> Callbacks {
+        Callbacks::default()
+    }
+
+    pub fn add(&mut self, event: &str, callback: Function) -> Result<(), JsValue> {
+        let name = HostEvent::NAMES
+            .iter()
+            .find(|&&name| name == event)
+            .ok_or_else(|| JsValue::from_str(&format!("unknown event: {}", event)))?;
+        self.callbacks.push((name, callback));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, event: &str, callback: &Function) {
+        self.callbacks
+            .retain(|(name, registered)| !(*name == event && registered == callback));
+    }
+
+    pub fn clear(&mut self) {
+        self.callbacks.clear();
+    }
+
+    /// 呼ぶ前に借用を外すので、コールバックの中でon/offやゲームの操作をしてもよい
+    pub fn emit(callbacks: &RefCell<Callbacks>, event: HostEvent) {
+        let targets: Vec<Function> = callbacks
+            .borrow()
+            .callbacks
+            .iter()
+            .filter(|(name, _)| *name == event.get_name())
+            .map(|(_, callback)| callback.clone())
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+        let payload = match event.to_js() {
+            Ok(payload) => payload,
+            Err(_) => return,
+        };
+        for callback in targets {
+            // ページ側の例外でゲームを止めない
+            if let Err(e) = callback.call1(&JsValue::NULL, &payload) {
+                console::error_1(&e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::BrickKind;
+    use serde_json::json;
+
+    fn status() -> GameStatus {
+        let mut status = GameStatus::new();
+        status.set_score(120);
+        status.set_lives(2);
+        status
+    }
+
+    /// JSに渡すオブジェクトと同じ形
+    fn payload(event: &GameEvent) -> (&'static str, serde_json::Value) {
+        let host = HostEvent::from_game_event(event, &status()).unwrap();
+        (host.get_name(), serde_json::to_value(host).unwrap())
+    }
+
+    #[test]
+    fn brick_broken_has_the_cell_and_score() {
+        let event = GameEvent::BrickBroken {
+            col: 3,
+            row: 5,
+            x: 10.0,
+            y: 20.0,
+            kind: BrickKind::Hard,
+        };
+        assert_eq!(
+            payload(&event),
+            (
+                "brickBroken",
+                json!({ "type": "brickBroken", "col": 3, "row": 5, "score": 120 })
+            )
+        );
+    }
+
+    #[test]
+    fn life_lost_has_the_remaining_lives() {
+        assert_eq!(
+            payload(&GameEvent::LifeLost),
+            ("lifeLost", json!({ "type": "lifeLost", "lives": 2 }))
+        );
+    }
+
+    #[test]
+    fn stage_cleared_has_the_score() {
+        assert_eq!(
+            payload(&GameEvent::StageCleared),
+            (
+                "stageCleared",
+                json!({ "type": "stageCleared", "score": 120 })
+            )
+        );
+    }
+
+    #[test]
+    fn game_over_has_the_score() {
+        assert_eq!(
+            payload(&GameEvent::GameOver),
+            ("gameOver", json!({ "type": "gameOver", "score": 120 }))
+        );
+    }
+
+    #[test]
+    fn other_events_are_not_sent_to_the_page() {
+        let events = [
+            GameEvent::BrickHit { col: 0, row: 0 },
+            GameEvent::PaddleHit { x: 0.0, y: 0.0 },
+            GameEvent::WallHit { x: 0.0, y: 0.0 },
+        ];
+        for event in events.iter() {
+            assert_eq!(HostEvent::from_game_event(event, &status()), None);
+        }
+    }
+
+    #[test]
+    fn every_name_can_be_registered() {
+        let names: Vec<&str> = [
+            GameEvent::BrickBroken {
+                col: 0,
+                row: 0,
+                x: 0.0,
+                y: 0.0,
+                kind: BrickKind::Normal,
+            },
+            GameEvent::LifeLost,
+            GameEvent::StageCleared,
+            GameEvent::GameOver,
+        ]
+        .iter()
+        .map(|event| payload(event).0)
+        .collect();
+        assert_eq!(names, HostEvent::NAMES.to_vec());
+    }
+}
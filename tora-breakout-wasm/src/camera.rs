@@ -0,0 +1,179 @@
+use crate::bricks::BrickKind;
+use crate::consts::{
+    BRICK_BREAK_TRAUMA, HARD_BRICK_BREAK_TRAUMA, HARD_BRICK_HIT_STOP_FRAMES,
+    LIFE_LOST_HIT_STOP_FRAMES, LIFE_LOST_TRAUMA, MAX_SHAKE_OFFSET, MAX_SHAKE_ZOOM, TRAUMA_DECAY,
+};
+use crate::events::GameEvent;
+use crate::renderer::Renderer;
+
+/// 画面揺れとヒットストップ
+/// 揺れはトラウマ値で管理し、衝撃のたびに足して時間で減らす
+#[derive(Debug, Clone)]
+pub struct Camera {
+    enabled: bool,
+    trauma: f64,
+    hit_stop: u32,
+    offset_x: f64,
+    offset_y: f64,
+    zoom: f64,
+    rng: u32,
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            enabled: true,
+            trauma: 0.0,
+            hit_stop: 0,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            zoom: 1.0,
+            rng: 0x9e37_79b9,
+        }
+    }
+
+    /// 無効にすると揺れもヒットストップも起きない
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.trauma = 0.0;
+            self.hit_stop = 0;
+            self.offset_x = 0.0;
+            self.offset_y = 0.0;
+            self.zoom = 1.0;
+        }
+    }
+
+    pub fn add_trauma(&mut self, amount: f64) {
+        if self.enabled {
+            self.trauma = (self.trauma + amount).min(1.0);
+        }
+    }
+
+    pub fn add_hit_stop(&mut self, frames: u32) {
+        if self.enabled {
+            self.hit_stop = self.hit_stop.max(frames);
+        }
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::BrickBroken {
+                kind: BrickKind::Hard,
+                ..
+            } => {
+                self.add_trauma(HARD_BRICK_BREAK_TRAUMA);
+                self.add_hit_stop(HARD_BRICK_HIT_STOP_FRAMES);
+            }
+            GameEvent::BrickBroken { .. } => self.add_trauma(BRICK_BREAK_TRAUMA),
+            GameEvent::LifeLost => {
+                self.add_trauma(LIFE_LOST_TRAUMA);
+                self.add_hit_stop(LIFE_LOST_HIT_STOP_FRAMES);
+            }
+            _ => {}
+        }
+    }
+
+    /// ヒットストップ中ならそのフレームを消費してtrueを返す trueの間はシミュレーションを進めない
+    pub fn take_hit_stop_frame(&mut self) -> bool {
+        if self.hit_stop == 0 {
+            return false;
+        }
+        self.hit_stop -= 1;
+        true
+    }
+
+    /// xorshift32で-1.0..1.0
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f64 / u32::MAX as f64 * 2.0 - 1.0
+    }
+
+    /// 1フレーム進める
+    pub fn update(&mut self) {
+        // 2乗にすると小さい衝撃はほとんど揺れず、大きい衝撃だけ目立つ
+        let shake = self.trauma * self.trauma;
+        self.offset_x = MAX_SHAKE_OFFSET * shake * self.random();
+        self.offset_y = MAX_SHAKE_OFFSET * shake * self.random();
+        self.zoom = 1.0 + MAX_SHAKE_ZOOM * shake;
+        self.trauma = (self.trauma - TRAUMA_DECAY).max(0.0);
+    }
+
+    /// これ以降の描画に揺れを掛ける endと対で使う
+    pub fn begin<R: Renderer>(&self, renderer: &mut R, width: f64, height: f64) {
+        renderer.save();
+        renderer.translate(width / 2.0 + self.offset_x, height / 2.0 + self.offset_y);
+        renderer.scale(self.zoom, self.zoom);
+        renderer.translate(-width / 2.0, -height / 2.0);
+    }
+
+    pub fn end<R: Renderer>(&self, renderer: &mut R) {
+        renderer.restore();
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trauma_is_clamped_and_decays() {
+        let mut camera = Camera::new();
+        camera.add_trauma(0.8);
+        camera.add_trauma(0.8);
+        assert_eq!(camera.trauma, 1.0);
+
+        camera.update();
+        assert!((camera.trauma - (1.0 - TRAUMA_DECAY)).abs() < 1e-9);
+        assert!(camera.zoom > 1.0);
+
+        for _ in 0..(1.0 / TRAUMA_DECAY) as usize + 1 {
+            camera.update();
+        }
+        assert_eq!(camera.trauma, 0.0);
+        camera.update();
+        assert_eq!(camera.offset_x, 0.0);
+        assert_eq!(camera.offset_y, 0.0);
+        assert_eq!(camera.zoom, 1.0);
+    }
+
+    #[test]
+    fn hit_stop_frames_are_used_up() {
+        let mut camera = Camera::new();
+        camera.handle(&GameEvent::LifeLost);
+        // 短いヒットストップが後から来ても長い方が残る
+        camera.add_hit_stop(1);
+        for _ in 0..LIFE_LOST_HIT_STOP_FRAMES {
+            assert!(camera.take_hit_stop_frame());
+        }
+        assert!(!camera.take_hit_stop_frame());
+    }
+
+    #[test]
+    fn disabling_stops_shake_and_hit_stop() {
+        let mut camera = Camera::new();
+        camera.add_trauma(1.0);
+        camera.add_hit_stop(HARD_BRICK_HIT_STOP_FRAMES);
+        camera.update();
+        camera.set_enabled(false);
+        assert_eq!(camera.trauma, 0.0);
+        assert_eq!(
+            (camera.offset_x, camera.offset_y, camera.zoom),
+            (0.0, 0.0, 1.0)
+        );
+        assert!(!camera.take_hit_stop_frame());
+
+        // 無効の間の衝撃は無視する
+        camera.handle(&GameEvent::LifeLost);
+        assert_eq!(camera.trauma, 0.0);
+        assert!(!camera.take_hit_stop_frame());
+    }
+}
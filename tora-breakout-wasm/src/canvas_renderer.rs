@@ -0,0 +1,97 @@
+use crate::renderer::{Renderer, TextAlign};
+use std::f64;
+use web_sys::{CanvasRenderingContext2d, ImageBitmap};
+
+/// CanvasRenderingContext2dに描画するRenderer
+pub struct CanvasRenderer {
+    ctx: CanvasRenderingContext2d,
+}
+
+impl CanvasRenderer {
+    pub fn new(ctx: CanvasRenderingContext2d) -> CanvasRenderer {
+        CanvasRenderer { ctx }
+    }
+
+    /// Rendererにない操作(別キャンバスの転送など)用
+    pub fn get_context(&self) -> &CanvasRenderingContext2d {
+        &self.ctx
+    }
+}
+
+impl Renderer for CanvasRenderer {
+    type Image = ImageBitmap;
+
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.ctx.clear_rect(x, y, width, height);
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: &str) {
+        self.ctx.set_fill_style_str(color);
+        self.ctx.fill_rect(x, y, width, height);
+    }
+
+    fn stroke_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: &str,
+        line_width: f64,
+    ) {
+        self.ctx.set_stroke_style_str(color);
+        self.ctx.set_line_width(line_width);
+        self.ctx.stroke_rect(x, y, width, height);
+    }
+
+    fn fill_circle(&mut self, x: f64, y: f64, radius: f64, color: &str) {
+        self.ctx.begin_path();
+        let _ = self.ctx.arc(x, y, radius, 0.0, f64::consts::PI * 2.0);
+        self.ctx.set_fill_style_str(color);
+        self.ctx.fill();
+        self.ctx.close_path();
+    }
+
+    fn draw_image(&mut self, image: &ImageBitmap, x: f64, y: f64, width: f64, height: f64) {
+        let _ = self
+            .ctx
+            .draw_image_with_image_bitmap_and_dw_and_dh(image, x, y, width, height);
+    }
+
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, font: &str, color: &str, align: TextAlign) {
+        self.ctx.set_font(font);
+        self.ctx.set_fill_style_str(color);
+        self.ctx.set_text_align(match align {
+            TextAlign::Left => "left",
+            TextAlign::Center => "center",
+            TextAlign::Right => "right",
+        });
+        let _ = self.ctx.fill_text(text, x, y);
+    }
+
+    fn clip_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.ctx.begin_path();
+        self.ctx.rect(x, y, width, height);
+        self.ctx.clip();
+    }
+
+    fn save(&mut self) {
+        self.ctx.save();
+    }
+
+    fn restore(&mut self) {
+        self.ctx.restore();
+    }
+
+    fn translate(&mut self, x: f64, y: f64) {
+        let _ = self.ctx.translate(x, y);
+    }
+
+    fn scale(&mut self, x: f64, y: f64) {
+        let _ = self.ctx.scale(x, y);
+    }
+
+    fn set_global_alpha(&mut self, alpha: f64) {
+        self.ctx.set_global_alpha(alpha);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::game_status::Difficulty;
+use wasm_bindgen::prelude::*;
+
+/// start()に渡す設定 JSのオブジェクトで、無い項目は既定値にする
+/// 同じページに複数のゲームを並べるときは、音やゲームパッドを片方だけにする
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GameConfig {
+    // ブロック画像の置き場所
+    pub asset_url: String,
+    // tora-breakout-assetsが書き出すマニフェストのassetUrlからのパス 空なら元のファイル名で読む
+    pub manifest: String,
+    // 効果音とBGMを鳴らす
+    pub audio: bool,
+    // ゲームパッドで操作する
+    pub gamepad: bool,
+    // 始めるときの難易度 タイトル画面でも変えられる
+    pub difficulty: Difficulty,
+}
+
+impl GameConfig {
+    /// undefinedやnullなら既定値
+    pub fn from_js(value: &JsValue) -> Result<GameConfig, JsValue> {
+        if value.is_undefined() || value.is_null() {
+            return Ok(GameConfig::default());
+        }
+        let json: String = js_sys::JSON::stringify(value)?.into();
+        serde_json::from_str(&json)
+            .map_err(|e| JsValue::from_str(&format!("invalid config: {}", e)))
+    }
+
+    /// valueにある項目だけを置き換えたものを返す setConfig用
+    pub fn merge_js(&self, value: &JsValue) -> Result<GameConfig, JsValue> {
+        let json: String = js_sys::JSON::stringify(value)?.into();
+        let changes: serde_json::Value = serde_json::from_str(&json)
+            .map_err(|e| JsValue::from_str(&format!("invalid config: {}", e)))?;
+        let mut merged = serde_json::to_value(self)
+            .map_err(|e| JsValue::from_str(&format!("invalid config: {}", e)))?;
+        match (merged.as_object_mut(), changes.as_object()) {
+            (Some(merged), Some(changes)) => {
+                for (key, value) in changes {
+                    merged.insert(key.clone(), value.clone());
+                }
+            }
+            _ => return Err(JsValue::from_str("config must be an object")),
+        }
+        serde_json::from_value(merged)
+            .map_err(|e| JsValue::from_str(&format!("invalid config: {}", e)))
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            asset_url: String::new(),
+            manifest: String::new(),
+            audio: true,
+            gamepad: true,
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
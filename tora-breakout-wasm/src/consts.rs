@@ -3,7 +3,6 @@ pub const PADDLE_HEIGHT: f64 = 5.0;
 pub const PADDLE_WIDTH: f64 = 75.0;
 pub const BRICK_ROW_COUNT: usize = 24;
 pub const BRICK_COLUMN_COUNT: usize = 10;
-pub const BRICK_SUM: u32 = (BRICK_ROW_COUNT * BRICK_COLUMN_COUNT) as u32;
 pub const BRICK_WIDTH: f64 = 48.0;
 pub const BRICK_HEIGHT: f64 = 20.0;
 pub const BRICK_PADDING: f64 = 0.0;
@@ -14,3 +13,83 @@ pub const SPEED: f64 = 0.05;
 pub const INIT_SPEED: usize = 20;
 pub const DEFAULT_SCORE: u32 = 0;
 pub const DEFAULT_LIVES: u32 = 3;
+// 難易度ごとの残機 NormalはDEFAULT_LIVES
+pub const EASY_LIVES: u32 = 5;
+pub const HARD_LIVES: u32 = 1;
+pub const DEFAULT_STAGE: u32 = 1;
+// ハイスコアは上位この件数だけlocalStorageに残す
+pub const HIGH_SCORE_COUNT: usize = 10;
+pub const HIGH_SCORE_STORAGE_KEY: &str = "tora-breakout-high-scores";
+pub const NAME_MAX_LENGTH: usize = 8;
+pub const NORMAL_BRICK_POINTS: u32 = 10;
+pub const HARD_BRICK_POINTS: u32 = 30;
+// パドルに触れるまでの連続ヒット数がこの数増えるごとに倍率が1上がる
+pub const COMBO_PER_MULTIPLIER: u32 = 4;
+pub const MAX_MULTIPLIER: u32 = 8;
+// ステージクリア時、この秒数より早ければ残り秒数 x TIME_BONUS_PER_SECONDのボーナス
+pub const TIME_BONUS_LIMIT: f64 = 300.0;
+pub const TIME_BONUS_PER_SECOND: u32 = 10;
+pub const LIFE_BONUS: u32 = 1000;
+// スコアのポップアップを表示しておくフレーム数
+pub const SCORE_POPUP_FRAMES: u32 = 45;
+// ステージクリアやゲームオーバーの表示を出しておくフレーム数 その後ネームエントリーかタイトルへ
+pub const RESULT_MESSAGE_FRAMES: u32 = 150;
+// デバッグ表示中、このフレーム数ごとにブロック層を全部描き直して時間を比べる
+pub const DEBUG_FULL_REDRAW_SAMPLE_FRAMES: u32 = 30;
+// 同時に出せるパーティクルの数 これ以上は古いものから上書きする
+pub const MAX_PARTICLES: usize = 512;
+// ボールの速さ(get_speed)がこれ以上のときに軌跡を出す
+pub const TRAIL_MIN_SPEED: usize = 30;
+// ブロックの画像から破片の色を拾う数(横 x 縦)
+pub const DEBRIS_SAMPLE_COLS: u32 = 4;
+pub const DEBRIS_SAMPLE_ROWS: u32 = 2;
+// ブロックが壊れて裏の絵が出てくるまでのフレーム数
+pub const REVEAL_FRAMES: u32 = 24;
+pub const SETTINGS_STORAGE_KEY: &str = "tora-breakout-settings";
+// 画面揺れ トラウマ(0.0..1.0)の2乗に比例して揺らし、毎フレームTRAUMA_DECAYずつ減らす
+pub const MAX_SHAKE_OFFSET: f64 = 8.0;
+pub const MAX_SHAKE_ZOOM: f64 = 0.02;
+pub const TRAUMA_DECAY: f64 = 0.02;
+pub const BRICK_BREAK_TRAUMA: f64 = 0.2;
+pub const HARD_BRICK_BREAK_TRAUMA: f64 = 0.35;
+pub const LIFE_LOST_TRAUMA: f64 = 0.6;
+// 重い当たりのときにシミュレーションを止めるフレーム数
+pub const HARD_BRICK_HIT_STOP_FRAMES: u32 = 3;
+pub const LIFE_LOST_HIT_STOP_FRAMES: u32 = 8;
+pub const MAX_SPEED: usize = 80;
+// ボールの軌跡として覚えておく位置の数 表示する数は速さに合わせてBALL_TRAIL_MIN_LENGTHからこの数まで伸ばす
+pub const BALL_TRAIL_CAPACITY: usize = 16;
+pub const BALL_TRAIL_MIN_LENGTH: usize = 3;
+pub const DEFAULT_VOLUME: f64 = 0.5;
+pub const VOLUME_STEP: f64 = 0.1;
+// BGM 先読みしてWeb Audioの時計に予約しておく秒数と、曲を切り替えるときのクロスフェードの秒数
+pub const MUSIC_LOOKAHEAD: f64 = 0.2;
+pub const MUSIC_CROSSFADE: f64 = 1.5;
+pub const MUSIC_VOLUME: f64 = 0.6;
+// 強さ1.0のときのテンポの上がり幅
+pub const MUSIC_TEMPO_BOOST: f64 = 0.3;
+// 残りのブロックがこの割合を切ったら強さを上げ始める
+pub const MUSIC_LOW_BRICKS_RATIO: f64 = 0.3;
+// キー1回の判定ごとにパドルが動く距離
+pub const PADDLE_SPEED: f64 = 7.0;
+// ゲームパッドのスティックの遊び これより小さい傾きは0とみなす
+pub const GAMEPAD_DEADZONE: f64 = 0.2;
+// タッチをタップとみなす長さ(ミリ秒)と動いた距離 これを超えたらドラッグ
+pub const TAP_MAX_DURATION: f64 = 250.0;
+pub const TAP_MAX_DISTANCE: f64 = 10.0;
+// プレイフィールドの論理サイズ 表示するときは画面に合わせて拡大縮小する
+pub const FIELD_WIDTH: u32 = 480;
+pub const FIELD_HEIGHT: u32 = 720;
+// ポインターロック中のマウスの感度 移動量(論理ピクセル)に掛ける
+pub const DEFAULT_MOUSE_SENSITIVITY: f64 = 1.0;
+pub const MOUSE_SENSITIVITY_STEP: f64 = 0.25;
+pub const MIN_MOUSE_SENSITIVITY: f64 = 0.25;
+pub const MAX_MOUSE_SENSITIVITY: f64 = 4.0;
+// imgs/以下にある絵のセット レベルエディタで表と裏の絵を選ぶときに順番に切り替える
+pub const PICTURE_SETS: [&str; 2] = ["27_devil", "26_angel"];
+// レベルエディタで戻せる操作の数
+pub const EDITOR_HISTORY_LIMIT: usize = 100;
+// レベルエディタのブラシを選ぶボタンの位置
+pub const EDITOR_PALETTE_Y: f64 = 530.0;
+pub const EDITOR_PALETTE_WIDTH: f64 = 105.0;
+pub const EDITOR_PALETTE_HEIGHT: f64 = 28.0;
@@ -0,0 +1,126 @@
+use crate::input::{display_key, Action, KeyBindings};
+use crate::renderer::{Renderer, TextAlign};
+
+/// タイトルからF1で開くキー設定画面
+/// 上下で操作を選び、Enterの後に押したキーを割り当てる
+#[derive(Debug, Clone, Default)]
+pub struct ControlsScreen {
+    open: bool,
+    selected: usize,
+    // Enterを押して割り当てるキーを待っている
+    waiting: bool,
+}
+
+impl ControlsScreen {
+    pub fn new() -> ControlsScreen {
+        ControlsScreen::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.selected = 0;
+        self.waiting = false;
+    }
+
+    /// 開いている間のキー入力 codeはKeyboardEvent.code
+    /// 割り当てを変えたらtrueを返すので保存する
+    pub fn handle_key(&mut self, code: &str, bindings: &mut KeyBindings) -> bool {
+        let actions = Action::all();
+        if self.waiting {
+            self.waiting = false;
+            // Escapeは割り当てずに取り消し
+            if code == "Escape" {
+                return false;
+            }
+            bindings.rebind(actions[self.selected], code);
+            return true;
+        }
+        match code {
+            "ArrowUp" => self.selected = (self.selected + actions.len() - 1) % actions.len(),
+            "ArrowDown" => self.selected = (self.selected + 1) % actions.len(),
+            "Enter" => self.waiting = true,
+            "Backspace" => {
+                *bindings = KeyBindings::default();
+                return true;
+            }
+            "Escape" | "F1" => self.open = false,
+            _ => {}
+        }
+        false
+    }
+
+    pub fn draw<R: Renderer>(
+        &self,
+        renderer: &mut R,
+        bindings: &KeyBindings,
+        width: f64,
+        height: f64,
+    ) {
+        renderer.fill_rect(0.0, 0.0, width, height, "rgba(0, 0, 0, 0.85)");
+        renderer.fill_text(
+            "CONTROLS",
+            width / 2.0,
+            140.0,
+            "24px Arial",
+            "rgb(255, 136, 0)",
+            TextAlign::Center,
+        );
+        for (i, &action) in Action::all().iter().enumerate() {
+            let y = 200.0 + i as f64 * 32.0;
+            let selected = i == self.selected;
+            if selected {
+                renderer.fill_rect(
+                    60.0,
+                    y - 20.0,
+                    width - 120.0,
+                    28.0,
+                    "rgba(0, 149, 208, 0.4)",
+                );
+            }
+            let keys = if selected && self.waiting {
+                "PRESS A KEY...".to_string()
+            } else {
+                bindings
+                    .keys_for(action)
+                    .iter()
+                    .map(|k| display_key(k))
+                    .collect::<Vec<_>>()
+                    .join(" / ")
+            };
+            renderer.fill_text(
+                action.get_label(),
+                80.0,
+                y,
+                "16px Arial",
+                "rgb(255, 255, 255)",
+                TextAlign::Left,
+            );
+            renderer.fill_text(
+                &keys,
+                width - 80.0,
+                y,
+                "16px Arial",
+                "rgb(255, 204, 0)",
+                TextAlign::Right,
+            );
+        }
+        let help = [
+            "UP/DOWN: SELECT  ENTER: CHANGE",
+            "BACKSPACE: DEFAULTS  ESC/F1: BACK",
+        ];
+        for (i, line) in help.iter().enumerate() {
+            renderer.fill_text(
+                line,
+                width / 2.0,
+                400.0 + i as f64 * 20.0,
+                "12px Arial",
+                "rgb(200, 200, 200)",
+                TextAlign::Center,
+            );
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::consts::DEBUG_FULL_REDRAW_SAMPLE_FRAMES;
+use crate::renderer::{Renderer, TextAlign};
+
+// 移動平均の重み
+const SMOOTHING: f64 = 0.1;
+
+/// F3で表示するデバッグ情報 ブロック層のキャッシュあり/なしの描画時間を比べる
+#[derive(Debug, Clone)]
+pub struct DebugOverlay {
+    visible: bool,
+    use_cache: bool,
+    cached_ms: f64,
+    full_ms: f64,
+    redrawn_cells: usize,
+    // 0になったフレームで全部描き直した時間も測る
+    sample_countdown: u32,
+}
+
+impl DebugOverlay {
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            visible: false,
+            use_cache: true,
+            cached_ms: 0.0,
+            full_ms: 0.0,
+            redrawn_cells: 0,
+            sample_countdown: 0,
+        }
+    }
+
+    pub fn toggle_visible(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// キャッシュを使わず毎フレーム全部描く方式と切り替える
+    pub fn toggle_cache(&mut self) {
+        self.use_cache = !self.use_cache;
+    }
+
+    pub fn get_use_cache(&self) -> bool {
+        self.use_cache
+    }
+
+    pub fn record_cached(&mut self, ms: f64, redrawn_cells: usize) {
+        self.sample_countdown = match self.sample_countdown {
+            0 => DEBUG_FULL_REDRAW_SAMPLE_FRAMES,
+            n => n - 1,
+        };
+        self.cached_ms += (ms - self.cached_ms) * SMOOTHING;
+        self.redrawn_cells = redrawn_cells;
+    }
+
+    pub fn record_full(&mut self, ms: f64) {
+        self.full_ms += (ms - self.full_ms) * SMOOTHING;
+    }
+
+    /// 表示中はキャッシュ使用時も時々全部描き直して比較用の時間を取る
+    pub fn should_sample_full(&self) -> bool {
+        self.visible && self.use_cache && self.sample_countdown == 0
+    }
+
+    pub fn draw<R: Renderer>(&self, renderer: &mut R, height: f64) {
+        if !self.visible {
+            return;
+        }
+        let mode = if self.use_cache { "cached" } else { "full" };
+        let lines = [
+            format!(
+                "bricks cached: {:.3}ms ({} cells)",
+                self.cached_ms, self.redrawn_cells
+            ),
+            format!("bricks full:   {:.3}ms", self.full_ms),
+            format!("mode: {} (F4)", mode),
+        ];
+        let top = height - 80.0;
+        renderer.fill_rect(4.0, top - 16.0, 260.0, 62.0, "rgba(0, 0, 0, 0.6)");
+        for (i, line) in lines.iter().enumerate() {
+            renderer.fill_text(
+                line,
+                10.0,
+                top + i as f64 * 18.0,
+                "12px monospace",
+                "rgb(0, 255, 0)",
+                TextAlign::Left,
+            );
+        }
+    }
+}
+
+impl Default for DebugOverlay {
+    fn default() -> DebugOverlay {
+        DebugOverlay::new()
+    }
+}
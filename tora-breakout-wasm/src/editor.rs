@@ -0,0 +1,501 @@
+use crate::bricks::BrickKind;
+use crate::consts::{
+    BRICK_COLUMN_COUNT, BRICK_HEIGHT, BRICK_OFFSET_LEFT, BRICK_OFFSET_TOP, BRICK_PADDING,
+    BRICK_ROW_COUNT, BRICK_WIDTH, EDITOR_HISTORY_LIMIT, EDITOR_PALETTE_HEIGHT,
+    EDITOR_PALETTE_WIDTH, EDITOR_PALETTE_Y, PICTURE_SETS,
+};
+use crate::level::{Level, RevealEffect};
+use crate::renderer::{Renderer, TextAlign};
+
+// 選べるブラシ Noneは消しゴム
+const BRUSHES: [Option<BrickKind>; 4] = [
+    Some(BrickKind::Normal),
+    Some(BrickKind::Hard),
+    Some(BrickKind::Unbreakable),
+    None,
+];
+
+/// エディタの外でしかできない操作 handle_keyが返すのでlib.rs側で行う
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorAction {
+    // 今のレベルですぐに遊ぶ
+    TestPlay,
+    // レベルのJSONをファイルに保存する
+    Export,
+    // ファイルからレベルのJSONを読み込む
+    Import,
+    // 読み込んだレベルをやめて組み込みのステージに戻る
+    PlayBuiltin,
+}
+
+/// タイトルからEで開くレベルエディタ
+/// マウスでマスにブロックを塗り、盤面の大きさや絵を変えて、その場で試しに遊べる
+#[derive(Debug, Clone)]
+pub struct Editor {
+    open: bool,
+    level: Level,
+    brush: Option<BrickKind>,
+    undo: Vec<Level>,
+    redo: Vec<Level>,
+    // ドラッグ中の塗り方と、塗り始める前のレベル
+    stroke: Option<(Option<BrickKind>, Level)>,
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// levelを編集し始める 戻す履歴は消す
+    pub fn open(&mut self, level: Level) {
+        self.open = true;
+        self.level = level;
+        self.undo.clear();
+        self.redo.clear();
+        self.stroke = None;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.stroke = None;
+    }
+
+    pub fn get_level(&self) -> &Level {
+        &self.level
+    }
+
+    /// 読み込んだレベルに置き換える 元に戻すこともできる
+    pub fn set_level(&mut self, level: Level) {
+        let previous = std::mem::replace(&mut self.level, level);
+        self.push_undo(previous);
+    }
+
+    pub fn get_brush(&self) -> Option<BrickKind> {
+        self.brush
+    }
+
+    /// マウスのボタンを押したとき x, yは論理座標 eraseなら選んでいるブラシに関係なく消す
+    pub fn pointer_down(&mut self, x: f64, y: f64, erase: bool) {
+        if let Some(brush) = palette_at(x, y) {
+            self.brush = brush;
+            return;
+        }
+        let brush = if erase { None } else { self.brush };
+        self.stroke = Some((brush, self.level.clone()));
+        self.paint(x, y);
+    }
+
+    /// ドラッグ中は通ったマスを塗る
+    pub fn pointer_move(&mut self, x: f64, y: f64) {
+        self.paint(x, y);
+    }
+
+    /// 1回のドラッグで塗ったものをまとめて1回分の履歴にする
+    pub fn pointer_up(&mut self) {
+        if let Some((_, before)) = self.stroke.take() {
+            if before != self.level {
+                self.push_undo(before);
+            }
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(level) = self.undo.pop() {
+            let current = std::mem::replace(&mut self.level, level);
+            self.redo.push(current);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(level) = self.redo.pop() {
+            let current = std::mem::replace(&mut self.level, level);
+            self.undo.push(current);
+        }
+    }
+
+    /// 開いている間のキー入力 codeはKeyboardEvent.code
+    pub fn handle_key(&mut self, code: &str, ctrl: bool, shift: bool) -> Option<EditorAction> {
+        let columns = self.level.get_column_count();
+        let rows = self.level.get_row_count();
+        match code {
+            "KeyZ" if ctrl && shift => self.redo(),
+            "KeyZ" if ctrl => self.undo(),
+            "KeyY" if ctrl => self.redo(),
+            "Digit1" | "Numpad1" => self.brush = BRUSHES[0],
+            "Digit2" | "Numpad2" => self.brush = BRUSHES[1],
+            "Digit3" | "Numpad3" => self.brush = BRUSHES[2],
+            "Digit0" | "Numpad0" => self.brush = BRUSHES[3],
+            "ArrowLeft" => self.edit(|level| level.resize(columns.saturating_sub(1), rows)),
+            "ArrowRight" => self.edit(|level| level.resize(columns + 1, rows)),
+            "ArrowUp" => self.edit(|level| level.resize(columns, rows.saturating_sub(1))),
+            "ArrowDown" => self.edit(|level| level.resize(columns, rows + 1)),
+            "KeyC" => self.edit(|level| {
+                let cover = next_picture_set(level.get_cover());
+                level.set_cover(cover);
+            }),
+            "KeyH" => self.edit(|level| {
+                let hidden = next_picture_set(level.get_hidden());
+                level.set_hidden(hidden);
+            }),
+            "KeyR" => self.edit(|level| {
+                let effects = RevealEffect::all();
+                let index = effects
+                    .iter()
+                    .position(|&e| e == level.get_reveal())
+                    .unwrap_or(0);
+                level.set_reveal(effects[(index + 1) % effects.len()]);
+            }),
+            "KeyT" => return Some(EditorAction::TestPlay),
+            "KeyX" => return Some(EditorAction::Export),
+            "KeyI" => return Some(EditorAction::Import),
+            "KeyB" => return Some(EditorAction::PlayBuiltin),
+            "Escape" => self.close(),
+            _ => {}
+        }
+        None
+    }
+
+    pub fn draw<R: Renderer>(&self, renderer: &mut R, width: f64, height: f64) {
+        renderer.fill_rect(0.0, 0.0, width, height, "rgb(0, 0, 0)");
+        renderer.fill_text(
+            &format!("LEVEL EDITOR  {}", self.level.get_name()),
+            8.0,
+            20.0,
+            "14px Arial",
+            "rgb(255, 136, 0)",
+            TextAlign::Left,
+        );
+        let columns = self.level.get_column_count();
+        let rows = self.level.get_row_count();
+        for col in 0..BRICK_COLUMN_COUNT {
+            for row in 0..BRICK_ROW_COUNT {
+                let (x, y) = cell_position(col, row);
+                if col >= columns || row >= rows {
+                    renderer.stroke_rect(x, y, BRICK_WIDTH, BRICK_HEIGHT, "rgb(40, 40, 40)", 0.5);
+                    continue;
+                }
+                if let Some(kind) = self.level.get_cell(col, row) {
+                    renderer.fill_rect(x, y, BRICK_WIDTH, BRICK_HEIGHT, brush_color(kind));
+                    // 何回当てると壊れるか
+                    if kind.is_breakable() {
+                        renderer.fill_text(
+                            &kind.get_life().to_string(),
+                            x + BRICK_WIDTH / 2.0,
+                            y + BRICK_HEIGHT - 6.0,
+                            "12px Arial",
+                            "rgb(255, 255, 255)",
+                            TextAlign::Center,
+                        );
+                    }
+                }
+                renderer.stroke_rect(x, y, BRICK_WIDTH, BRICK_HEIGHT, "rgb(95,95,95)", 0.5);
+            }
+        }
+        let (right, bottom) = cell_position(columns, rows);
+        renderer.stroke_rect(
+            BRICK_OFFSET_LEFT,
+            BRICK_OFFSET_TOP,
+            right - BRICK_OFFSET_LEFT,
+            bottom - BRICK_OFFSET_TOP,
+            "rgb(255, 255, 255)",
+            1.0,
+        );
+
+        for (i, &brush) in BRUSHES.iter().enumerate() {
+            let x = palette_x(i);
+            let color = brush.map(brush_color).unwrap_or("rgb(0, 0, 0)");
+            renderer.fill_rect(
+                x,
+                EDITOR_PALETTE_Y,
+                EDITOR_PALETTE_WIDTH,
+                EDITOR_PALETTE_HEIGHT,
+                color,
+            );
+            if brush == self.brush {
+                renderer.stroke_rect(
+                    x,
+                    EDITOR_PALETTE_Y,
+                    EDITOR_PALETTE_WIDTH,
+                    EDITOR_PALETTE_HEIGHT,
+                    "rgb(255, 204, 0)",
+                    2.0,
+                );
+            }
+            let label = match brush {
+                Some(BrickKind::Normal) => "1: NORMAL",
+                Some(BrickKind::Hard) => "2: HARD",
+                Some(BrickKind::Unbreakable) => "3: SOLID",
+                None => "0: ERASE",
+            };
+            renderer.fill_text(
+                label,
+                x + EDITOR_PALETTE_WIDTH / 2.0,
+                EDITOR_PALETTE_Y + 19.0,
+                "14px Arial",
+                "rgb(255, 255, 255)",
+                TextAlign::Center,
+            );
+        }
+
+        let info = [
+            format!("SIZE {} x {}", columns, rows),
+            format!(
+                "COVER {}  HIDDEN {}  REVEAL {:?}",
+                self.level.get_cover(),
+                self.level.get_hidden(),
+                self.level.get_reveal()
+            ),
+        ];
+        for (i, line) in info.iter().enumerate() {
+            renderer.fill_text(
+                line,
+                width / 2.0,
+                EDITOR_PALETTE_Y + 56.0 + i as f64 * 18.0,
+                "14px Arial",
+                "rgb(255, 255, 255)",
+                TextAlign::Center,
+            );
+        }
+        let help = [
+            "DRAG: PAINT  RIGHT DRAG: ERASE",
+            "ARROWS: RESIZE  C/H: PICTURES  R: REVEAL",
+            "CTRL+Z: UNDO  CTRL+Y: REDO  T: TEST PLAY",
+            "X: EXPORT  I: IMPORT  B: BUILT-IN STAGES  ESC: BACK",
+        ];
+        for (i, line) in help.iter().enumerate() {
+            renderer.fill_text(
+                line,
+                width / 2.0,
+                EDITOR_PALETTE_Y + 104.0 + i as f64 * 18.0,
+                "12px Arial",
+                "rgb(200, 200, 200)",
+                TextAlign::Center,
+            );
+        }
+    }
+
+    fn paint(&mut self, x: f64, y: f64) {
+        let brush = match &self.stroke {
+            Some((brush, _)) => *brush,
+            None => return,
+        };
+        if let Some((col, row)) = cell_at(x, y) {
+            self.level.set_cell(col, row, brush);
+        }
+    }
+
+    /// 1回分の履歴を残してlevelを変える
+    fn edit(&mut self, f: impl FnOnce(&mut Level)) {
+        let before = self.level.clone();
+        f(&mut self.level);
+        if before != self.level {
+            self.push_undo(before);
+        }
+    }
+
+    fn push_undo(&mut self, level: Level) {
+        self.undo.push(level);
+        if self.undo.len() > EDITOR_HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor {
+            open: false,
+            level: Level::default(),
+            brush: Some(BrickKind::Normal),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            stroke: None,
+        }
+    }
+}
+
+fn brush_color(kind: BrickKind) -> &'static str {
+    match kind {
+        BrickKind::Normal => "rgb(0, 149, 208)",
+        BrickKind::Hard => "rgb(255, 136, 0)",
+        BrickKind::Unbreakable => "rgb(128, 128, 128)",
+    }
+}
+
+fn cell_position(col: usize, row: usize) -> (f64, f64) {
+    (
+        col as f64 * (BRICK_WIDTH + BRICK_PADDING) + BRICK_OFFSET_LEFT,
+        row as f64 * (BRICK_HEIGHT + BRICK_PADDING) + BRICK_OFFSET_TOP,
+    )
+}
+
+/// 論理座標にあるマス 盤面の外ならNone
+fn cell_at(x: f64, y: f64) -> Option<(usize, usize)> {
+    let col = ((x - BRICK_OFFSET_LEFT) / (BRICK_WIDTH + BRICK_PADDING)).floor();
+    let row = ((y - BRICK_OFFSET_TOP) / (BRICK_HEIGHT + BRICK_PADDING)).floor();
+    if col < 0.0 || row < 0.0 {
+        return None;
+    }
+    let (col, row) = (col as usize, row as usize);
+    if col >= BRICK_COLUMN_COUNT || row >= BRICK_ROW_COUNT {
+        return None;
+    }
+    Some((col, row))
+}
+
+fn palette_x(index: usize) -> f64 {
+    15.0 + index as f64 * (EDITOR_PALETTE_WIDTH + 10.0)
+}
+
+/// 論理座標にあるブラシのボタン
+fn palette_at(x: f64, y: f64) -> Option<Option<BrickKind>> {
+    if !(EDITOR_PALETTE_Y..=EDITOR_PALETTE_Y + EDITOR_PALETTE_HEIGHT).contains(&y) {
+        return None;
+    }
+    (0..BRUSHES.len())
+        .find(|&i| (palette_x(i)..=palette_x(i) + EDITOR_PALETTE_WIDTH).contains(&x))
+        .map(|i| BRUSHES[i])
+}
+
+fn next_picture_set(current: &str) -> &'static str {
+    let index = PICTURE_SETS
+        .iter()
+        .position(|&set| set == current)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    PICTURE_SETS[index % PICTURE_SETS.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> Editor {
+        let mut editor = Editor::new();
+        editor.open(Level::new(
+            "test",
+            "27_devil",
+            "26_angel",
+            RevealEffect::Flip,
+        ));
+        editor
+    }
+
+    /// マスの真ん中の論理座標
+    fn center(col: usize, row: usize) -> (f64, f64) {
+        let (x, y) = cell_position(col, row);
+        (x + BRICK_WIDTH / 2.0, y + BRICK_HEIGHT / 2.0)
+    }
+
+    fn drag(editor: &mut Editor, cells: &[(usize, usize)], erase: bool) {
+        let (x, y) = center(cells[0].0, cells[0].1);
+        editor.pointer_down(x, y, erase);
+        for &(col, row) in &cells[1..] {
+            let (x, y) = center(col, row);
+            editor.pointer_move(x, y);
+        }
+        editor.pointer_up();
+    }
+
+    #[test]
+    fn one_drag_is_undone_in_one_step() {
+        let mut editor = editor();
+        let original = editor.get_level().clone();
+        drag(&mut editor, &[(0, 0), (1, 0), (2, 0)], true);
+        assert_eq!(editor.get_level().get_rows()[0].get(..3), Some("..."));
+        editor.undo();
+        assert_eq!(editor.get_level(), &original);
+        editor.redo();
+        assert_eq!(editor.get_level().get_rows()[0].get(..3), Some("..."));
+    }
+
+    #[test]
+    fn new_edit_after_undo_clears_redo() {
+        let mut editor = editor();
+        editor.handle_key("ArrowLeft", false, false);
+        let narrow = editor.get_level().clone();
+        editor.handle_key("KeyR", false, false);
+        editor.undo();
+        assert_eq!(editor.get_level(), &narrow);
+        editor.handle_key("KeyC", false, false);
+        let changed = editor.get_level().clone();
+        editor.redo();
+        assert_eq!(editor.get_level(), &changed);
+    }
+
+    #[test]
+    fn keyboard_shortcuts_undo_and_redo() {
+        let mut editor = editor();
+        let original = editor.get_level().clone();
+        editor.handle_key("ArrowUp", false, false);
+        assert_eq!(editor.get_level().get_row_count(), BRICK_ROW_COUNT - 1);
+        editor.handle_key("KeyZ", true, false);
+        assert_eq!(editor.get_level(), &original);
+        editor.handle_key("KeyZ", true, true);
+        assert_eq!(editor.get_level().get_row_count(), BRICK_ROW_COUNT - 1);
+        editor.handle_key("KeyZ", true, false);
+        editor.handle_key("KeyY", true, false);
+        assert_eq!(editor.get_level().get_row_count(), BRICK_ROW_COUNT - 1);
+    }
+
+    #[test]
+    fn drag_without_changes_and_edits_at_the_limit_leave_no_history() {
+        let mut editor = editor();
+        drag(&mut editor, &[(0, 0)], true);
+        drag(&mut editor, &[(0, 0)], true);
+        assert_eq!(editor.undo.len(), 1);
+        editor.undo.clear();
+        // これ以上小さくできないので履歴は増えない
+        for _ in 0..BRICK_COLUMN_COUNT + 3 {
+            editor.handle_key("ArrowLeft", false, false);
+        }
+        assert_eq!(editor.undo.len(), BRICK_COLUMN_COUNT - 1);
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut editor = editor();
+        for i in 0..EDITOR_HISTORY_LIMIT + 10 {
+            let key = if i % 2 == 0 { "ArrowUp" } else { "ArrowDown" };
+            editor.handle_key(key, false, false);
+        }
+        assert_eq!(editor.undo.len(), EDITOR_HISTORY_LIMIT);
+    }
+
+    #[test]
+    fn imported_level_can_be_undone_and_reopening_forgets_history() {
+        let mut editor = editor();
+        let original = editor.get_level().clone();
+        editor.set_level(Level::new(
+            "other",
+            "26_angel",
+            "27_devil",
+            RevealEffect::Wipe,
+        ));
+        editor.undo();
+        assert_eq!(editor.get_level(), &original);
+        editor.open(original.clone());
+        editor.redo();
+        assert_eq!(editor.get_level(), &original);
+    }
+
+    #[test]
+    fn editor_actions_are_returned_to_the_caller() {
+        let mut editor = editor();
+        assert_eq!(
+            editor.handle_key("KeyT", false, false),
+            Some(EditorAction::TestPlay)
+        );
+        assert_eq!(
+            editor.handle_key("KeyB", false, false),
+            Some(EditorAction::PlayBuiltin)
+        );
+        assert_eq!(editor.handle_key("Escape", false, false), None);
+        assert!(!editor.is_open());
+    }
+}
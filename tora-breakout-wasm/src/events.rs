@@ -0,0 +1,33 @@
+use crate::bricks::BrickKind;
+
+/// シミュレーション中に起きた出来事
+/// collision_detectionやupdateが積んで、フレームの最後にまとめて処理する
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub enum GameEvent {
+    /// 壊せるブロックに当たった(壊れたかどうかは問わない)
+    BrickHit {
+        col: usize,
+        row: usize,
+    },
+    /// ブロックが壊れた x,yはブロックの中心
+    BrickBroken {
+        col: usize,
+        row: usize,
+        x: f64,
+        y: f64,
+        kind: BrickKind,
+    },
+    PaddleHit {
+        x: f64,
+        y: f64,
+    },
+    WallHit {
+        x: f64,
+        y: f64,
+    },
+    LifeLost,
+    StageCleared,
+    GameOver,
+}
+
+pub type Events = Vec<GameEvent>;
@@ -1,14 +1,49 @@
-use crate::consts::DEFAULT_LIVES;
 use crate::consts::DEFAULT_SCORE;
+use crate::consts::DEFAULT_STAGE;
+use crate::consts::{DEFAULT_LIVES, EASY_LIVES, HARD_LIVES};
+use crate::renderer::{Renderer, TextAlign};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Status {
+    Title,
     Prepare,
     Stop,
     Start,
+    Pause,
+    GameOver,
+    NameEntry,
+    // ステージクリアの表示中 終わると次のステージに進む
+    StageClear,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn get_label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+        }
+    }
+
+    pub fn get_lives(self) -> u32 {
+        match self {
+            Difficulty::Easy => EASY_LIVES,
+            Difficulty::Normal => DEFAULT_LIVES,
+            Difficulty::Hard => HARD_LIVES,
+        }
+    }
 }
 
 #[wasm_bindgen]
@@ -17,6 +52,16 @@ pub struct GameStatus {
     score: u32,
     lives: u32,
     status: Status,
+    stage: u32,
+    difficulty: Difficulty,
+    // プレイ時間計測用 Date.now()のミリ秒
+    started_at: f64,
+    finished_at: f64,
+    // 一時停止した時刻と、これまでに一時停止していた合計(ミリ秒) プレイ時間から除く
+    paused_at: f64,
+    paused_total: f64,
+    // クリアしたステージのプレイ時間の合計(秒)
+    cleared_duration: f64,
 }
 
 #[wasm_bindgen]
@@ -44,23 +89,124 @@ impl GameStatus {
         self.status = status;
     }
 
+    pub fn get_stage(&self) -> u32 {
+        self.stage
+    }
+
+    pub fn set_stage(&mut self, stage: u32) {
+        self.stage = stage;
+    }
+
+    /// 次のステージへ 点数、残機、難易度はそのままで、ボールを打ち出すところから始める
+    /// クリアタイムのボーナスはステージごとなので時間は測り直す
+    pub fn next_stage(&mut self) {
+        self.cleared_duration += self.get_duration(self.finished_at);
+        self.stage += 1;
+        self.status = Status::Stop;
+        self.started_at = 0.0;
+        self.finished_at = 0.0;
+        self.paused_at = 0.0;
+        self.paused_total = 0.0;
+    }
+
+    pub fn get_difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// 残機も難易度に合わせる ゲームを始める前に呼ぶ
+    pub fn set_difficulty(&mut self, difficulty: Difficulty) {
+        self.difficulty = difficulty;
+        self.lives = difficulty.get_lives();
+    }
+
+    /// 最初にボールを打ち出した時刻を記録する 2回目以降は無視
+    pub fn start_timer(&mut self, now: f64) {
+        if self.started_at == 0.0 {
+            self.started_at = now;
+        }
+    }
+
+    pub fn stop_timer(&mut self, now: f64) {
+        if self.finished_at == 0.0 {
+            self.finished_at = now;
+        }
+    }
+
+    pub fn pause_timer(&mut self, now: f64) {
+        if self.paused_at == 0.0 {
+            self.paused_at = now;
+        }
+    }
+
+    pub fn resume_timer(&mut self, now: f64) {
+        if self.paused_at != 0.0 {
+            self.paused_total += now - self.paused_at;
+            self.paused_at = 0.0;
+        }
+    }
+
+    /// プレイ時間(秒) 一時停止していた時間は含めない
+    pub fn get_duration(&self, now: f64) -> f64 {
+        if self.started_at == 0.0 {
+            return 0.0;
+        }
+        let end = if self.finished_at != 0.0 {
+            self.finished_at
+        } else if self.paused_at != 0.0 {
+            self.paused_at
+        } else {
+            now
+        };
+        (end - self.started_at - self.paused_total) / 1000.0
+    }
+
+    /// 最初のステージからのプレイ時間(秒) ハイスコアに残す
+    pub fn get_total_duration(&self, now: f64) -> f64 {
+        self.cleared_duration + self.get_duration(now)
+    }
+
     pub fn new() -> GameStatus {
         GameStatus {
             score: DEFAULT_SCORE,
             lives: DEFAULT_LIVES,
-            status: Status::Prepare,
+            status: Status::Title,
+            stage: DEFAULT_STAGE,
+            difficulty: Difficulty::Normal,
+            started_at: 0.0,
+            finished_at: 0.0,
+            paused_at: 0.0,
+            paused_total: 0.0,
+            cleared_duration: 0.0,
         }
     }
+}
 
-    pub fn draw_score(&self, ctx: &web_sys::CanvasRenderingContext2d) {
-        ctx.set_font("16px Arial");
-        ctx.set_fill_style(&"rgb(0, 149, 208)".into());
-        let _ = ctx.fill_text(&format!("Score: {}", self.score), 8.0, 20.0);
+impl GameStatus {
+    pub fn draw_score<R: Renderer>(&self, renderer: &mut R) {
+        renderer.fill_text(
+            &format!("Score: {}", self.score),
+            8.0,
+            20.0,
+            "16px Arial",
+            "rgb(0, 149, 208)",
+            TextAlign::Left,
+        );
     }
 
-    pub fn draw_lives(&self, ctx: &web_sys::CanvasRenderingContext2d, width: f64) {
-        ctx.set_font("16px Arial");
-        ctx.set_fill_style(&"rgb(0, 149, 208)".into());
-        let _ = ctx.fill_text(&format!("Lives: {}", self.lives), width - 65.0, 20.0);
+    pub fn draw_lives<R: Renderer>(&self, renderer: &mut R, width: f64) {
+        renderer.fill_text(
+            &format!("Lives: {}", self.lives),
+            width - 65.0,
+            20.0,
+            "16px Arial",
+            "rgb(0, 149, 208)",
+            TextAlign::Left,
+        );
+    }
+}
+
+impl Default for GameStatus {
+    fn default() -> GameStatus {
+        GameStatus::new()
     }
 }
@@ -0,0 +1,149 @@
+use crate::consts::GAMEPAD_DEADZONE;
+use crate::input::Action;
+use crate::renderer::{Renderer, TextAlign};
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton};
+
+// 標準マッピングのボタン番号
+const BUTTON_SOUTH: u32 = 0;
+const BUTTON_EAST: u32 = 1;
+const BUTTON_START: u32 = 9;
+const BUTTON_DPAD_LEFT: u32 = 14;
+const BUTTON_DPAD_RIGHT: u32 = 15;
+
+// 押した瞬間に操作を起こすボタン
+const ACTION_BUTTONS: [(u32, Action); 3] = [
+    (BUTTON_SOUTH, Action::Launch),
+    (BUTTON_EAST, Action::Fire),
+    (BUTTON_START, Action::Pause),
+];
+
+/// 1フレーム分のゲームパッドの入力
+#[derive(Debug, Clone, Default)]
+pub struct GamepadFrame {
+    // パドルの移動 -1.0(左)..1.0(右)
+    pub axis: f64,
+    // このフレームで押されたボタンの操作
+    pub actions: Vec<Action>,
+}
+
+/// navigator.getGamepads()を毎フレーム読む
+/// 最初に見つかった接続中のパッドを使い、抜き差しはidの変化で検出する
+#[derive(Debug, Clone, Default)]
+pub struct GamepadInput {
+    connected: Option<String>,
+    previous: [bool; ACTION_BUTTONS.len()],
+}
+
+impl GamepadInput {
+    pub fn new() -> GamepadInput {
+        GamepadInput::default()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.is_some()
+    }
+
+    pub fn poll(&mut self) -> GamepadFrame {
+        let pad = find_gamepad();
+        let id = pad.as_ref().map(|p| p.id());
+        if id != self.connected {
+            self.connected = id;
+            self.previous = Default::default();
+        }
+        let pad = match pad {
+            Some(pad) => pad,
+            None => return GamepadFrame::default(),
+        };
+
+        let mut frame = GamepadFrame::default();
+        let stick = pad.axes().get(0).as_f64().unwrap_or(0.0);
+        frame.axis = apply_deadzone(stick);
+        if is_pressed(&pad, BUTTON_DPAD_LEFT) {
+            frame.axis = -1.0;
+        } else if is_pressed(&pad, BUTTON_DPAD_RIGHT) {
+            frame.axis = 1.0;
+        }
+        for (i, &(button, action)) in ACTION_BUTTONS.iter().enumerate() {
+            let pressed = is_pressed(&pad, button);
+            if pressed && !self.previous[i] {
+                frame.actions.push(action);
+            }
+            self.previous[i] = pressed;
+        }
+        frame
+    }
+
+    /// 接続中は左下に表示する
+    pub fn draw_indicator<R: Renderer>(&self, renderer: &mut R, height: f64) {
+        if !self.is_connected() {
+            return;
+        }
+        renderer.fill_text(
+            "PAD",
+            8.0,
+            height - 16.0,
+            "12px Arial",
+            "rgb(0, 149, 208)",
+            TextAlign::Left,
+        );
+    }
+}
+
+fn find_gamepad() -> Option<Gamepad> {
+    let pads = web_sys::window()?.navigator().get_gamepads().ok()?;
+    pads.iter()
+        .filter_map(|p| p.dyn_into::<Gamepad>().ok())
+        .find(|p| p.connected())
+}
+
+fn is_pressed(pad: &Gamepad, button: u32) -> bool {
+    pad.buttons()
+        .get(button)
+        .dyn_into::<GamepadButton>()
+        .map(|b| b.pressed())
+        .unwrap_or(false)
+}
+
+/// 遊びより小さい傾きは0にし、遊びの外側を0.0..1.0に伸ばす
+fn apply_deadzone(value: f64) -> f64 {
+    let magnitude = value.abs();
+    if magnitude < GAMEPAD_DEADZONE {
+        return 0.0;
+    }
+    value.signum() * ((magnitude - GAMEPAD_DEADZONE) / (1.0 - GAMEPAD_DEADZONE)).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_tilt_inside_the_deadzone_is_ignored() {
+        assert_eq!(apply_deadzone(0.0), 0.0);
+        assert_eq!(apply_deadzone(GAMEPAD_DEADZONE / 2.0), 0.0);
+        assert_eq!(apply_deadzone(-GAMEPAD_DEADZONE / 2.0), 0.0);
+    }
+
+    #[test]
+    fn edge_of_the_deadzone_starts_from_zero() {
+        assert_eq!(apply_deadzone(GAMEPAD_DEADZONE), 0.0);
+        let just_outside = apply_deadzone(GAMEPAD_DEADZONE + 0.01);
+        assert!(just_outside > 0.0 && just_outside < 0.05);
+    }
+
+    #[test]
+    fn full_tilt_is_full_speed() {
+        assert_eq!(apply_deadzone(1.0), 1.0);
+        assert_eq!(apply_deadzone(-1.0), -1.0);
+        // 1.0を少し超える値を返すパッドもある
+        assert_eq!(apply_deadzone(1.05), 1.0);
+    }
+
+    #[test]
+    fn direction_is_kept() {
+        let half = (1.0 + GAMEPAD_DEADZONE) / 2.0;
+        assert!((apply_deadzone(half) - 0.5).abs() < 1e-9);
+        assert!((apply_deadzone(-half) + 0.5).abs() < 1e-9);
+    }
+}
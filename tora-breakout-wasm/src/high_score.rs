@@ -0,0 +1,323 @@
+use crate::consts::{HIGH_SCORE_COUNT, HIGH_SCORE_STORAGE_KEY, NAME_MAX_LENGTH};
+use crate::game_status::{Difficulty, GameStatus, Status};
+use crate::renderer::{Renderer, TextAlign};
+use crate::utils::local_storage;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    name: String,
+    score: u32,
+    stage: u32,
+    difficulty: Difficulty,
+    // YYYY-MM-DD
+    date: String,
+    // 秒
+    duration: f64,
+}
+
+impl HighScoreEntry {
+    pub fn new(name: String, status: &GameStatus, date: String, duration: f64) -> HighScoreEntry {
+        HighScoreEntry {
+            name,
+            score: status.get_score(),
+            stage: status.get_stage(),
+            difficulty: status.get_difficulty(),
+            date,
+            duration,
+        }
+    }
+}
+
+/// 上位HIGH_SCORE_COUNT件のスコアとネームエントリー中の入力を持つ
+#[derive(Debug, Clone, Default)]
+pub struct HighScores {
+    entries: Vec<HighScoreEntry>,
+    name_input: String,
+}
+
+impl HighScores {
+    /// localStorageから読み込む 壊れたデータが入っていた場合は空で始める
+    pub fn load() -> HighScores {
+        let entries = local_storage()
+            .and_then(|storage| storage.get_item(HIGH_SCORE_STORAGE_KEY).ok()?)
+            .and_then(|json| serde_json::from_str::<Vec<HighScoreEntry>>(&json).ok())
+            .unwrap_or_default();
+        let mut high_scores = HighScores {
+            entries: Vec::new(),
+            name_input: String::new(),
+        };
+        for entry in entries {
+            high_scores.insert(entry);
+        }
+        high_scores
+    }
+
+    pub fn save(&self) {
+        let storage = match local_storage() {
+            Some(s) => s,
+            None => return,
+        };
+        if let Ok(json) = serde_json::to_string(&self.entries) {
+            let _ = storage.set_item(HIGH_SCORE_STORAGE_KEY, &json);
+        }
+    }
+
+    /// 結果の表示の後に名前を聞くかどうか 残機がなくなって終わったときだけで、ステージクリアでは聞かない
+    pub fn wants_name_entry(&self, status: &GameStatus) -> bool {
+        status.get_status() == Status::GameOver
+            && status.get_lives() == 0
+            && self.is_high_score(status.get_score())
+    }
+
+    pub fn is_high_score(&self, score: u32) -> bool {
+        if score == 0 {
+            return false;
+        }
+        self.entries.len() < HIGH_SCORE_COUNT || score > self.entries[HIGH_SCORE_COUNT - 1].score
+    }
+
+    /// スコア順の位置に挿入して順位(0始まり)を返す ランク外ならNone
+    pub fn insert(&mut self, entry: HighScoreEntry) -> Option<usize> {
+        // 同点の場合は先に登録された方を上位にする
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| entry.score > e.score)
+            .unwrap_or(self.entries.len());
+        if rank >= HIGH_SCORE_COUNT {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(HIGH_SCORE_COUNT);
+        Some(rank)
+    }
+
+    pub fn push_char(&mut self, c: char) {
+        if self.name_input.chars().count() >= NAME_MAX_LENGTH || !c.is_ascii_alphanumeric() {
+            return;
+        }
+        self.name_input.push(c.to_ascii_uppercase());
+    }
+
+    pub fn pop_char(&mut self) {
+        self.name_input.pop();
+    }
+
+    /// 入力中の名前で登録して保存する 空なら仮の名前にする
+    pub fn commit(&mut self, status: &GameStatus, date: String, duration: f64) -> Option<usize> {
+        let name = if self.name_input.is_empty() {
+            "NONAME".to_string()
+        } else {
+            std::mem::take(&mut self.name_input)
+        };
+        let rank = self.insert(HighScoreEntry::new(name, status, date, duration));
+        self.save();
+        rank
+    }
+
+    /// タイトル画面用のハイスコア一覧
+    pub fn draw_table<R: Renderer>(&self, renderer: &mut R, width: f64, top: f64) {
+        renderer.fill_text(
+            "HIGH SCORES",
+            width / 2.0,
+            top,
+            "16px Arial",
+            "rgb(255, 204, 0)",
+            TextAlign::Center,
+        );
+        if self.entries.is_empty() {
+            renderer.fill_text(
+                "NO RECORDS YET",
+                width / 2.0,
+                top + 28.0,
+                "14px monospace",
+                "rgb(255, 255, 255)",
+                TextAlign::Center,
+            );
+        }
+        for (i, e) in self.entries.iter().enumerate() {
+            let line = format!(
+                "{:>2}. {:<8} {:>6} ST{} {:?} {} {:>4.0}s",
+                i + 1,
+                e.name,
+                e.score,
+                e.stage,
+                e.difficulty,
+                e.date,
+                e.duration
+            );
+            renderer.fill_text(
+                &line,
+                width / 2.0,
+                top + 28.0 + i as f64 * 20.0,
+                "14px monospace",
+                "rgb(255, 255, 255)",
+                TextAlign::Center,
+            );
+        }
+    }
+
+    /// ゲームオーバー時のネームエントリー画面
+    pub fn draw_name_entry<R: Renderer>(
+        &self,
+        renderer: &mut R,
+        status: &GameStatus,
+        width: f64,
+        height: f64,
+    ) {
+        let center = width / 2.0;
+        let middle = height / 2.0;
+        renderer.fill_rect(0.0, 0.0, width, height, "rgba(0, 0, 0, 0.7)");
+        renderer.fill_text(
+            "NEW HIGH SCORE!",
+            center,
+            middle - 60.0,
+            "24px Arial",
+            "rgb(255, 204, 0)",
+            TextAlign::Center,
+        );
+        renderer.fill_text(
+            &format!("Score: {}", status.get_score()),
+            center,
+            middle - 30.0,
+            "16px Arial",
+            "rgb(255, 255, 255)",
+            TextAlign::Center,
+        );
+        renderer.fill_text(
+            "ENTER YOUR NAME",
+            center,
+            middle,
+            "16px Arial",
+            "rgb(255, 255, 255)",
+            TextAlign::Center,
+        );
+        renderer.fill_text(
+            &format!("{:_<width$}", self.name_input, width = NAME_MAX_LENGTH),
+            center,
+            middle + 36.0,
+            "24px monospace",
+            "rgb(255, 255, 255)",
+            TextAlign::Center,
+        );
+        renderer.fill_text(
+            "A-Z 0-9 / BACKSPACE / ENTER",
+            center,
+            middle + 64.0,
+            "12px Arial",
+            "rgb(255, 255, 255)",
+            TextAlign::Center,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: u32) -> HighScoreEntry {
+        let mut status = GameStatus::new();
+        status.set_score(score);
+        HighScoreEntry::new(name.to_string(), &status, "2020-01-01".to_string(), 60.0)
+    }
+
+    fn names(high_scores: &HighScores) -> Vec<&str> {
+        high_scores
+            .entries
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn insert_keeps_entries_sorted_by_score() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.insert(entry("B", 200)), Some(0));
+        assert_eq!(high_scores.insert(entry("C", 100)), Some(1));
+        assert_eq!(high_scores.insert(entry("A", 300)), Some(0));
+        assert_eq!(names(&high_scores), vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn tie_ranks_below_the_earlier_entry() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(entry("FIRST", 100));
+        assert_eq!(high_scores.insert(entry("SECOND", 100)), Some(1));
+        assert_eq!(names(&high_scores), vec!["FIRST", "SECOND"]);
+    }
+
+    #[test]
+    fn insert_truncates_to_the_table_size() {
+        let mut high_scores = HighScores::default();
+        for i in 0..HIGH_SCORE_COUNT as u32 {
+            high_scores.insert(entry("OLD", 100 + i));
+        }
+        // 最下位と同点はランク外
+        assert_eq!(high_scores.insert(entry("TIE", 100)), None);
+        assert_eq!(high_scores.insert(entry("NEW", 1000)), Some(0));
+        assert_eq!(high_scores.entries.len(), HIGH_SCORE_COUNT);
+        assert_eq!(high_scores.entries.last().unwrap().score, 101);
+    }
+
+    #[test]
+    fn is_high_score_compares_with_the_last_entry_when_full() {
+        let mut high_scores = HighScores::default();
+        assert!(!high_scores.is_high_score(0));
+        assert!(high_scores.is_high_score(1));
+        for i in 0..HIGH_SCORE_COUNT as u32 {
+            high_scores.insert(entry("OLD", 100 + i));
+        }
+        assert!(!high_scores.is_high_score(100));
+        assert!(high_scores.is_high_score(101));
+    }
+
+    #[test]
+    fn name_input_is_uppercase_alphanumeric_and_limited() {
+        let mut high_scores = HighScores::default();
+        for c in "ab-c1234567".chars() {
+            high_scores.push_char(c);
+        }
+        assert_eq!(high_scores.name_input, "ABC12345");
+        high_scores.pop_char();
+        assert_eq!(high_scores.name_input, "ABC1234");
+    }
+
+    #[test]
+    fn only_a_real_game_over_asks_for_a_name() {
+        let high_scores = HighScores::default();
+        let mut status = GameStatus::new();
+        status.set_score(500);
+        status.set_status(Status::StageClear);
+        assert!(!high_scores.wants_name_entry(&status));
+        status.set_status(Status::GameOver);
+        assert!(!high_scores.wants_name_entry(&status));
+        status.set_lives(0);
+        assert!(high_scores.wants_name_entry(&status));
+        status.set_score(0);
+        assert!(!high_scores.wants_name_entry(&status));
+    }
+
+    #[test]
+    fn entry_records_the_stage_reached_and_the_whole_play_time() {
+        let mut status = GameStatus::new();
+        status.set_score(900);
+        for stage in 0..2 {
+            let start = 1000.0 + stage as f64 * 100_000.0;
+            status.start_timer(start);
+            status.stop_timer(start + 30_000.0);
+            status.next_stage();
+        }
+        status.start_timer(500_000.0);
+        let duration = status.get_total_duration(510_000.0);
+        assert_eq!(duration, 70.0);
+        let mut high_scores = HighScores::default();
+        high_scores.insert(HighScoreEntry::new(
+            "AAA".to_string(),
+            &status,
+            "2020-01-01".to_string(),
+            duration,
+        ));
+        assert_eq!(high_scores.entries[0].stage, 3);
+        assert_eq!(high_scores.entries[0].duration, 70.0);
+    }
+}
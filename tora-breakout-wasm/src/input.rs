@@ -0,0 +1,168 @@
+/// キーに割り当てる操作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Launch,
+    Pause,
+    Fire,
+}
+
+impl Action {
+    /// 設定画面に並べる順
+    pub fn all() -> [Action; 5] {
+        [
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::Launch,
+            Action::Pause,
+            Action::Fire,
+        ]
+    }
+
+    pub fn get_label(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "MOVE LEFT",
+            Action::MoveRight => "MOVE RIGHT",
+            Action::Launch => "LAUNCH",
+            Action::Pause => "PAUSE",
+            Action::Fire => "FIRE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct Binding {
+    action: Action,
+    // KeyboardEvent.codeの値 キーボード配列に関係なく物理的な位置で決まる
+    keys: Vec<String>,
+}
+
+/// 物理キーから操作への対応
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct KeyBindings {
+    bindings: Vec<Binding>,
+}
+
+impl KeyBindings {
+    pub fn action_for(&self, code: &str) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|b| b.keys.iter().any(|k| k == code))
+            .map(|b| b.action)
+    }
+
+    pub fn keys_for(&self, action: Action) -> &[String] {
+        self.bindings
+            .iter()
+            .find(|b| b.action == action)
+            .map(|b| &b.keys[..])
+            .unwrap_or(&[])
+    }
+
+    /// actionをcodeだけに割り当て直す 他の操作に同じキーがあればそちらからは外す
+    pub fn rebind(&mut self, action: Action, code: &str) {
+        for binding in self.bindings.iter_mut() {
+            binding.keys.retain(|k| k != code);
+        }
+        match self.bindings.iter_mut().find(|b| b.action == action) {
+            Some(binding) => binding.keys = vec![code.to_string()],
+            None => self.bindings.push(Binding {
+                action,
+                keys: vec![code.to_string()],
+            }),
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        let binding = |action, keys: &[&str]| Binding {
+            action,
+            keys: keys.iter().map(|k| k.to_string()).collect(),
+        };
+        KeyBindings {
+            bindings: vec![
+                binding(Action::MoveLeft, &["ArrowLeft", "KeyA"]),
+                binding(Action::MoveRight, &["ArrowRight", "KeyD"]),
+                binding(Action::Launch, &["Space"]),
+                binding(Action::Pause, &["KeyP", "Escape"]),
+                binding(Action::Fire, &["ArrowUp", "KeyW"]),
+            ],
+        }
+    }
+}
+
+/// 画面に出すキーの名前 "KeyA"は"A"、"ArrowLeft"は"LEFT"
+pub fn display_key(code: &str) -> String {
+    let name = code
+        .strip_prefix("Key")
+        .or_else(|| code.strip_prefix("Digit"))
+        .or_else(|| code.strip_prefix("Arrow"))
+        .unwrap_or(code);
+    name.to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_cover_every_action() {
+        let bindings = KeyBindings::default();
+        for action in Action::all().iter() {
+            assert!(!bindings.keys_for(*action).is_empty(), "{:?}", action);
+        }
+        assert_eq!(bindings.action_for("KeyA"), Some(Action::MoveLeft));
+        assert_eq!(bindings.action_for("Escape"), Some(Action::Pause));
+        assert_eq!(bindings.action_for("KeyZ"), None);
+    }
+
+    #[test]
+    fn rebind_replaces_all_keys_of_the_action() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::MoveLeft, "KeyJ");
+        assert_eq!(bindings.keys_for(Action::MoveLeft), ["KeyJ".to_string()]);
+        assert_eq!(bindings.action_for("KeyJ"), Some(Action::MoveLeft));
+        assert_eq!(bindings.action_for("ArrowLeft"), None);
+        assert_eq!(bindings.action_for("KeyA"), None);
+    }
+
+    #[test]
+    fn rebind_takes_the_key_away_from_another_action() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::Launch, "KeyD");
+        assert_eq!(bindings.action_for("KeyD"), Some(Action::Launch));
+        // MoveRightにはもう片方のキーが残る
+        assert_eq!(
+            bindings.keys_for(Action::MoveRight),
+            ["ArrowRight".to_string()]
+        );
+        assert_eq!(bindings.action_for("Space"), None);
+    }
+
+    #[test]
+    fn rebind_adds_a_missing_action() {
+        let mut bindings: KeyBindings = serde_json::from_str(r#"{"bindings": []}"#).unwrap();
+        bindings.rebind(Action::Fire, "KeyF");
+        assert_eq!(bindings.action_for("KeyF"), Some(Action::Fire));
+    }
+
+    #[test]
+    fn bindings_survive_a_serde_round_trip() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::Pause, "KeyQ");
+        let json = serde_json::to_string(&bindings).unwrap();
+        let restored: KeyBindings = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, bindings);
+        assert_eq!(restored.action_for("KeyQ"), Some(Action::Pause));
+    }
+
+    #[test]
+    fn display_key_strips_the_code_prefix() {
+        assert_eq!(display_key("KeyA"), "A");
+        assert_eq!(display_key("Digit1"), "1");
+        assert_eq!(display_key("ArrowLeft"), "LEFT");
+        assert_eq!(display_key("Space"), "SPACE");
+    }
+}
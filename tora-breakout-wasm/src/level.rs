@@ -0,0 +1,412 @@
+use crate::bricks::BrickKind;
+use crate::consts::{BRICK_COLUMN_COUNT, BRICK_ROW_COUNT, PICTURE_SETS};
+use crate::tween::Easing;
+use std::collections::VecDeque;
+
+// レイアウトでブロックを置かないマスの文字 最初から裏の絵が見えている
+pub const EMPTY_SYMBOL: char = '.';
+
+/// ブロックが壊れて裏の絵が出てくるときの見せ方
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RevealEffect {
+    /// 横に裏返す
+    Flip,
+    /// 表の絵が薄くなって消える
+    Dissolve,
+    /// 左から右へ拭き取る
+    Wipe,
+}
+
+impl RevealEffect {
+    pub fn all() -> [RevealEffect; 3] {
+        [
+            RevealEffect::Flip,
+            RevealEffect::Dissolve,
+            RevealEffect::Wipe,
+        ]
+    }
+
+    pub fn get_easing(self) -> Easing {
+        match self {
+            RevealEffect::Flip => Easing::EaseInOutQuad,
+            RevealEffect::Dissolve => Easing::EaseOutQuad,
+            RevealEffect::Wipe => Easing::EaseInOutCubic,
+        }
+    }
+}
+
+/// ステージごとの設定 表の絵と裏の絵はimgs/以下のファイル名の先頭部分
+/// rowsは上の行から順に1行1文字列で、1文字が1列 Nは普通、Hは硬い、#は壊れないブロック、.は何も置かない
+/// rowsが空なら全部普通のブロックで埋める
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Level {
+    name: String,
+    cover: String,
+    hidden: String,
+    reveal: RevealEffect,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rows: Vec<String>,
+}
+
+impl Level {
+    pub fn new(name: &str, cover: &str, hidden: &str, reveal: RevealEffect) -> Level {
+        Level {
+            name: name.to_string(),
+            cover: cover.to_string(),
+            hidden: hidden.to_string(),
+            reveal,
+            rows: Vec::new(),
+        }
+    }
+
+    /// 組み込みのステージ 絵は同じで見せ方と硬いブロックの並びを変える
+    pub fn builtin() -> Vec<Level> {
+        vec![
+            // 上の2行が硬い
+            Level::new("flip", "27_devil", "26_angel", RevealEffect::Flip)
+                .with_layout(|_, row| row < 2),
+            // 4行ごとに硬い行
+            Level::new("dissolve", "27_devil", "26_angel", RevealEffect::Dissolve)
+                .with_layout(|_, row| row % 4 == 3),
+            // 上の行と左右の端が硬い
+            Level::new("wipe", "27_devil", "26_angel", RevealEffect::Wipe)
+                .with_layout(|col, row| row == 0 || col == 0 || col == BRICK_COLUMN_COUNT - 1),
+        ]
+    }
+
+    /// 盤面全部にブロックを置く hard(列, 行)がtrueのマスは硬いブロック
+    fn with_layout(mut self, hard: impl Fn(usize, usize) -> bool) -> Level {
+        self.rows = (0..BRICK_ROW_COUNT)
+            .map(|row| {
+                (0..BRICK_COLUMN_COUNT)
+                    .map(|col| {
+                        let kind = if hard(col, row) {
+                            BrickKind::Hard
+                        } else {
+                            BrickKind::Normal
+                        };
+                        kind.get_symbol()
+                    })
+                    .collect()
+            })
+            .collect();
+        self
+    }
+
+    /// ステージ番号(1始まり)の組み込みステージ 数が足りなければ最初から繰り返す
+    pub fn for_stage(stage: u32) -> Level {
+        let levels = Level::builtin();
+        let index = (stage.max(1) - 1) as usize % levels.len();
+        levels[index].clone()
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_cover(&self) -> &str {
+        &self.cover
+    }
+
+    pub fn get_hidden(&self) -> &str {
+        &self.hidden
+    }
+
+    pub fn get_reveal(&self) -> RevealEffect {
+        self.reveal
+    }
+
+    pub fn set_reveal(&mut self, reveal: RevealEffect) {
+        self.reveal = reveal;
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    pub fn set_cover(&mut self, cover: &str) {
+        self.cover = cover.to_string();
+    }
+
+    pub fn set_hidden(&mut self, hidden: &str) {
+        self.hidden = hidden.to_string();
+    }
+
+    pub fn get_rows(&self) -> &[String] {
+        &self.rows
+    }
+
+    /// 列の数 行ごとに長さが違えば一番長い行に合わせる
+    pub fn get_column_count(&self) -> usize {
+        if self.rows.is_empty() {
+            return BRICK_COLUMN_COUNT;
+        }
+        self.rows
+            .iter()
+            .map(|r| r.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn get_row_count(&self) -> usize {
+        if self.rows.is_empty() {
+            return BRICK_ROW_COUNT;
+        }
+        self.rows.len()
+    }
+
+    /// そのマスのブロック 置かないマスや知らない文字ならNone
+    pub fn get_cell(&self, col: usize, row: usize) -> Option<BrickKind> {
+        if self.rows.is_empty() {
+            return Some(BrickKind::Normal);
+        }
+        self.rows
+            .get(row)
+            .and_then(|r| r.chars().nth(col))
+            .and_then(BrickKind::from_symbol)
+    }
+
+    pub fn set_cell(&mut self, col: usize, row: usize, kind: Option<BrickKind>) {
+        if col >= self.get_column_count() || row >= self.get_row_count() {
+            return;
+        }
+        self.materialize_rows();
+        let columns = self.get_column_count();
+        let mut cells: Vec<char> = self.rows[row].chars().collect();
+        cells.resize(columns, EMPTY_SYMBOL);
+        cells[col] = kind.map(|k| k.get_symbol()).unwrap_or(EMPTY_SYMBOL);
+        self.rows[row] = cells.into_iter().collect();
+    }
+
+    /// 盤面の大きさを変える 増えたマスは普通のブロックにする
+    pub fn resize(&mut self, columns: usize, rows: usize) {
+        let columns = columns.clamp(1, BRICK_COLUMN_COUNT);
+        let rows = rows.clamp(1, BRICK_ROW_COUNT);
+        self.materialize_rows();
+        let normal = BrickKind::Normal.get_symbol();
+        self.rows.resize(rows, String::new());
+        for row in self.rows.iter_mut() {
+            let mut cells: Vec<char> = row.chars().take(columns).collect();
+            cells.resize(columns, normal);
+            *row = cells.into_iter().collect();
+        }
+    }
+
+    /// ゲームで遊べるレベルか確かめる 絵の組、盤面の大きさ、知らない文字、クリアできるかどうか
+    pub fn validate(&self) -> Result<(), String> {
+        for set in [&self.cover, &self.hidden].iter() {
+            if !PICTURE_SETS.contains(&set.as_str()) {
+                return Err(format!(
+                    "unknown picture set '{}', expected one of {}",
+                    set,
+                    PICTURE_SETS.join(", ")
+                ));
+            }
+        }
+        if self.rows.len() > BRICK_ROW_COUNT {
+            return Err(format!(
+                "level has {} rows but at most {} fit in the playfield",
+                self.rows.len(),
+                BRICK_ROW_COUNT
+            ));
+        }
+        for (r, row) in self.rows.iter().enumerate() {
+            let columns = row.chars().count();
+            if columns > BRICK_COLUMN_COUNT {
+                return Err(format!(
+                    "row {} has {} cells but at most {} fit in the playfield",
+                    r + 1,
+                    columns,
+                    BRICK_COLUMN_COUNT
+                ));
+            }
+            if let Some(symbol) = row
+                .chars()
+                .find(|&c| c != EMPTY_SYMBOL && BrickKind::from_symbol(c).is_none())
+            {
+                return Err(format!("row {} has unknown brick '{}'", r + 1, symbol));
+            }
+        }
+        let breakable = (0..self.get_row_count()).any(|row| {
+            (0..self.get_column_count())
+                .any(|col| self.get_cell(col, row).is_some_and(|k| k.is_breakable()))
+        });
+        if !breakable {
+            return Err("level has no breakable bricks, so it can never be cleared".to_string());
+        }
+        if let Some(&(col, row)) = self.get_unreachable_bricks().first() {
+            return Err(format!(
+                "brick at row {}, column {} is enclosed by unbreakable bricks and can never be hit",
+                row + 1,
+                col + 1
+            ));
+        }
+        Ok(())
+    }
+
+    /// 壊れないブロックに囲まれていてボールが届かないブロックの(列, 行) 行ごとに上から
+    /// 盤面の上には隙間があり、下はパドルの方に開いているので、そこから壊れないブロック以外のマスを上下左右にたどる
+    pub fn get_unreachable_bricks(&self) -> Vec<(usize, usize)> {
+        let passable = |col: usize, row: usize| {
+            self.get_cell(col, row)
+                .map(|k| k.is_breakable())
+                .unwrap_or(true)
+        };
+        let mut reached = vec![vec![false; BRICK_ROW_COUNT]; BRICK_COLUMN_COUNT];
+        let mut queue = VecDeque::new();
+        for (col, column) in reached.iter_mut().enumerate() {
+            for &row in [0, BRICK_ROW_COUNT - 1].iter() {
+                if passable(col, row) && !column[row] {
+                    column[row] = true;
+                    queue.push_back((col, row));
+                }
+            }
+        }
+        while let Some((col, row)) = queue.pop_front() {
+            let mut neighbours = vec![(col + 1, row), (col, row + 1)];
+            if col > 0 {
+                neighbours.push((col - 1, row));
+            }
+            if row > 0 {
+                neighbours.push((col, row - 1));
+            }
+            for (c, r) in neighbours {
+                if c < BRICK_COLUMN_COUNT && r < BRICK_ROW_COUNT && !reached[c][r] && passable(c, r)
+                {
+                    reached[c][r] = true;
+                    queue.push_back((c, r));
+                }
+            }
+        }
+        (0..BRICK_ROW_COUNT)
+            .flat_map(|row| (0..BRICK_COLUMN_COUNT).map(move |col| (col, row)))
+            .filter(|&(col, row)| {
+                self.get_cell(col, row).is_some_and(|k| k.is_breakable()) && !reached[col][row]
+            })
+            .collect()
+    }
+
+    /// 既定のレイアウトを文字列にしておく 編集する前に呼ぶ
+    fn materialize_rows(&mut self) {
+        if !self.rows.is_empty() {
+            return;
+        }
+        let row = BrickKind::Normal
+            .get_symbol()
+            .to_string()
+            .repeat(BRICK_COLUMN_COUNT);
+        self.rows = vec![row; BRICK_ROW_COUNT];
+    }
+}
+
+impl Default for Level {
+    fn default() -> Level {
+        Level::for_stage(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_stages_fill_the_grid_and_have_hard_bricks() {
+        for level in Level::builtin() {
+            assert_eq!(level.get_row_count(), BRICK_ROW_COUNT);
+            assert_eq!(level.get_column_count(), BRICK_COLUMN_COUNT);
+            let kinds: Vec<BrickKind> = (0..BRICK_ROW_COUNT)
+                .flat_map(|row| (0..BRICK_COLUMN_COUNT).map(move |col| (col, row)))
+                .map(|(col, row)| level.get_cell(col, row).unwrap())
+                .collect();
+            assert!(kinds.contains(&BrickKind::Hard), "{}", level.get_name());
+            assert!(kinds.contains(&BrickKind::Normal), "{}", level.get_name());
+        }
+    }
+
+    fn with_rows(rows: &[&str]) -> Level {
+        let mut level = Level::new("test", "27_devil", "26_angel", RevealEffect::Flip);
+        level.rows = rows.iter().map(|r| r.to_string()).collect();
+        level
+    }
+
+    #[test]
+    fn builtin_stages_are_valid() {
+        for level in Level::builtin() {
+            assert_eq!(level.validate(), Ok(()), "{}", level.get_name());
+        }
+    }
+
+    #[test]
+    fn validate_rejects_unknown_picture_sets() {
+        let mut level = with_rows(&["N"]);
+        level.set_hidden("28_nobody");
+        assert!(level.validate().unwrap_err().contains("28_nobody"));
+    }
+
+    #[test]
+    fn validate_rejects_grids_larger_than_the_playfield() {
+        let too_wide = "N".repeat(BRICK_COLUMN_COUNT + 1);
+        assert!(with_rows(&[too_wide.as_str()]).validate().is_err());
+        let too_tall = vec!["N"; BRICK_ROW_COUNT + 1];
+        assert!(with_rows(&too_tall).validate().is_err());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_symbols_and_unclearable_levels() {
+        assert!(with_rows(&["NX"]).validate().unwrap_err().contains("'X'"));
+        assert!(with_rows(&["##", ".."]).validate().is_err());
+    }
+
+    #[test]
+    fn bricks_enclosed_by_unbreakable_bricks_are_unreachable() {
+        let level = with_rows(&["...", ".#.", "#N#", ".#."]);
+        assert_eq!(level.get_unreachable_bricks(), vec![(1, 2)]);
+        assert!(level.validate().is_err());
+        assert!(with_rows(&["...", ".#.", "#N.", ".#."])
+            .get_unreachable_bricks()
+            .is_empty());
+    }
+
+    #[test]
+    fn resize_clamps_to_the_playfield_and_fills_new_cells_with_normal_bricks() {
+        let mut level = with_rows(&["H."]);
+        level.resize(3, 2);
+        assert_eq!(level.get_rows(), ["H.N", "NNN"]);
+        level.resize(0, 0);
+        assert_eq!(level.get_rows(), ["H"]);
+        level.resize(BRICK_COLUMN_COUNT + 5, BRICK_ROW_COUNT + 5);
+        assert_eq!(level.get_column_count(), BRICK_COLUMN_COUNT);
+        assert_eq!(level.get_row_count(), BRICK_ROW_COUNT);
+    }
+
+    #[test]
+    fn resize_of_the_default_layout_keeps_the_normal_bricks() {
+        let mut level = Level::new("test", "27_devil", "26_angel", RevealEffect::Flip);
+        assert!(level.get_rows().is_empty());
+        level.resize(2, 3);
+        assert_eq!(level.get_rows(), ["NN", "NN", "NN"]);
+    }
+
+    #[test]
+    fn set_cell_pads_short_rows_and_ignores_cells_outside_the_grid() {
+        let mut level = with_rows(&["NNN", "N"]);
+        assert_eq!(level.get_cell(2, 1), None);
+        level.set_cell(2, 1, Some(BrickKind::Hard));
+        assert_eq!(level.get_rows(), ["NNN", "N.H"]);
+        level.set_cell(0, 0, None);
+        assert_eq!(level.get_cell(0, 0), None);
+        level.set_cell(3, 0, Some(BrickKind::Hard));
+        level.set_cell(0, 2, Some(BrickKind::Hard));
+        assert_eq!(level.get_rows(), [".NN", "N.H"]);
+    }
+
+    #[test]
+    fn set_cell_on_the_default_layout_writes_out_the_full_grid() {
+        let mut level = Level::new("test", "27_devil", "26_angel", RevealEffect::Flip);
+        assert_eq!(level.get_cell(4, 7), Some(BrickKind::Normal));
+        level.set_cell(4, 7, Some(BrickKind::Unbreakable));
+        assert_eq!(level.get_rows().len(), BRICK_ROW_COUNT);
+        assert_eq!(level.get_cell(4, 7), Some(BrickKind::Unbreakable));
+        assert_eq!(level.get_cell(5, 7), Some(BrickKind::Normal));
+    }
+}
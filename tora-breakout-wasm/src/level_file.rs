@@ -0,0 +1,115 @@
+use crate::level::Level;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{console, Blob, BlobPropertyBag, Document, HtmlAnchorElement, HtmlInputElement, Url};
+
+/// レベルをJSONのファイルとしてダウンロードさせる ファイル名はレベルの名前
+pub fn download(document: &Document, level: &Level) -> Result<(), JsValue> {
+    let json =
+        serde_json::to_string_pretty(level).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let options = BlobPropertyBag::new();
+    options.set_type("application/json");
+    let blob = Blob::new_with_str_sequence_and_options(
+        &js_sys::Array::of1(&JsValue::from_str(&json)),
+        &options,
+    )?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+    let anchor = document
+        .create_element("a")?
+        .dyn_into::<HtmlAnchorElement>()?;
+    anchor.set_href(&url);
+    anchor.set_download(&format!("{}.json", level.get_name()));
+    anchor.click();
+    Url::revoke_object_url(&url)
+}
+
+/// レベルのJSONを読んでゲームで遊べるか確かめる loadLevelと読み込みで同じものを使う
+pub fn parse(json: &str) -> Result<Level, String> {
+    let level: Level = serde_json::from_str(json).map_err(|e| format!("invalid level: {}", e))?;
+    level
+        .validate()
+        .map_err(|e| format!("invalid level: {}", e))?;
+    Ok(level)
+}
+
+/// ファイルを選ぶダイアログ
+/// 選ばずに閉じるとonchangeが呼ばれないので、そのクロージャは次に開くときかこれを捨てるときに解放する
+#[derive(Default)]
+pub struct FileDialog {
+    on_change: RefCell<Option<Closure<dyn FnMut()>>>,
+}
+
+impl FileDialog {
+    pub fn new() -> FileDialog {
+        FileDialog::default()
+    }
+
+    /// ファイルを選ばせてレベルのJSONを読み込む 遊べるレベルならon_loadを呼び、そうでなければコンソールに出す
+    pub fn open(
+        &self,
+        document: &Document,
+        on_load: impl FnOnce(Level) + 'static,
+    ) -> Result<(), JsValue> {
+        let input = document
+            .create_element("input")?
+            .dyn_into::<HtmlInputElement>()?;
+        input.set_type("file");
+        input.set_accept("application/json,.json");
+        let target = input.clone();
+        let on_change = Closure::once(move || {
+            let file = match target.files().and_then(|files| files.get(0)) {
+                Some(file) => file,
+                None => return,
+            };
+            spawn_local(async move {
+                let level = JsFuture::from(file.text()).await.and_then(|text| {
+                    let text = text.as_string().unwrap_or_default();
+                    parse(&text).map_err(|e| JsValue::from_str(&e))
+                });
+                match level {
+                    Ok(level) => on_load(level),
+                    Err(e) => console::error_1(&e),
+                }
+            });
+        });
+        input.set_onchange(Some(on_change.as_ref().unchecked_ref()));
+        // 前のダイアログが選ばれずに閉じられていれば、そのクロージャはここで解放される
+        *self.on_change.borrow_mut() = Some(on_change);
+        input.click();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level::RevealEffect;
+
+    #[test]
+    fn exported_level_can_be_read_back() {
+        let level = Level::for_stage(2);
+        let json = serde_json::to_string_pretty(&level).unwrap();
+        assert_eq!(parse(&json).unwrap(), level);
+    }
+
+    #[test]
+    fn broken_json_is_rejected() {
+        let error = parse("{").unwrap_err();
+        assert!(error.starts_with("invalid level: "), "{}", error);
+    }
+
+    #[test]
+    fn level_that_cannot_be_played_is_rejected() {
+        let mut level = Level::new("test", "27_devil", "26_angel", RevealEffect::Flip);
+        level.set_cover("no_such_set");
+        let json = serde_json::to_string(&level).unwrap();
+        let error = parse(&json).unwrap_err();
+        assert!(error.starts_with("invalid level: "), "{}", error);
+        assert_eq!(
+            error,
+            format!("invalid level: {}", level.validate().unwrap_err())
+        );
+    }
+}
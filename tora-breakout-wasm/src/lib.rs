@@ -1,26 +1,80 @@
-mod ball;
-mod bricks;
+// 描画やスコア計算をブラウザなしでも確認できるようにゲームオブジェクトとRendererは公開する
+pub mod assets;
+mod audio;
+pub mod ball;
+pub mod bricks;
+mod callbacks;
+pub mod camera;
 
-mod bitmap_container;
-mod consts;
-mod game_status;
-mod paddle;
+pub mod bitmap_container;
+mod brick_layer;
+pub mod canvas_renderer;
+pub mod config;
+pub mod consts;
+mod controls_screen;
+mod debug_overlay;
+pub mod editor;
+pub mod events;
+pub mod game_status;
+mod gamepad;
+mod high_score;
+mod input;
+pub mod level;
+mod level_file;
+mod listeners;
+mod music;
+pub mod paddle;
+pub mod particles;
+mod pointer;
+pub mod recording_renderer;
+pub mod renderer;
+pub mod scoring;
+mod settings;
+pub mod snapshot;
+pub mod software_renderer;
+pub mod svg_renderer;
+pub mod tween;
 mod utils;
+mod viewport;
 
+use crate::assets::AssetManifest;
+use crate::audio::Audio;
 use crate::ball::Ball;
-use crate::bitmap_container::get_image;
+use crate::bitmap_container::{get_asset_manifest, load_image, BitmapContainer, ColorSampler};
+use crate::brick_layer::BrickLayer;
 use crate::bricks::{Brick, BrickStatus};
+use crate::callbacks::{Callbacks, HostEvent};
+use crate::camera::Camera;
+use crate::canvas_renderer::CanvasRenderer;
+use crate::config::GameConfig;
 use crate::consts::*;
-use crate::game_status::{GameStatus, Status};
+use crate::controls_screen::ControlsScreen;
+use crate::debug_overlay::DebugOverlay;
+use crate::editor::{Editor, EditorAction};
+use crate::events::{Events, GameEvent};
+use crate::game_status::{Difficulty, GameStatus, Status};
+use crate::gamepad::{GamepadFrame, GamepadInput};
+use crate::high_score::HighScores;
+use crate::input::Action;
+use crate::level::Level;
+use crate::level_file::FileDialog;
+use crate::listeners::EventListeners;
 use crate::paddle::Paddle;
-use std::collections::HashMap;
+use crate::particles::ParticleSystem;
+use crate::pointer::{exit_pointer_lock, is_pointer_locked, TouchControls};
+use crate::renderer::{Renderer, TextAlign};
+use crate::scoring::Scoring;
+use crate::settings::Settings;
+use crate::snapshot::GameState;
+use crate::tween::Tween;
+use crate::viewport::Viewport;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::spawn_local;
-use web_sys::{console, ImageBitmap};
+use web_sys::ImageBitmap;
 
 #[cfg(feature = "wee_alloc")]
 #[global_allocator]
@@ -51,26 +105,55 @@ fn is_nealy_edge(top_dist: f64, bottom_dist: f64, left_dist: f64, right_dist: f6
 
 pub type Bricks = Vec<Vec<Brick>>;
 
+/// 裏の絵へ切り替え中のセル
+#[derive(Debug, Clone, Copy)]
+struct CellReveal {
+    col: usize,
+    row: usize,
+    tween: Tween,
+}
+
 #[wasm_bindgen]
 #[derive(Debug, Clone)]
 pub struct Container {
     bricks: Vec<Vec<Brick>>,
     asset_url: String,
+    // 画像のハッシュ入りのファイル名 読み込むまでは空で、元のファイル名を使う
+    manifest: Rc<AssetManifest>,
+    // 見た目が変わったセル ブロック層の部分再描画に使う
+    dirty: Vec<(usize, usize)>,
+    level: Level,
+    reveals: Vec<CellReveal>,
 }
 
 impl Container {
     pub fn new(asset_url: String) -> Container {
         let mut bricks: Bricks = Vec::new();
         bricks.resize(BRICK_COLUMN_COUNT, Vec::new());
-        for c in 0..bricks.len() {
-            bricks[c].resize(BRICK_ROW_COUNT, Brick::new(0.0, 0.0, BrickStatus::Live));
+        for (c, column) in bricks.iter_mut().enumerate() {
+            column.resize(BRICK_ROW_COUNT, Brick::new(0.0, 0.0, BrickStatus::Live));
+            for (r, brick) in column.iter_mut().enumerate() {
+                brick.set_x_and_y_from_col_row(c as f64, r as f64);
+            }
         }
         Container {
             bricks: bricks,
             asset_url: asset_url,
+            manifest: Rc::new(AssetManifest::new()),
+            dirty: Vec::new(),
+            level: Level::default(),
+            reveals: Vec::new(),
         }
     }
 
+    pub fn get_manifest(&self) -> Rc<AssetManifest> {
+        self.manifest.clone()
+    }
+
+    pub fn set_manifest(&mut self, manifest: Rc<AssetManifest>) {
+        self.manifest = manifest;
+    }
+
     pub fn get_col_len(&self) -> usize {
         self.bricks.len()
     }
@@ -97,32 +180,124 @@ impl Container {
 
     pub fn set_status(&mut self, col: usize, row: usize, status: BrickStatus) {
         self.bricks[col][row].set_status(status);
+        self.dirty.push((col, row));
     }
 
-    pub fn draw(
-        &mut self,
-        image_container: &HashMap<String, ImageBitmap>,
-        ctx: &web_sys::CanvasRenderingContext2d,
-    ) {
+    /// 前回呼んでから見た目が変わったセル
+    pub fn take_dirty_cells(&mut self) -> Vec<(usize, usize)> {
+        std::mem::take(&mut self.dirty)
+    }
+
+    pub fn get_level(&self) -> &Level {
+        &self.level
+    }
+
+    /// レベルのレイアウトに合わせてブロックを置き直す 置かないマスは最初から壊れている
+    pub fn set_level(&mut self, level: Level) {
+        for (c, column) in self.bricks.iter_mut().enumerate() {
+            for (r, brick) in column.iter_mut().enumerate() {
+                match level.get_cell(c, r) {
+                    Some(kind) => {
+                        brick.set_kind(kind);
+                        brick.set_status(BrickStatus::Live);
+                    }
+                    None => brick.set_status(BrickStatus::Dead),
+                }
+            }
+        }
+        self.level = level;
+    }
+
+    /// 裏の絵が届いたセルの切り替えを始める
+    pub fn start_reveals(&mut self, cells: Vec<(usize, usize)>) {
+        let easing = self.level.get_reveal().get_easing();
+        for (col, row) in cells {
+            self.reveals.retain(|r| r.col != col || r.row != row);
+            self.reveals.push(CellReveal {
+                col,
+                row,
+                tween: Tween::new(0.0, 1.0, REVEAL_FRAMES, easing),
+            });
+        }
+    }
+
+    /// 切り替えを1フレーム進める 描き直しが必要なのでdirtyにする
+    /// 終わったセルを返すので、前の画像はそこで捨てる
+    pub fn update_reveals(&mut self) -> Vec<(usize, usize)> {
+        let mut finished = Vec::new();
+        for reveal in self.reveals.iter_mut() {
+            reveal.tween.update();
+            self.dirty.push((reveal.col, reveal.row));
+            if reveal.tween.is_finished() {
+                finished.push((reveal.col, reveal.row));
+            }
+        }
+        self.reveals.retain(|r| !r.tween.is_finished());
+        finished
+    }
+
+    /// 生きているブロックの割合 0.0..1.0
+    pub fn get_live_ratio(&self) -> f64 {
+        let total = self.bricks.iter().map(|c| c.len()).sum::<usize>();
+        if total == 0 {
+            return 0.0;
+        }
+        let live = self
+            .bricks
+            .iter()
+            .flatten()
+            .filter(|b| b.get_status() == BrickStatus::Live && b.get_kind().is_breakable())
+            .count();
+        live as f64 / total as f64
+    }
+
+    /// 壊せるブロックが1つも残っていなければクリア
+    pub fn is_cleared(&self) -> bool {
+        self.bricks
+            .iter()
+            .flatten()
+            .all(|b| b.get_status() == BrickStatus::Dead || !b.get_kind().is_breakable())
+    }
+
+    pub fn draw<R: Renderer>(&self, image_container: &BitmapContainer<R::Image>, renderer: &mut R) {
         for c in 0..self.bricks.len() {
             for r in 0..self.bricks[c].len() {
-                // if self.bricks[c][r].get_status() == BrickStatus::Live {
-                self.bricks[c][r].set_x_and_y_from_col_row(c as f64, r as f64);
-                let bitmap = image_container.get(&format!("{}-{}", c, r));
-                match bitmap {
-                    Some(b) => self.bricks[c][r].draw(&b, &ctx),
-                    None => continue,
-                };
-                // }
+                self.draw_cell(c, r, image_container, renderer);
             }
         }
     }
 
+    /// 1セル分だけ描く 画像がまだ読み込まれていなければ何もしない
+    pub fn draw_cell<R: Renderer>(
+        &self,
+        col: usize,
+        row: usize,
+        image_container: &BitmapContainer<R::Image>,
+        renderer: &mut R,
+    ) {
+        let b = match image_container.get(col, row) {
+            Some(b) => b,
+            None => return,
+        };
+        let reveal = self.reveals.iter().find(|r| r.col == col && r.row == row);
+        match (reveal, image_container.get_previous(col, row)) {
+            (Some(reveal), Some(previous)) => self.bricks[col][row].draw_reveal(
+                previous,
+                b,
+                self.level.get_reveal(),
+                reveal.tween.get_value(),
+                renderer,
+            ),
+            _ => self.bricks[col][row].draw(b, renderer),
+        }
+    }
+
     pub fn collision_detection(
         &mut self,
         status: &mut GameStatus,
         ball: &mut Ball,
-        bitmaps_container: &Rc<RefCell<HashMap<String, ImageBitmap>>>,
+        bitmaps_container: &Rc<RefCell<BitmapContainer<ImageBitmap>>>,
+        events: &mut Events,
     ) {
         for c in 0..self.bricks.len() {
             for r in 0..self.bricks[c].len() {
@@ -138,17 +313,6 @@ impl Container {
                         && y < brick_y + BRICK_HEIGHT
                     {
                         // ここに来る場合中心点がいずれかのブロックの中にめり込んでいる場合なので、あとはどの辺が一番近いかチェックする
-                        let url = self.asset_url.clone();
-                        let bitmap_container = bitmaps_container.clone();
-                        spawn_local(async move {
-                            let image = get_image(c as u32, r as u32, &url, "26_angel")
-                                .await
-                                .unwrap();
-                            bitmap_container
-                                .borrow_mut()
-                                .insert(format!("{}-{}", c, r), image);
-                        });
-
                         let left_dist = (x - brick_x).abs();
                         let right_dist = (x - (brick_x + BRICK_WIDTH)).abs();
                         let bottom_dist = (y - brick_y).abs();
@@ -159,16 +323,42 @@ impl Container {
                             BrickEdge::Left => ball.set_dx(-ball.get_dx()),
                             BrickEdge::Right => ball.set_dx(-ball.get_dx()),
                         }
+                        // 壊れないブロックは跳ね返すだけで、速さもコンボも上げない
+                        if !b.get_kind().is_breakable() {
+                            continue;
+                        }
                         ball.add_speed();
+                        events.push(GameEvent::BrickHit { col: c, row: r });
                         let is_break = self.bricks[c][r].update_status();
                         if is_break == BrickStatus::Dead {
-                            status.set_score(status.get_score() + 1);
-                        }
-                        if status.get_score() == BRICK_SUM {
-                            let _ = web_sys::window()
-                                .unwrap()
-                                .alert_with_message("YOU WIN, CONGRATULATIONS!");
-                            let _ = web_sys::window().unwrap().location().reload();
+                            self.dirty.push((c, r));
+                            // 壊れたときだけ裏の絵を取りに行く
+                            let url = self.asset_url.clone();
+                            let hidden = self.level.get_hidden().to_string();
+                            let manifest = self.manifest.clone();
+                            let bitmap_container = bitmaps_container.clone();
+                            let generation = bitmap_container.borrow().get_generation();
+                            spawn_local(async move {
+                                // 読めなければ表の絵のまま残す
+                                let image =
+                                    load_image(c as u32, r as u32, &url, &hidden, &manifest).await;
+                                if let Some(image) = image {
+                                    bitmap_container
+                                        .borrow_mut()
+                                        .replace_for(generation, c, r, image);
+                                }
+                            });
+                            events.push(GameEvent::BrickBroken {
+                                col: c,
+                                row: r,
+                                x: brick_x + BRICK_WIDTH / 2.0,
+                                y: brick_y + BRICK_HEIGHT / 2.0,
+                                kind: b.get_kind(),
+                            });
+                            if self.is_cleared() {
+                                status.set_status(Status::StageClear);
+                                events.push(GameEvent::StageCleared);
+                            }
                         }
                     }
                 }
@@ -177,21 +367,262 @@ impl Container {
     }
 }
 
+/// 1フレーム分のブロック・ボール・パドル・HUDを描く
+/// タイトルなどのオーバーレイは含まない
+#[allow(clippy::too_many_arguments)]
+pub fn draw_frame<R: Renderer>(
+    renderer: &mut R,
+    bricks: &Container,
+    images: &BitmapContainer<R::Image>,
+    ball: &Ball,
+    paddle: &Paddle,
+    status: &GameStatus,
+    scoring: &Scoring,
+    width: f64,
+    height: f64,
+) {
+    // 前のフレームの描画を消すために一旦clearする
+    renderer.clear_rect(0.0, 0.0, width, height);
+    // ボールが下側に表示されないようにブロックから表示する
+    bricks.draw(images, renderer);
+    draw_objects(renderer, ball, paddle, status, scoring, width, height);
+}
+
+/// ブロックの上に重ねるボール・パドル・HUD
+pub fn draw_objects<R: Renderer>(
+    renderer: &mut R,
+    ball: &Ball,
+    paddle: &Paddle,
+    status: &GameStatus,
+    scoring: &Scoring,
+    width: f64,
+    height: f64,
+) {
+    ball.draw(renderer);
+    paddle.draw(renderer, height);
+
+    status.draw_score(renderer);
+    status.draw_lives(renderer, width);
+    ball.draw_speed_gauge(renderer, width, height);
+    scoring.draw_multiplier(renderer, width);
+    scoring.draw_popups(renderer);
+}
+
 // startをつけると読み込み時に自動で実行される
 #[wasm_bindgen(start)]
 pub fn initialize() {
     utils::set_panic_hook();
 }
 
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &'static str = r#"
+export interface GameConfig {
+  assetUrl: string;
+  manifest: string;
+  audio: boolean;
+  gamepad: boolean;
+  difficulty: "Easy" | "Normal" | "Hard";
+}
+
+export type GameEvent =
+  | { type: "brickBroken"; col: number; row: number; score: number }
+  | { type: "lifeLost"; lives: number }
+  | { type: "stageCleared"; score: number }
+  | { type: "gameOver"; score: number };
+
+export interface GameState {
+  status: "Title" | "Prepare" | "Stop" | "Start" | "Pause" | "GameOver" | "NameEntry" | "StageClear";
+  score: number;
+  lives: number;
+  stage: number;
+  level: string;
+  ball: { x: number; y: number; dx: number; dy: number; speed: number };
+  paddleX: number;
+  bricks: { status: "Live" | "Dead"; kind: "Normal" | "Hard" | "Unbreakable" }[][];
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "HTMLCanvasElement | string")]
+    pub type CanvasTarget;
+
+    #[wasm_bindgen(typescript_type = "Partial<GameConfig>")]
+    pub type PartialGameConfig;
+
+    #[wasm_bindgen(typescript_type = "GameState")]
+    pub type GameStateObject;
+
+    #[wasm_bindgen(typescript_type = "(event: GameEvent) => void")]
+    pub type GameEventCallback;
+}
+
+type FrameLoop = Rc<RefCell<Option<Closure<dyn FnMut()>>>>;
+
+/// start()が返すゲームの操作用ハンドル
+/// ページから取り除くときはdestroy()を呼ぶ
+#[wasm_bindgen]
+pub struct GameHandle {
+    listeners: EventListeners,
+    frame: FrameLoop,
+    // 予約中のrequestAnimationFrameのID
+    animation_id: Rc<Cell<i32>>,
+    canvas: web_sys::HtmlCanvasElement,
+    audio: Rc<Audio>,
+    callbacks: Rc<RefCell<Callbacks>>,
+    status: Rc<RefCell<GameStatus>>,
+    ball: Rc<RefCell<Ball>>,
+    paddle: Rc<RefCell<Paddle>>,
+    bricks: Rc<RefCell<Container>>,
+    config: Rc<RefCell<GameConfig>>,
+    // loadLevelで読み込んだレベル restartしてもこれを使う
+    custom_level: Rc<RefCell<Option<Level>>>,
+    restart: Rc<Cell<bool>>,
+    // step()で残っているフレーム数
+    step_frames: Rc<Cell<u32>>,
+    editor: Rc<RefCell<Editor>>,
+    // 読み込み中のマニフェストなど、後から届く処理が止まったゲームに触らないように見る
+    destroyed: Rc<Cell<bool>>,
+}
+
+#[wasm_bindgen]
+impl GameHandle {
+    /// ゲーム中の出来事を受け取るコールバックを登録する
+    /// eventは"brickBroken" "lifeLost" "stageCleared" "gameOver"のどれか
+    pub fn on(&self, event: &str, callback: GameEventCallback) -> Result<(), JsValue> {
+        self.callbacks
+            .borrow_mut()
+            .add(event, callback.unchecked_into())
+    }
+
+    pub fn off(&self, event: &str, callback: &GameEventCallback) {
+        self.callbacks
+            .borrow_mut()
+            .remove(event, callback.unchecked_ref());
+    }
+
+    pub fn pause(&self) {
+        let mut status = self.status.borrow_mut();
+        if status.get_status() == Status::Start {
+            pause(&mut status);
+        }
+    }
+
+    pub fn resume(&self) {
+        let mut status = self.status.borrow_mut();
+        if status.get_status() == Status::Pause {
+            resume(&mut status);
+        }
+    }
+
+    /// 次のフレームでタイトルからやり直す
+    pub fn restart(&self) {
+        self.restart.set(true);
+    }
+
+    /// レベルのJSONを読み込んでやり直す 遊べないレベルならエラーを返して今のレベルのまま
+    #[wasm_bindgen(js_name = loadLevel)]
+    pub fn load_level(&self, json: &str) -> Result<(), JsValue> {
+        let level = level_file::parse(json).map_err(|e| JsValue::from_str(&e))?;
+        *self.custom_level.borrow_mut() = Some(level);
+        self.restart();
+        Ok(())
+    }
+
+    /// loadLevelやテストプレイで読み込んだレベルをやめて、組み込みのステージでやり直す
+    #[wasm_bindgen(js_name = clearLevel)]
+    pub fn clear_level(&self) {
+        self.custom_level.borrow_mut().take();
+        self.restart();
+    }
+
+    /// 渡した項目だけ変える assetUrlとdifficultyは次のrestartから使う
+    #[wasm_bindgen(js_name = setConfig)]
+    pub fn set_config(&self, config: PartialGameConfig) -> Result<(), JsValue> {
+        let config = self.config.borrow().merge_js(&config)?;
+        self.audio.set_enabled(config.audio);
+        *self.config.borrow_mut() = config;
+        Ok(())
+    }
+
+    #[wasm_bindgen(js_name = getState)]
+    pub fn get_state(&self) -> Result<GameStateObject, JsValue> {
+        let state = GameState::new(
+            &self.status.borrow(),
+            &self.ball.borrow(),
+            &self.paddle.borrow(),
+            &self.bricks.borrow(),
+        );
+        let json = serde_json::to_string(&state).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(js_sys::JSON::parse(&json)?.unchecked_into())
+    }
+
+    /// 一時停止してから、この後のアニメーションフレームでframesフレームだけ進める
+    pub fn step(&self, frames: u32) {
+        self.pause();
+        if self.status.borrow().get_status() == Status::Pause {
+            self.step_frames.set(self.step_frames.get() + frames);
+        }
+    }
+
+    /// 今のレベルでレベルエディタを開く 遊んでいる途中なら一時停止する
+    #[wasm_bindgen(js_name = openEditor)]
+    pub fn open_editor(&self) {
+        self.pause();
+        let level = self.bricks.borrow().get_level().clone();
+        self.editor.borrow_mut().open(level);
+    }
+
+    /// エディタで編集中のレベルのJSON 開いていなければ遊んでいるレベル
+    #[wasm_bindgen(js_name = exportLevel)]
+    pub fn export_level(&self) -> Result<String, JsValue> {
+        let editor = self.editor.borrow();
+        let bricks = self.bricks.borrow();
+        let level = if editor.is_open() {
+            editor.get_level()
+        } else {
+            bricks.get_level()
+        };
+        serde_json::to_string(level).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// イベントリスナーを外して描画ループを止める 何度呼んでもよい
+    pub fn destroy(&mut self) {
+        if self.destroyed.replace(true) {
+            return;
+        }
+        self.listeners.remove_all();
+        if let Some(window) = web_sys::window() {
+            let _ = window.cancel_animation_frame(self.animation_id.get());
+        }
+        // ループのクロージャは自分自身を持っているので、ここで外さないと解放されない
+        self.frame.borrow_mut().take();
+        exit_pointer_lock(&self.canvas);
+        self.audio.close();
+        self.callbacks.borrow_mut().clear();
+    }
+}
+
+impl Drop for GameHandle {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
+
+/// targetはキャンバスの要素かセレクタ 状態はすべて返すハンドルごとに持つので、同じページにいくつでも置ける
 #[wasm_bindgen]
-pub fn start(asset_url: String) {
+pub fn start(
+    target: CanvasTarget,
+    config: Option<PartialGameConfig>,
+) -> Result<GameHandle, JsValue> {
     // 各種エレメントがない/APIが実行できない場合は進めないのでその場で終了
-    let document: web_sys::Document = web_sys::window().unwrap().document().unwrap();
-    let canvas = document.get_element_by_id("myCanvas").unwrap();
-    let canvas: web_sys::HtmlCanvasElement = canvas
-        .dyn_into::<web_sys::HtmlCanvasElement>()
-        .map_err(|_| console::log_1(&JsValue::from_str("CanvasElement is invalid")))
-        .unwrap();
+    let window = web_sys::window().unwrap();
+    let document: web_sys::Document = window.document().unwrap();
+    let canvas = find_canvas(&document, target.into())?;
+    let config = match config {
+        Some(config) => GameConfig::from_js(&config)?,
+        None => GameConfig::default(),
+    };
 
     let context = canvas
         .get_context("2d")
@@ -200,164 +631,1188 @@ pub fn start(asset_url: String) {
         .dyn_into::<web_sys::CanvasRenderingContext2d>()
         .unwrap();
 
-    let width = canvas.width();
-    let height = canvas.height();
-    let offset_left = canvas.offset_left();
-    let bitmap_container: HashMap<String, ImageBitmap> = HashMap::new();
+    // 描画や当たり判定は論理サイズで行い、キャンバスの実際の大きさはViewportが決める
+    let width = FIELD_WIDTH;
+    let height = FIELD_HEIGHT;
+    let viewport = Rc::new(RefCell::new(Viewport::new(canvas.clone(), width, height)));
+    let bitmap_container: BitmapContainer<ImageBitmap> = BitmapContainer::new();
     let bitmap_container = Rc::new(RefCell::new(bitmap_container));
+    let mut status = GameStatus::new();
+    status.set_difficulty(config.difficulty);
+    let level = Level::for_stage(status.get_stage());
 
-    for i in 0..BRICK_COLUMN_COUNT {
-        for j in 0..BRICK_ROW_COUNT {
-            let asset_url = asset_url.clone();
-            let local_container = bitmap_container.clone();
-            spawn_local(async move {
-                // moveされたやつの参照を取るのでライフタイムは問題ない
-                // asyncの中でborrow_mutを取るとマルチスレッドについて考慮が必要(lockとか)
-                // let mut mut_bitmap = local_container.borrow_mut();
-                let image = get_image(i as u32, j as u32, &asset_url, "27_devil")
-                    .await
-                    .unwrap();
-                local_container
-                    .borrow_mut()
-                    .insert(format!("{}-{}", i, j), image);
-            });
-        }
+    let mut bricks = Container::new(config.asset_url.clone());
+    // マニフェストがあれば読み込んでから画像を取りに行く
+    if config.manifest.is_empty() {
+        load_images(
+            &bitmap_container,
+            &config.asset_url,
+            &level,
+            &bricks.get_manifest(),
+        );
     }
-
-    let mut bricks = Container::new(asset_url);
-    let ball = Ball::new(
-        2.0 * SPEED,
-        -2.0 * SPEED,
-        canvas.width() as f64 / 2.0,
-        canvas.height() as f64 - BALL_RADIUS * 2.0,
-    );
-    let status = GameStatus::new();
-    let paddle = Paddle::new((canvas.width() as f64 - PADDLE_WIDTH) / 2.0);
+    bricks.set_level(level);
+    let ball = new_ball(width, height);
+    let paddle = Paddle::new((width as f64 - PADDLE_WIDTH) / 2.0);
+    let high_scores = HighScores::load();
 
     let f = Rc::new(RefCell::new(None));
+    // destroyでループを止められるようにハンドルにも持たせる
+    let frame_loop = f.clone();
+    // 描画はrequestAnimationFrameのクロージャだけが行うのでRendererはそこにmoveする
+    let mut renderer = CanvasRenderer::new(context);
+    let mut brick_layer = BrickLayer::new(&document, width, height).unwrap();
+    let debug_overlay = Rc::new(RefCell::new(DebugOverlay::new()));
+    let color_sampler = ColorSampler::new(&document).unwrap();
     // イベントハンドラ内で変更して、描画処理で使うものについては参照を共有したいのでRcで作る
-    let context = Rc::new(context);
     // 変更したい変数は更に追加でCellで作る
     let paddle = Rc::new(RefCell::new(paddle));
+    let bricks = Rc::new(RefCell::new(bricks));
     let status = Rc::new(RefCell::new(status));
     let ball = Rc::new(RefCell::new(ball));
+    let high_scores = Rc::new(RefCell::new(high_scores));
+    let settings = Rc::new(RefCell::new(Settings::load()));
+    let controls_screen = Rc::new(RefCell::new(ControlsScreen::new()));
+    let editor = Rc::new(RefCell::new(Editor::new()));
+    let audio = Rc::new(if config.audio {
+        Audio::new(&settings.borrow())
+    } else {
+        Audio::silent(&settings.borrow())
+    });
+    let config = Rc::new(RefCell::new(config));
+    let custom_level: Rc<RefCell<Option<Level>>> = Rc::new(RefCell::new(None));
+    let step_frames = Rc::new(Cell::new(0));
+    // ゲームオーバーの後はページを読み直さずにこのゲームだけ最初に戻す
+    let restart = Rc::new(Cell::new(false));
+    let callbacks = Rc::new(RefCell::new(Callbacks::new()));
+    let destroyed = Rc::new(Cell::new(false));
+    // マニフェストが届くまではrestartしても画像を読まず、届いたときのレベルの画像だけを読む
+    let manifest_pending = Rc::new(Cell::new(!config.borrow().manifest.is_empty()));
+    if manifest_pending.get() {
+        let bricks = bricks.clone();
+        let bitmap_container = bitmap_container.clone();
+        let config = config.clone();
+        let destroyed = destroyed.clone();
+        let manifest_pending = manifest_pending.clone();
+        let (asset_url, path) = {
+            let config = config.borrow();
+            (config.asset_url.clone(), config.manifest.clone())
+        };
+        spawn_local(async move {
+            let manifest = get_asset_manifest(&asset_url, &path).await;
+            if destroyed.get() {
+                return;
+            }
+            // 読めなければ元のファイル名で続ける
+            let manifest = Rc::new(manifest.unwrap_or_else(|e| {
+                web_sys::console::error_1(&e);
+                AssetManifest::new()
+            }));
+            manifest_pending.set(false);
+            let level = bricks.borrow().get_level().clone();
+            bricks.borrow_mut().set_manifest(manifest.clone());
+            let asset_url = config.borrow().asset_url.clone();
+            load_images(&bitmap_container, &asset_url, &level, &manifest);
+        });
+    }
+    // 入力はキャンバスにだけ登録する キー入力を受け取れるようにフォーカスできるようにしておく
+    let event_target: web_sys::EventTarget = canvas.clone().into();
+    let _ = canvas.set_attribute("tabindex", "0");
+    let _ = canvas.style().set_property("outline", "none");
+    let _ = canvas.focus();
+    let mut listeners = EventListeners::new();
+    let animation_id = Rc::new(Cell::new(0));
 
     {
         let g = f.clone();
-        let context = context.clone();
+        let frame_id = animation_id.clone();
+        let canvas = canvas.clone();
+        let viewport = viewport.clone();
         let paddle = paddle.clone();
         let status = status.clone();
         let ball = ball.clone();
         let bitmap_container = bitmap_container.clone();
+        let high_scores = high_scores.clone();
+        let debug_overlay = debug_overlay.clone();
+        let settings = settings.clone();
+        let audio = audio.clone();
+        let controls_screen = controls_screen.clone();
+        let editor = editor.clone();
+        let mut scoring = Scoring::new();
+        let mut camera = Camera::new();
+        let mut gamepad = GamepadInput::new();
+        let bricks = bricks.clone();
+        let config = config.clone();
+        let custom_level = custom_level.clone();
+        let step_frames = step_frames.clone();
+        let restart = restart.clone();
+        let callbacks = callbacks.clone();
+        let manifest_pending = manifest_pending.clone();
+        let mut host_events: Vec<HostEvent> = Vec::new();
+        let mut particles = ParticleSystem::new();
+        let mut events: Events = Vec::new();
+        // ステージクリアかゲームオーバーの表示と、それを出しておく残りフレーム数
+        let mut result_message = "";
+        let mut result_frames = 0;
 
         // クロージャのキャプチャで一回Moveされる
         *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+            if restart.replace(false) {
+                *status.borrow_mut() = GameStatus::new();
+                status
+                    .borrow_mut()
+                    .set_difficulty(config.borrow().difficulty);
+                *ball.borrow_mut() = new_ball(width, height);
+                *paddle.borrow_mut() = Paddle::new((width as f64 - PADDLE_WIDTH) / 2.0);
+                *high_scores.borrow_mut() = HighScores::load();
+                let level = custom_level
+                    .borrow()
+                    .clone()
+                    .unwrap_or_else(|| Level::for_stage(status.borrow().get_stage()));
+                let asset_url = config.borrow().asset_url.clone();
+                set_up_level(
+                    &bricks,
+                    &bitmap_container,
+                    level,
+                    asset_url,
+                    !manifest_pending.get(),
+                );
+                brick_layer.invalidate();
+                scoring = Scoring::new();
+                camera = Camera::new();
+                particles = ParticleSystem::new();
+                events.clear();
+                result_frames = 0;
+            }
+            let mut container = bricks.borrow_mut();
+            {
+                let mut viewport = viewport.borrow_mut();
+                if viewport.take_resized() {
+                    brick_layer.set_pixel_ratio(viewport.get_pixel_ratio());
+                }
+                let _ = viewport.apply(renderer.get_context());
+            }
+            // エディタを開いている間はゲームを止めてエディタだけを描く
+            if editor.borrow().is_open() {
+                exit_pointer_lock(&canvas);
+                editor
+                    .borrow()
+                    .draw(&mut renderer, width as f64, height as f64);
+                if let Some(frame) = f.borrow().as_ref() {
+                    frame_id.set(request_animation_frame(frame));
+                }
+                return;
+            }
             // 前のフレームの描画を消すために一旦clearする
-            context.clear_rect(0.0, 0.0, width as f64, height as f64);
+            renderer.clear_rect(0.0, 0.0, width as f64, height as f64);
+            camera.set_enabled(!settings.borrow().get_reduce_motion());
+            camera.update();
+            camera.begin(&mut renderer, width as f64, height as f64);
             // ボールが下側に表示されないようにブロックから表示する
-            bricks.draw(&mut bitmap_container.borrow_mut(), &context);
-            ball.borrow_mut().draw(&context);
-            paddle.borrow_mut().draw(&context, height as f64);
-
-            status.borrow().draw_score(&context);
-            status.borrow().draw_lives(&context, width as f64);
+            {
+                let mut overlay = debug_overlay.borrow_mut();
+                let mut images = bitmap_container.borrow_mut();
+                container.start_reveals(images.take_replaced());
+                for (col, row) in container.update_reveals() {
+                    images.remove_previous(col, row);
+                }
+                let started = utils::now();
+                if overlay.get_use_cache() {
+                    let cells = brick_layer.update(&mut container, &mut images);
+                    brick_layer.blit(renderer.get_context());
+                    overlay.record_cached(utils::now() - started, cells);
+                    if overlay.should_sample_full() {
+                        let started = utils::now();
+                        brick_layer.invalidate();
+                        brick_layer.update(&mut container, &mut images);
+                        overlay.record_full(utils::now() - started);
+                    }
+                } else {
+                    container.draw(&images, &mut renderer);
+                    overlay.record_full(utils::now() - started);
+                    // キャッシュに戻したときは全部描き直す
+                    brick_layer.invalidate();
+                }
+            }
+            draw_objects(
+                &mut renderer,
+                &ball.borrow(),
+                &paddle.borrow(),
+                &status.borrow(),
+                &scoring,
+                width as f64,
+                height as f64,
+            );
+            particles.draw(&mut renderer);
+            camera.end(&mut renderer);
+            gamepad.draw_indicator(&mut renderer, height as f64);
+            debug_overlay.borrow().draw(&mut renderer, height as f64);
+            scoring.update_popups();
+            let pad = if config.borrow().gamepad {
+                gamepad.poll()
+            } else {
+                GamepadFrame::default()
+            };
+            // キーボードやクリックと同じく、操作設定やエディタを開いている間はゲームに渡さない
+            if controls_screen.borrow().is_open() || editor.borrow().is_open() {
+                paddle.borrow_mut().set_axis(0.0);
+            } else {
+                paddle.borrow_mut().set_axis(pad.axis);
+                for action in pad.actions {
+                    handle_action(
+                        action,
+                        &mut paddle.borrow_mut(),
+                        &mut ball.borrow_mut(),
+                        &mut status.borrow_mut(),
+                    );
+                }
+            }
+            // ヒットストップ中はボールもパドルもパーティクルも止める
+            let hit_stop = camera.take_hit_stop_frame();
+            // step()で進めるときは一時停止中でもこのフレームだけシミュレーションを動かす
+            let stepping = status.borrow().get_status() == Status::Pause && step_frames.get() > 0;
+            if stepping {
+                step_frames.set(step_frames.get() - 1);
+                status.borrow_mut().set_status(Status::Start);
+            } else if status.borrow().get_status() != Status::Pause {
+                step_frames.set(0);
+            }
             // forの中でborrowとかするとスコープ内のborrow_mutとかち合うので外側で取っておく
-            let ball_speed = ball.borrow().get_speed();
+            let ball_speed = if hit_stop {
+                0
+            } else {
+                ball.borrow().get_speed()
+            };
             for _ in 0..ball_speed {
-                bricks.collision_detection(
+                container.collision_detection(
                     &mut status.borrow_mut(),
                     &mut ball.borrow_mut(),
                     &bitmap_container,
+                    &mut events,
                 );
                 update(
                     &mut ball.borrow_mut(),
                     &mut paddle.borrow_mut(),
                     &mut status.borrow_mut(),
-                    canvas.width() as f64,
-                    canvas.height() as f64,
+                    &mut events,
+                    width as f64,
+                    height as f64,
+                );
+            }
+            if stepping && status.borrow().get_status() == Status::Start {
+                status.borrow_mut().set_status(Status::Pause);
+            }
+
+            let now = js_sys::Date::now();
+            for event in events.drain(..) {
+                scoring.handle(&event, &mut status.borrow_mut(), now);
+                host_events.extend(HostEvent::from_game_event(&event, &status.borrow()));
+                camera.handle(&event);
+                audio.handle(&event);
+                match event {
+                    // ホストにはイベントで伝わるので、ページを止めるalertではなくキャンバスに出す
+                    GameEvent::StageCleared => {
+                        result_message = "STAGE CLEAR!";
+                        result_frames = RESULT_MESSAGE_FRAMES;
+                    }
+                    GameEvent::GameOver => {
+                        result_message = "GAME OVER";
+                        result_frames = RESULT_MESSAGE_FRAMES;
+                    }
+                    GameEvent::BrickBroken { col, row, x, y, .. } => {
+                        // 画像の差し替えは非同期なので、この時点ではまだ壊れる前の画像が入っている
+                        let colors = bitmap_container
+                            .borrow()
+                            .get(col, row)
+                            .map(|image| color_sampler.sample(image))
+                            .unwrap_or_default();
+                        particles.spawn_debris(x, y, &colors);
+                    }
+                    GameEvent::PaddleHit { x, y } => particles.spawn_sparks(x, y),
+                    _ => {}
+                }
+            }
+            // コールバックからゲームを操作されてもいいように、何も借用していないところで呼ぶ
+            drop(container);
+            for event in host_events.drain(..) {
+                Callbacks::emit(&callbacks, event);
+            }
+            if !hit_stop {
+                let mut ball = ball.borrow_mut();
+                if status.borrow().get_status() == Status::Start {
+                    ball.record_trail();
+                    particles.spawn_trail(ball.get_x(), ball.get_y(), ball.get_speed());
+                }
+                particles.update();
+            }
+
+            // BGMは速くなるほど、残りのブロックが減るほど盛り上げる
+            let low_bricks = 1.0 - bricks.borrow().get_live_ratio() / MUSIC_LOW_BRICKS_RATIO;
+            let intensity = ball
+                .borrow()
+                .get_speed_ratio()
+                .max(low_bricks)
+                .clamp(0.0, 1.0);
+            let track = match status.borrow().get_status() {
+                Status::Title => "title",
+                Status::Prepare | Status::Start | Status::Pause | Status::Stop => "game",
+                Status::GameOver | Status::NameEntry => "gameover",
+                Status::StageClear => "game",
+            };
+            audio.update_music(track, intensity);
+
+            // 点数と残機はそのままで次のステージのブロックを置く
+            if status.borrow().get_status() == Status::StageClear {
+                status.borrow_mut().stop_timer(js_sys::Date::now());
+                if result_frames > 0 {
+                    result_frames -= 1;
+                } else {
+                    let level =
+                        next_level(&mut status.borrow_mut(), custom_level.borrow().as_ref());
+                    *ball.borrow_mut() = new_ball(width, height);
+                    *paddle.borrow_mut() = Paddle::new((width as f64 - PADDLE_WIDTH) / 2.0);
+                    let asset_url = config.borrow().asset_url.clone();
+                    set_up_level(
+                        &bricks,
+                        &bitmap_container,
+                        level,
+                        asset_url,
+                        !manifest_pending.get(),
+                    );
+                    brick_layer.invalidate();
+                }
+            }
+            if status.borrow().get_status() == Status::GameOver {
+                status.borrow_mut().stop_timer(js_sys::Date::now());
+                if result_frames > 0 {
+                    result_frames -= 1;
+                } else if high_scores.borrow().wants_name_entry(&status.borrow()) {
+                    status.borrow_mut().set_status(Status::NameEntry);
+                } else {
+                    restart.set(true);
+                }
+            }
+            // 一時停止やゲームオーバーではカーソルを返す
+            if !is_controlling_paddle(status.borrow().get_status())
+                || !settings.borrow().get_pointer_lock()
+            {
+                exit_pointer_lock(&canvas);
+            }
+            match status.borrow().get_status() {
+                Status::Title => draw_title(
+                    &mut renderer,
+                    &high_scores.borrow(),
+                    &settings.borrow(),
+                    &status.borrow(),
+                    width as f64,
+                    height as f64,
+                ),
+                Status::NameEntry => high_scores.borrow().draw_name_entry(
+                    &mut renderer,
+                    &status.borrow(),
+                    width as f64,
+                    height as f64,
+                ),
+                Status::Pause => draw_pause(&mut renderer, width as f64, height as f64),
+                Status::GameOver | Status::StageClear if result_frames > 0 => draw_result(
+                    &mut renderer,
+                    result_message,
+                    &status.borrow(),
+                    width as f64,
+                    height as f64,
+                ),
+                _ => {}
+            }
+            if controls_screen.borrow().is_open() {
+                controls_screen.borrow().draw(
+                    &mut renderer,
+                    settings.borrow().get_key_bindings(),
+                    width as f64,
+                    height as f64,
                 );
             }
 
-            request_animation_frame(f.borrow().as_ref().unwrap());
+            if let Some(frame) = f.borrow().as_ref() {
+                frame_id.set(request_animation_frame(frame));
+            }
         }) as Box<dyn FnMut()>));
 
-        request_animation_frame(g.borrow().as_ref().unwrap());
+        animation_id.set(request_animation_frame(g.borrow().as_ref().unwrap()));
     }
 
     // キーボードのキー押した時のイベント
     {
         let paddle = paddle.clone();
+        let status = status.clone();
+        let high_scores = high_scores.clone();
 
-        let keydown_handler = Closure::wrap(Box::new(move |e: web_sys::KeyboardEvent| {
-            if e.key() == "Right" || e.key() == "ArrowRight" {
-                paddle.borrow_mut().set_right_pressed(true);
-            } else if e.key() == "Left" || e.key() == "ArrowLeft" {
-                paddle.borrow_mut().set_left_pressed(true);
-            }
-        }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+        let debug_overlay = debug_overlay.clone();
+        let settings = settings.clone();
+        let audio = audio.clone();
+        let controls_screen = controls_screen.clone();
+        let ball = ball.clone();
+        let restart = restart.clone();
+        let editor = editor.clone();
+        let bricks = bricks.clone();
+        let custom_level = custom_level.clone();
+        let document = document.clone();
+        let file_dialog = FileDialog::new();
+        let config = config.clone();
 
-        document.set_onkeydown(Some(keydown_handler.as_ref().unchecked_ref()));
-        keydown_handler.forget();
+        listeners.add(
+            &event_target,
+            "keydown",
+            move |e: web_sys::KeyboardEvent| {
+                audio.resume();
+                if controls_screen.borrow().is_open() {
+                    e.prevent_default();
+                    let mut settings = settings.borrow_mut();
+                    if controls_screen
+                        .borrow_mut()
+                        .handle_key(&e.code(), settings.get_key_bindings_mut())
+                    {
+                        settings.save();
+                    }
+                    return;
+                }
+                if editor.borrow().is_open() {
+                    e.prevent_default();
+                    let action = editor.borrow_mut().handle_key(
+                        &e.code(),
+                        e.ctrl_key() || e.meta_key(),
+                        e.shift_key(),
+                    );
+                    if let Some(action) = action {
+                        handle_editor_action(
+                            action,
+                            &editor,
+                            &custom_level,
+                            &restart,
+                            &document,
+                            &file_dialog,
+                        );
+                    }
+                    return;
+                }
+                if e.key() == "F1" {
+                    e.prevent_default();
+                    if status.borrow().get_status() == Status::Title {
+                        controls_screen.borrow_mut().open();
+                    }
+                    return;
+                } else if e.key() == "F3" {
+                    e.prevent_default();
+                    debug_overlay.borrow_mut().toggle_visible();
+                    return;
+                } else if e.key() == "F4" {
+                    e.prevent_default();
+                    debug_overlay.borrow_mut().toggle_cache();
+                    return;
+                } else if e.key() == "F2" {
+                    e.prevent_default();
+                    let mut settings = settings.borrow_mut();
+                    let reduce_motion = !settings.get_reduce_motion();
+                    settings.set_reduce_motion(reduce_motion);
+                    settings.save();
+                    return;
+                }
+                if status.borrow().get_status() == Status::NameEntry {
+                    if handle_name_entry(&e, &status.borrow(), &mut high_scores.borrow_mut()) {
+                        restart.set(true);
+                    }
+                    return;
+                }
+                let action = settings.borrow().get_key_bindings().action_for(&e.code());
+                match action {
+                    Some(action) => {
+                        // Spaceでのスクロールなどを止める
+                        e.prevent_default();
+                        handle_action(
+                            action,
+                            &mut paddle.borrow_mut(),
+                            &mut ball.borrow_mut(),
+                            &mut status.borrow_mut(),
+                        );
+                    }
+                    None => {
+                        let title = status.borrow().get_status() == Status::Title;
+                        if e.code() == "KeyE" && title {
+                            editor
+                                .borrow_mut()
+                                .open(bricks.borrow().get_level().clone());
+                        } else if let Some(difficulty) =
+                            difficulty_for_key(&e.code()).filter(|_| title)
+                        {
+                            // 次のrestartでも同じ難易度で始める
+                            config.borrow_mut().difficulty = difficulty;
+                            status.borrow_mut().set_difficulty(difficulty);
+                        } else if handle_sound_key(&e.key(), &mut settings.borrow_mut()) {
+                            audio.apply_settings(&settings.borrow());
+                        } else {
+                            handle_mouse_key(&e.key(), &mut settings.borrow_mut());
+                        }
+                    }
+                }
+            },
+        );
     }
 
     // キーボードのキー離したときのイベント
     {
         let paddle = paddle.clone();
+        let settings = settings.clone();
 
-        let keyup_handler = Closure::wrap(Box::new(move |e: web_sys::KeyboardEvent| {
-            if e.key() == "Right" || e.key() == "ArrowRight" {
-                paddle.borrow_mut().set_right_pressed(false);
-            } else if e.key() == "Left" || e.key() == "ArrowLeft" {
-                paddle.borrow_mut().set_left_pressed(false);
-            }
-        }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
-        document.set_onkeyup(Some(keyup_handler.as_ref().unchecked_ref()));
-        keyup_handler.forget();
+        listeners.add(
+            &event_target,
+            "keyup",
+            move |e: web_sys::KeyboardEvent| match settings
+                .borrow()
+                .get_key_bindings()
+                .action_for(&e.code())
+            {
+                Some(Action::MoveRight) => paddle.borrow_mut().set_right_pressed(false),
+                Some(Action::MoveLeft) => paddle.borrow_mut().set_left_pressed(false),
+                _ => {}
+            },
+        );
     }
 
-    // マウスイベント
+    // 画面の大きさや拡大率が変わったらキャンバスを合わせ直す 入力の座標変換もここで更新される
     {
-        let paddle = paddle.clone();
-        let status = status.clone();
-        let ball = ball.clone();
+        let viewport = viewport.clone();
+        listeners.add(&window, "resize", move |_: web_sys::Event| {
+            viewport.borrow_mut().fit();
+        });
+    }
 
-        let mousemove_handler = Closure::wrap(Box::new(move |e: web_sys::MouseEvent| {
-            let relative_x = e.client_x() - offset_left;
-            if relative_x > 0 && relative_x < (width as i32) {
-                paddle
-                    .borrow_mut()
-                    .set_x(relative_x as f64 - PADDLE_WIDTH / 2.0);
-                if status.borrow().get_status() != Status::Start {
-                    ball.borrow_mut().set_x(relative_x as f64);
+    // マウスとタッチのイベント
+    // マウスは今まで通りカーソルの位置にパドルを合わせ、タッチはTouchControlsでドラッグとタップを扱う
+    {
+        // ドラッグ中にページがスクロールしたり拡大したりしないようにする
+        let _ = canvas.style().set_property("touch-action", "none");
+        let touch = Rc::new(RefCell::new(TouchControls::new()));
+
+        {
+            let canvas = canvas.clone();
+            let viewport = viewport.clone();
+            let touch = touch.clone();
+            let paddle = paddle.clone();
+            let status = status.clone();
+            let ball = ball.clone();
+            let audio = audio.clone();
+            let controls_screen = controls_screen.clone();
+            let settings = settings.clone();
+            let editor = editor.clone();
+            listeners.add(
+                &event_target,
+                "pointerdown",
+                move |e: web_sys::PointerEvent| {
+                    audio.resume();
+                    if controls_screen.borrow().is_open() {
+                        return;
+                    }
+                    if editor.borrow().is_open() {
+                        e.prevent_default();
+                        // キャンバスの外までドラッグしても離すまで塗り続ける
+                        let _ = canvas.set_pointer_capture(e.pointer_id());
+                        let viewport = viewport.borrow();
+                        editor.borrow_mut().pointer_down(
+                            viewport.to_logical_x(e.client_x()),
+                            viewport.to_logical_y(e.client_y()),
+                            e.button() == 2,
+                        );
+                        return;
+                    }
+                    if e.pointer_type() == "mouse" {
+                        if e.button() == 0 {
+                            // 発射のクリックでロックする 一時停止から戻ったときはクリックでロックし直す
+                            if is_controlling_paddle(status.borrow().get_status())
+                                && settings.borrow().get_pointer_lock()
+                                && !is_pointer_locked(&canvas)
+                            {
+                                canvas.request_pointer_lock();
+                            }
+                            launch(&mut status.borrow_mut());
+                        }
+                        return;
+                    }
+                    // タッチはキャンバスの上で始まったものだけ
+                    let canvas_target: &web_sys::EventTarget = canvas.as_ref();
+                    if e.target().as_ref() != Some(canvas_target) {
+                        return;
+                    }
+                    e.prevent_default();
+                    // 指がキャンバスの外に出ても離すまでイベントを受け取る
+                    let _ = canvas.set_pointer_capture(e.pointer_id());
+                    let x = viewport.borrow().to_logical_x(e.client_x());
+                    let paddle_x = paddle.borrow().get_x();
+                    let action =
+                        touch
+                            .borrow_mut()
+                            .down(e.pointer_id(), x, e.time_stamp(), paddle_x);
+                    if let Some(action) = action {
+                        handle_action(
+                            action,
+                            &mut paddle.borrow_mut(),
+                            &mut ball.borrow_mut(),
+                            &mut status.borrow_mut(),
+                        );
+                    }
+                },
+            );
+        }
+
+        {
+            let canvas = canvas.clone();
+            let viewport = viewport.clone();
+            let settings = settings.clone();
+            let touch = touch.clone();
+            let paddle = paddle.clone();
+            let status = status.clone();
+            let ball = ball.clone();
+            let editor = editor.clone();
+            listeners.add(
+                &event_target,
+                "pointermove",
+                move |e: web_sys::PointerEvent| {
+                    let x = viewport.borrow().to_logical_x(e.client_x());
+                    if editor.borrow().is_open() {
+                        let y = viewport.borrow().to_logical_y(e.client_y());
+                        editor.borrow_mut().pointer_move(x, y);
+                        return;
+                    }
+                    let mut paddle = paddle.borrow_mut();
+                    if e.pointer_type() == "mouse" && is_pointer_locked(&canvas) {
+                        // ロック中はカーソルの位置がないので移動量だけで動かす
+                        let distance = viewport.borrow().to_logical_distance(e.movement_x() as f64)
+                            * settings.borrow().get_mouse_sensitivity();
+                        let paddle_x = paddle.get_x() + distance;
+                        paddle.set_x(paddle_x.clamp(0.0, width as f64 - PADDLE_WIDTH));
+                        if is_waiting_for_launch(status.borrow().get_status()) {
+                            ball.borrow_mut().set_x(paddle.get_x() + PADDLE_WIDTH / 2.0);
+                        }
+                        return;
+                    }
+                    if e.pointer_type() == "mouse" {
+                        if x > 0.0 && x < width as f64 {
+                            paddle.set_x(x - PADDLE_WIDTH / 2.0);
+                            if is_waiting_for_launch(status.borrow().get_status()) {
+                                ball.borrow_mut().set_x(x);
+                            }
+                        }
+                        return;
+                    }
+                    if let Some(paddle_x) = touch.borrow_mut().drag(e.pointer_id(), x) {
+                        paddle.set_x(paddle_x.clamp(0.0, width as f64 - PADDLE_WIDTH));
+                        if is_waiting_for_launch(status.borrow().get_status()) {
+                            ball.borrow_mut().set_x(paddle.get_x() + PADDLE_WIDTH / 2.0);
+                        }
+                    }
+                },
+            );
+        }
+
+        {
+            let touch = touch.clone();
+            let paddle = paddle.clone();
+            let status = status.clone();
+            let ball = ball.clone();
+            let audio = audio.clone();
+            let editor = editor.clone();
+            listeners.add(
+                &event_target,
+                "pointerup",
+                move |e: web_sys::PointerEvent| {
+                    // iOSでは指を離したときでないと音を出せないことがある
+                    audio.resume();
+                    if editor.borrow().is_open() {
+                        editor.borrow_mut().pointer_up();
+                        return;
+                    }
+                    let action = touch.borrow_mut().up(e.pointer_id(), e.time_stamp());
+                    if let Some(action) = action {
+                        handle_action(
+                            action,
+                            &mut paddle.borrow_mut(),
+                            &mut ball.borrow_mut(),
+                            &mut status.borrow_mut(),
+                        );
+                    }
+                },
+            );
+        }
+
+        {
+            let editor = editor.clone();
+            listeners.add(
+                &event_target,
+                "pointercancel",
+                move |e: web_sys::PointerEvent| {
+                    editor.borrow_mut().pointer_up();
+                    touch.borrow_mut().cancel(e.pointer_id());
+                },
+            );
+        }
+
+        // エディタでは右ドラッグで消すのでメニューを出さない
+        {
+            let editor = editor.clone();
+            listeners.add(&event_target, "contextmenu", move |e: web_sys::Event| {
+                if editor.borrow().is_open() {
+                    e.prevent_default();
                 }
-            }
-        }) as Box<dyn FnMut(web_sys::MouseEvent)>);
-        document.set_onmousemove(Some(mousemove_handler.as_ref().unchecked_ref()));
-        mousemove_handler.forget();
+            });
+        }
     }
 
-    // クリックのスタートイベント
+    // Escなどでブラウザがロックを外したときは一時停止にする
     {
+        let canvas = canvas.clone();
         let status = status.clone();
-        let click_handler = Closure::wrap(Box::new(move |_e: web_sys::MouseEvent| {
-            status.borrow_mut().set_status(Status::Start);
-        }) as Box<dyn FnMut(web_sys::MouseEvent)>);
-        document.set_onclick(Some(click_handler.as_ref().unchecked_ref()));
-        click_handler.forget();
+        listeners.add(&document, "pointerlockchange", move |_: web_sys::Event| {
+            let mut status = status.borrow_mut();
+            if !is_pointer_locked(&canvas) && status.get_status() == Status::Start {
+                pause(&mut status);
+            }
+        });
+    }
+
+    Ok(GameHandle {
+        listeners,
+        frame: frame_loop,
+        animation_id,
+        canvas,
+        audio,
+        callbacks,
+        status,
+        ball,
+        paddle,
+        bricks,
+        config,
+        custom_level,
+        restart,
+        step_frames,
+        editor,
+        destroyed,
+    })
+}
+
+/// キャンバスの要素かセレクタ文字列からキャンバスを探す
+fn find_canvas(
+    document: &web_sys::Document,
+    target: JsValue,
+) -> Result<web_sys::HtmlCanvasElement, JsValue> {
+    let element = match target.as_string() {
+        Some(selector) => document
+            .query_selector(&selector)?
+            .ok_or_else(|| JsValue::from_str(&format!("{} is not found", selector)))?
+            .into(),
+        None => target,
+    };
+    element
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| JsValue::from_str("CanvasElement is invalid"))
+}
+
+/// ステージをクリアした後に遊ぶレベル ステージを1つ進め、点数と残機はそのまま
+/// 読み込んだレベルがあれば次のステージもそれを遊ぶ
+pub fn next_level(status: &mut GameStatus, custom_level: Option<&Level>) -> Level {
+    status.next_stage();
+    custom_level
+        .cloned()
+        .unwrap_or_else(|| Level::for_stage(status.get_stage()))
+}
+
+/// levelのブロックを置き直す 前のレベルの画像は捨て、load_imagesがtrueなら画像を読み始める
+fn set_up_level(
+    bricks: &RefCell<Container>,
+    bitmap_container: &Rc<RefCell<BitmapContainer<ImageBitmap>>>,
+    level: Level,
+    asset_url: String,
+    load: bool,
+) {
+    let manifest = bricks.borrow().get_manifest();
+    bitmap_container.borrow_mut().reset();
+    if load {
+        load_images(bitmap_container, &asset_url, &level, &manifest);
+    }
+    let mut new_bricks = Container::new(asset_url);
+    new_bricks.set_manifest(manifest);
+    new_bricks.set_level(level);
+    *bricks.borrow_mut() = new_bricks;
+}
+
+/// 各セルの画像を読み込んでcontainerに入れる ブロックを置かないマスは最初から裏の絵
+fn load_images(
+    container: &Rc<RefCell<BitmapContainer<ImageBitmap>>>,
+    asset_url: &str,
+    level: &Level,
+    manifest: &Rc<AssetManifest>,
+) {
+    for i in 0..BRICK_COLUMN_COUNT {
+        for j in 0..BRICK_ROW_COUNT {
+            let asset_url = asset_url.to_string();
+            let manifest = manifest.clone();
+            let cover = match level.get_cell(i, j) {
+                Some(_) => level.get_cover().to_string(),
+                None => level.get_hidden().to_string(),
+            };
+            let local_container = container.clone();
+            // 読み終わる前にrestartされたら捨てる
+            let generation = container.borrow().get_generation();
+            spawn_local(async move {
+                // moveされたやつの参照を取るのでライフタイムは問題ない
+                // asyncの中でborrow_mutを取るとマルチスレッドについて考慮が必要(lockとか)
+                // let mut mut_bitmap = local_container.borrow_mut();
+                // 読めなければそのセルは描かない
+                let image = load_image(i as u32, j as u32, &asset_url, &cover, &manifest).await;
+                if let Some(image) = image {
+                    local_container
+                        .borrow_mut()
+                        .insert_for(generation, i, j, image);
+                }
+            });
+        }
+    }
+}
+
+/// パドルの上で発射を待っているボール
+fn new_ball(width: u32, height: u32) -> Ball {
+    Ball::new(
+        2.0 * SPEED,
+        -2.0 * SPEED,
+        width as f64 / 2.0,
+        height as f64 - BALL_RADIUS * 2.0,
+    )
+}
+
+/// ボールがパドルの上で発射を待っている状態
+fn is_waiting_for_launch(status: Status) -> bool {
+    matches!(status, Status::Title | Status::Prepare | Status::Stop)
+}
+
+/// パドルを操作している状態 ポインターロックはこの間だけ使う
+/// ステージクリアの表示中もすぐ次のステージが始まるのでロックしたままにする
+fn is_controlling_paddle(status: Status) -> bool {
+    is_waiting_for_launch(status) || matches!(status, Status::Start | Status::StageClear)
+}
+
+fn launch(status: &mut GameStatus) {
+    if is_waiting_for_launch(status.get_status()) {
+        status.start_timer(js_sys::Date::now());
+        status.set_status(Status::Start);
+    }
+}
+
+fn pause(status: &mut GameStatus) {
+    status.pause_timer(js_sys::Date::now());
+    status.set_status(Status::Pause);
+}
+
+fn resume(status: &mut GameStatus) {
+    status.resume_timer(js_sys::Date::now());
+    status.set_status(Status::Start);
+}
+
+/// 割り当てられたキーが押されたときの操作
+fn handle_action(action: Action, paddle: &mut Paddle, ball: &mut Ball, status: &mut GameStatus) {
+    match action {
+        Action::MoveLeft => paddle.set_left_pressed(true),
+        Action::MoveRight => paddle.set_right_pressed(true),
+        Action::Launch => {
+            // キーボードで発射するときはパドルの中央から
+            if is_waiting_for_launch(status.get_status()) {
+                ball.set_x(paddle.get_x() + PADDLE_WIDTH / 2.0);
+            }
+            launch(status);
+        }
+        Action::Pause => match status.get_status() {
+            Status::Start => pause(status),
+            Status::Pause => resume(status),
+            _ => {}
+        },
+        // 撃つものはまだないので割り当てだけ用意しておく
+        Action::Fire => {}
+    }
+}
+
+fn draw_pause<R: Renderer>(renderer: &mut R, width: f64, height: f64) {
+    renderer.fill_rect(0.0, 0.0, width, height, "rgba(0, 0, 0, 0.5)");
+    renderer.fill_text(
+        "PAUSED",
+        width / 2.0,
+        height / 2.0,
+        "32px Arial",
+        "rgb(255, 255, 255)",
+        TextAlign::Center,
+    );
+}
+
+/// ステージクリアやゲームオーバーの表示
+fn draw_result<R: Renderer>(
+    renderer: &mut R,
+    message: &str,
+    status: &GameStatus,
+    width: f64,
+    height: f64,
+) {
+    renderer.fill_rect(0.0, 0.0, width, height, "rgba(0, 0, 0, 0.5)");
+    renderer.fill_text(
+        message,
+        width / 2.0,
+        height / 2.0,
+        "24px Arial",
+        "rgb(255, 255, 255)",
+        TextAlign::Center,
+    );
+    renderer.fill_text(
+        &format!("Score: {}", status.get_score()),
+        width / 2.0,
+        height / 2.0 + 32.0,
+        "16px Arial",
+        "rgb(255, 255, 255)",
+        TextAlign::Center,
+    );
+}
+
+/// エディタから頼まれたファイルの読み書きと試遊
+fn handle_editor_action(
+    action: EditorAction,
+    editor: &Rc<RefCell<Editor>>,
+    custom_level: &RefCell<Option<Level>>,
+    restart: &Cell<bool>,
+    document: &web_sys::Document,
+    file_dialog: &FileDialog,
+) {
+    match action {
+        EditorAction::TestPlay => {
+            // 遊べないレベルならエディタのまま直してもらう
+            let level = editor.borrow().get_level().clone();
+            if let Err(e) = level.validate() {
+                web_sys::console::error_1(&JsValue::from_str(&format!("invalid level: {}", e)));
+                return;
+            }
+            // エディタに戻るとこのレベルのまま続きを編集できる
+            *custom_level.borrow_mut() = Some(level);
+            editor.borrow_mut().close();
+            restart.set(true);
+        }
+        EditorAction::Export => {
+            if let Err(e) = level_file::download(document, editor.borrow().get_level()) {
+                web_sys::console::error_1(&e);
+            }
+        }
+        EditorAction::Import => {
+            let editor = editor.clone();
+            let result = file_dialog.open(document, move |level| {
+                editor.borrow_mut().set_level(level);
+            });
+            if let Err(e) = result {
+                web_sys::console::error_1(&e);
+            }
+        }
+        EditorAction::PlayBuiltin => {
+            custom_level.borrow_mut().take();
+            editor.borrow_mut().close();
+            restart.set(true);
+        }
     }
 }
 
-fn request_animation_frame(f: &Closure<dyn FnMut()>) {
+/// タイトル画面の1/2/3で選ぶ難易度
+fn difficulty_for_key(code: &str) -> Option<Difficulty> {
+    match code {
+        "Digit1" => Some(Difficulty::Easy),
+        "Digit2" => Some(Difficulty::Normal),
+        "Digit3" => Some(Difficulty::Hard),
+        _ => None,
+    }
+}
+
+/// M でミュート、-/+ で音量 設定を変えたらtrue
+fn handle_sound_key(key: &str, settings: &mut Settings) -> bool {
+    match key {
+        "m" | "M" => {
+            let muted = !settings.get_muted();
+            settings.set_muted(muted);
+        }
+        "-" => {
+            let volume = settings.get_volume() - VOLUME_STEP;
+            settings.set_volume(volume);
+        }
+        "+" | "=" => {
+            let volume = settings.get_volume() + VOLUME_STEP;
+            settings.set_volume(volume);
+        }
+        _ => return false,
+    }
+    settings.save();
+    true
+}
+
+/// L でポインターロックの切り替え、[/] でその感度
+fn handle_mouse_key(key: &str, settings: &mut Settings) {
+    match key {
+        "l" | "L" => {
+            let pointer_lock = !settings.get_pointer_lock();
+            settings.set_pointer_lock(pointer_lock);
+        }
+        "[" => {
+            let sensitivity = settings.get_mouse_sensitivity() - MOUSE_SENSITIVITY_STEP;
+            settings.set_mouse_sensitivity(sensitivity);
+        }
+        "]" => {
+            let sensitivity = settings.get_mouse_sensitivity() + MOUSE_SENSITIVITY_STEP;
+            settings.set_mouse_sensitivity(sensitivity);
+        }
+        _ => return,
+    }
+    settings.save();
+}
+
+/// ネームエントリー中のキー入力 Enterで登録したらtrueを返すのでタイトルに戻す
+fn handle_name_entry(
+    e: &web_sys::KeyboardEvent,
+    status: &GameStatus,
+    high_scores: &mut HighScores,
+) -> bool {
+    let key = e.key();
+    match key.as_str() {
+        "Enter" => {
+            let now = js_sys::Date::now();
+            // toISOStringはUTCなのでローカルの日付に直してから切り出す
+            let date = js_sys::Date::new(&JsValue::from_f64(
+                now - js_sys::Date::new_0().get_timezone_offset() * 60.0 * 1000.0,
+            ));
+            let date: String = String::from(date.to_iso_string())
+                .chars()
+                .take(10)
+                .collect();
+            high_scores.commit(status, date, status.get_total_duration(now));
+            return true;
+        }
+        "Backspace" => {
+            e.prevent_default();
+            high_scores.pop_char();
+        }
+        _ => {
+            let mut chars = key.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                high_scores.push_char(c);
+            }
+        }
+    }
+    false
+}
+
+/// タイトル画面 クリックかタップでスタート
+fn draw_title<R: Renderer>(
+    renderer: &mut R,
+    high_scores: &HighScores,
+    settings: &Settings,
+    status: &GameStatus,
+    width: f64,
+    height: f64,
+) {
+    renderer.fill_rect(0.0, 0.0, width, height, "rgba(0, 0, 0, 0.7)");
+    renderer.fill_text(
+        "TORA BREAKOUT",
+        width / 2.0,
+        160.0,
+        "32px Arial",
+        "rgb(255, 136, 0)",
+        TextAlign::Center,
+    );
+    renderer.fill_text(
+        "CLICK OR TAP TO START",
+        width / 2.0,
+        200.0,
+        "16px Arial",
+        "rgb(255, 255, 255)",
+        TextAlign::Center,
+    );
+    let motion = if settings.get_reduce_motion() {
+        "ON"
+    } else {
+        "OFF"
+    };
+    renderer.fill_text(
+        "F1: CONTROLS",
+        width / 2.0,
+        256.0,
+        "12px Arial",
+        "rgb(200, 200, 200)",
+        TextAlign::Center,
+    );
+    renderer.fill_text(
+        &format!("F2: REDUCE MOTION {}", motion),
+        width / 2.0,
+        224.0,
+        "12px Arial",
+        "rgb(200, 200, 200)",
+        TextAlign::Center,
+    );
+    let sound = if settings.get_muted() {
+        "MUTED".to_string()
+    } else {
+        format!("{}%", (settings.get_volume() * 100.0).round())
+    };
+    renderer.fill_text(
+        &format!("M: MUTE  -/+: VOLUME {}", sound),
+        width / 2.0,
+        240.0,
+        "12px Arial",
+        "rgb(200, 200, 200)",
+        TextAlign::Center,
+    );
+    let pointer_lock = if settings.get_pointer_lock() {
+        "ON"
+    } else {
+        "OFF"
+    };
+    renderer.fill_text(
+        &format!(
+            "L: POINTER LOCK {}  [/]: SENSITIVITY {:.2}",
+            pointer_lock,
+            settings.get_mouse_sensitivity()
+        ),
+        width / 2.0,
+        272.0,
+        "12px Arial",
+        "rgb(200, 200, 200)",
+        TextAlign::Center,
+    );
+    renderer.fill_text(
+        "E: LEVEL EDITOR",
+        width / 2.0,
+        288.0,
+        "12px Arial",
+        "rgb(200, 200, 200)",
+        TextAlign::Center,
+    );
+    renderer.fill_text(
+        &format!("1/2/3: DIFFICULTY {}", status.get_difficulty().get_label()),
+        width / 2.0,
+        304.0,
+        "12px Arial",
+        "rgb(200, 200, 200)",
+        TextAlign::Center,
+    );
+    high_scores.draw_table(renderer, width, 328.0);
+}
+
+fn request_animation_frame(f: &Closure<dyn FnMut()>) -> i32 {
     web_sys::window()
         .unwrap()
         .request_animation_frame(f.as_ref().unchecked_ref())
-        .expect("should register `requestAnimationFrame` OK");
+        .expect("should register `requestAnimationFrame` OK")
 }
 
 pub fn update(
     ball: &mut Ball,
     paddle: &mut Paddle,
     status: &mut GameStatus,
+    events: &mut Events,
     width: f64,
     height: f64,
 ) {
@@ -369,11 +1824,19 @@ pub fn update(
     {
         // 壁にあたった場合その１
         ball.set_dx(-ball.get_dx());
+        events.push(GameEvent::WallHit {
+            x: ball.get_x(),
+            y: ball.get_y(),
+        });
     }
 
     if ball.get_y() + ball.get_dy() < BALL_RADIUS {
         // 壁にあたった場合その2
         ball.set_dy(-ball.get_dy());
+        events.push(GameEvent::WallHit {
+            x: ball.get_x(),
+            y: ball.get_y(),
+        });
     } else if ball.get_y() + ball.get_dy() > height as f64 - BALL_RADIUS {
         if ball.get_x() > paddle.get_x() && ball.get_x() < paddle.get_x() + PADDLE_WIDTH {
             // パドルにボールが当たった場合
@@ -383,28 +1846,35 @@ pub fn update(
             ball.set_dx(radian.cos() * speed);
             ball.set_dy(-radian.sin() * speed);
             ball.add_speed();
+            events.push(GameEvent::PaddleHit {
+                x: ball.get_x(),
+                y: ball.get_y(),
+            });
         } else {
             ball.set_dy(-ball.get_dy());
             // ここのelse節は下に突き抜けた場合
             status.set_status(Status::Stop);
             status.set_lives(status.get_lives() - 1);
+            events.push(GameEvent::LifeLost);
             if status.get_lives() == 0 {
-                let _ = web_sys::window().unwrap().alert_with_message("GAME OVER");
-                let _ = web_sys::window().unwrap().location().reload();
+                status.set_status(Status::GameOver);
+                events.push(GameEvent::GameOver);
             } else {
                 ball.set_x(width as f64 / 2.0);
                 ball.set_y(height as f64 - BALL_RADIUS * 2.0);
                 ball.set_dx(2.0 * SPEED);
                 ball.set_dy(-2.0 * SPEED);
                 ball.init_speed();
+                ball.clear_trail();
                 paddle.set_x((width as f64 - PADDLE_WIDTH) / 2.0);
             }
         }
     }
-    if paddle.get_right_pressed() && paddle.get_x() < width as f64 - PADDLE_WIDTH {
-        paddle.set_x(paddle.get_x() + 7.0);
-    } else if paddle.get_left_pressed() && paddle.get_x() > 0.0 {
-        paddle.set_x(paddle.get_x() - 7.0);
+    let direction = paddle.get_direction();
+    if (direction > 0.0 && paddle.get_x() < width as f64 - PADDLE_WIDTH)
+        || (direction < 0.0 && paddle.get_x() > 0.0)
+    {
+        paddle.set_x(paddle.get_x() + PADDLE_SPEED * direction);
     }
     ball.set_x(ball.get_x() + ball.get_dx());
     ball.set_y(ball.get_y() + ball.get_dy());
@@ -0,0 +1,58 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::EventTarget;
+
+struct Listener {
+    target: EventTarget,
+    event: &'static str,
+    closure: Closure<dyn FnMut(JsValue)>,
+}
+
+/// addEventListenerで登録したハンドラ
+/// ページの他のハンドラを上書きしないようにon〜には代入せず、後でまとめて外せるようにクロージャを持っておく
+#[derive(Default)]
+pub struct EventListeners {
+    listeners: Vec<Listener>,
+}
+
+impl EventListeners {
+    pub fn new() -> EventListeners {
+        EventListeners::default()
+    }
+
+    pub fn add<E, F>(&mut self, target: &EventTarget, event: &'static str, mut handler: F)
+    where
+        E: JsCast,
+        F: FnMut(E) + 'static,
+    {
+        let closure =
+            Closure::wrap(Box::new(move |e: JsValue| handler(e.unchecked_into::<E>()))
+                as Box<dyn FnMut(JsValue)>);
+        if target
+            .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+            .is_ok()
+        {
+            self.listeners.push(Listener {
+                target: target.clone(),
+                event,
+                closure,
+            });
+        }
+    }
+
+    pub fn remove_all(&mut self) {
+        for listener in self.listeners.drain(..) {
+            let _ = listener.target.remove_event_listener_with_callback(
+                listener.event,
+                listener.closure.as_ref().unchecked_ref(),
+            );
+        }
+    }
+}
+
+impl Drop for EventListeners {
+    // クロージャを捨てた後に呼ばれるとJS側で例外になるので、先に外しておく
+    fn drop(&mut self) {
+        self.remove_all();
+    }
+}
@@ -0,0 +1,330 @@
+use crate::audio::{schedule_tone, Tone, Wave};
+use crate::consts::{MUSIC_CROSSFADE, MUSIC_LOOKAHEAD, MUSIC_TEMPO_BOOST};
+use web_sys::{AudioContext, AudioNode, GainNode};
+
+/// パターンファイルの1レイヤー Noneは休符
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    wave: Wave,
+    volume: f64,
+    // 強さがこれ以上のときだけ鳴らす
+    intensity: f64,
+    notes: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    name: String,
+    bpm: f64,
+    steps_per_beat: u32,
+    layers: Vec<Layer>,
+}
+
+impl Track {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// 強さに応じてテンポを上げた1ステップの秒数
+    fn step_duration(&self, intensity: f64) -> f64 {
+        60.0 / (self.bpm * (1.0 + MUSIC_TEMPO_BOOST * intensity)) / self.steps_per_beat as f64
+    }
+}
+
+/// "A4" "C#5" "Eb3" を周波数にする
+pub fn note_frequency(note: &str) -> Option<f64> {
+    let mut chars = note.chars();
+    let semitone = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    let (semitone, octave) = if let Some(octave) = rest.strip_prefix('#') {
+        (semitone + 1, octave)
+    } else if let Some(octave) = rest.strip_prefix('b') {
+        (semitone - 1, octave)
+    } else {
+        (semitone, rest)
+    };
+    let octave: i32 = octave.parse().ok()?;
+    let midi = 12 * (octave + 1) + semitone;
+    Some(440.0 * 2f64.powf((midi - 69) as f64 / 12.0))
+}
+
+/// パターンファイルを読む 書式はmusic/tracks.txtの先頭を参照
+pub fn parse_tracks(source: &str) -> Result<Vec<Track>, String> {
+    let mut tracks: Vec<Track> = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", i + 1, message);
+        let mut words = line.split_whitespace();
+        if line.split_whitespace().next() == Some("track") {
+            words.next();
+            let name = words.next().ok_or_else(|| error("missing track name"))?;
+            let bpm = words
+                .next()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|&v| v > 0.0)
+                .ok_or_else(|| error("invalid bpm"))?;
+            let steps_per_beat = words
+                .next()
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|&v| v > 0)
+                .ok_or_else(|| error("invalid steps per beat"))?;
+            tracks.push(Track {
+                name: name.to_string(),
+                bpm,
+                steps_per_beat,
+                layers: Vec::new(),
+            });
+            continue;
+        }
+        let track = tracks
+            .last_mut()
+            .ok_or_else(|| error("layer before any track"))?;
+        let (header, notes) = line
+            .split_once('|')
+            .ok_or_else(|| error("missing '|' between layer settings and notes"))?;
+        let mut header = header.split_whitespace();
+        // レイヤー名はパターンファイルを読みやすくするためだけのもの
+        header.next().ok_or_else(|| error("missing layer name"))?;
+        let wave = header
+            .next()
+            .and_then(Wave::from_name)
+            .ok_or_else(|| error("invalid wave"))?;
+        let volume = header
+            .next()
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or_else(|| error("invalid volume"))?;
+        let intensity = header
+            .next()
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or_else(|| error("invalid intensity"))?;
+        let notes = notes
+            .split_whitespace()
+            .map(|n| match n {
+                "." => Ok(None),
+                n => note_frequency(n)
+                    .map(Some)
+                    .ok_or_else(|| error(&format!("invalid note {}", n))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if notes.is_empty() {
+            return Err(error("layer has no notes"));
+        }
+        track.layers.push(Layer {
+            wave,
+            volume,
+            intensity,
+            notes,
+        });
+    }
+    Ok(tracks)
+}
+
+/// ゲームに組み込んだmusic/tracks.txtの曲 エラーにはファイル名を付ける
+pub fn builtin_tracks() -> Result<Vec<Track>, String> {
+    parse_tracks(include_str!("../music/tracks.txt"))
+        .map_err(|e| format!("music/tracks.txt: {}", e))
+}
+
+/// 再生中の曲 曲ごとにGainNodeを持ってクロスフェードする
+struct Playing {
+    track: usize,
+    gain: GainNode,
+    step: usize,
+    next_time: f64,
+    // フェードアウト中なら鳴らし終わる時刻
+    fade_end: Option<f64>,
+}
+
+/// トラッカー風のBGMシーケンサー
+/// 毎フレームupdateを呼び、MUSIC_LOOKAHEAD秒先までのノートをWeb Audioの時計に予約する
+pub struct Sequencer {
+    tracks: Vec<Track>,
+    current: Option<Playing>,
+    fading: Vec<Playing>,
+}
+
+impl Sequencer {
+    pub fn new(tracks: Vec<Track>) -> Sequencer {
+        Sequencer {
+            tracks,
+            current: None,
+            fading: Vec::new(),
+        }
+    }
+
+    /// 曲を切り替える 同じ曲なら何もしない
+    pub fn play(&mut self, context: &AudioContext, bus: &AudioNode, name: &str) {
+        let index = match self.tracks.iter().position(|t| t.get_name() == name) {
+            Some(index) => index,
+            None => return,
+        };
+        if self.current.as_ref().map(|p| p.track) == Some(index) {
+            return;
+        }
+        let now = context.current_time();
+        if let Some(mut old) = self.current.take() {
+            let gain = old.gain.gain();
+            let _ = gain.cancel_scheduled_values(now);
+            let _ = gain.set_value_at_time(gain.value(), now);
+            let _ = gain.linear_ramp_to_value_at_time(0.0, now + MUSIC_CROSSFADE);
+            old.fade_end = Some(now + MUSIC_CROSSFADE);
+            self.fading.push(old);
+        }
+        let gain = match context.create_gain() {
+            Ok(gain) => gain,
+            Err(_) => return,
+        };
+        let _ = gain.gain().set_value_at_time(0.0, now);
+        let _ = gain
+            .gain()
+            .linear_ramp_to_value_at_time(1.0, now + MUSIC_CROSSFADE);
+        if gain.connect_with_audio_node(bus).is_err() {
+            return;
+        }
+        self.current = Some(Playing {
+            track: index,
+            gain,
+            step: 0,
+            next_time: now,
+            fade_end: None,
+        });
+    }
+
+    /// intensityは0.0..1.0 テンポとレイヤーの数を変える
+    pub fn update(&mut self, context: &AudioContext, intensity: f64) {
+        let now = context.current_time();
+        // タブが裏にあった後などで遅れすぎていたら今から鳴らし直す
+        let tracks = &self.tracks;
+        for playing in self.current.iter_mut().chain(self.fading.iter_mut()) {
+            if playing.next_time < now {
+                playing.next_time = now;
+            }
+            let track = &tracks[playing.track];
+            let horizon = match playing.fade_end {
+                Some(end) => (now + MUSIC_LOOKAHEAD).min(end),
+                None => now + MUSIC_LOOKAHEAD,
+            };
+            while playing.next_time < horizon {
+                let step_duration = track.step_duration(intensity);
+                for layer in track.layers.iter().filter(|l| intensity >= l.intensity) {
+                    if let Some(frequency) = layer.notes[playing.step % layer.notes.len()] {
+                        let tone =
+                            Tone::new(layer.wave, frequency, step_duration * 0.9, layer.volume);
+                        let _ = schedule_tone(context, &playing.gain, &tone, playing.next_time);
+                    }
+                }
+                playing.step += 1;
+                playing.next_time += step_duration;
+            }
+        }
+        self.fading.retain(|p| match p.fade_end {
+            Some(end) if end <= now => {
+                p.gain.disconnect().ok();
+                false
+            }
+            _ => true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn note_frequency_follows_equal_temperament() {
+        assert!(close(note_frequency("A4").unwrap(), 440.0));
+        assert!(close(note_frequency("A5").unwrap(), 880.0));
+        assert!(close(note_frequency("C4").unwrap(), 261.63));
+        assert!(close(note_frequency("a2").unwrap(), 110.0));
+        assert_eq!(note_frequency("C#5"), note_frequency("Db5"));
+        assert!(close(note_frequency("Eb3").unwrap(), 155.56));
+    }
+
+    #[test]
+    fn note_frequency_rejects_unknown_notes() {
+        for note in ["", "H4", "A", "A#", "Ax", "4A"].iter() {
+            assert_eq!(note_frequency(note), None, "{}", note);
+        }
+    }
+
+    #[test]
+    fn parse_tracks_reads_tracks_and_layers() {
+        let source = "# comment
+
+track intro 120 4
+lead triangle 0.1 0.0 | A4 . C5
+bass sine 0.2 0.5 | A2
+track outro 60 1
+lead square 0.3 0.0 | E4
+";
+        let tracks = parse_tracks(source).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].get_name(), "intro");
+        assert_eq!(tracks[0].layers.len(), 2);
+        let lead = &tracks[0].layers[0];
+        assert_eq!(lead.wave, Wave::Triangle);
+        assert_eq!(lead.notes.len(), 3);
+        assert_eq!(lead.notes[1], None);
+        assert!(close(lead.notes[0].unwrap(), 440.0));
+        assert_eq!(tracks[0].layers[1].intensity, 0.5);
+        // 120BPMで1拍4ステップなら1ステップ0.125秒
+        assert!((tracks[0].step_duration(0.0) - 0.125).abs() < 1e-12);
+        assert_eq!(tracks[1].layers.len(), 1);
+    }
+
+    #[test]
+    fn parse_tracks_reports_the_line_of_an_error() {
+        let cases = [
+            ("lead sine 0.1 0.0 | A4", "line 1: layer before any track"),
+            ("track a 0 2", "line 1: invalid bpm"),
+            ("track a 120", "line 1: invalid steps per beat"),
+            ("track", "line 1: missing track name"),
+            ("track a 120 2\nlead sine 0.1 0.0 A4", "line 2: missing '|'"),
+            (
+                "track a 120 2\nlead noise 0.1 0.0 | A4",
+                "line 2: invalid wave",
+            ),
+            (
+                "track a 120 2\nlead sine loud 0.0 | A4",
+                "line 2: invalid volume",
+            ),
+            (
+                "track a 120 2\n\nlead sine 0.1 0.0 | A4 X9",
+                "line 3: invalid note X9",
+            ),
+            (
+                "track a 120 2\nlead sine 0.1 0.0 |",
+                "line 2: layer has no notes",
+            ),
+        ];
+        for (source, expected) in cases.iter() {
+            let error = parse_tracks(source).unwrap_err();
+            assert!(error.starts_with(expected), "{:?}: {}", source, error);
+        }
+    }
+
+    #[test]
+    fn builtin_tracks_parse() {
+        let tracks = builtin_tracks().unwrap();
+        let names: Vec<&str> = tracks.iter().map(|t| t.get_name()).collect();
+        assert_eq!(names, vec!["title", "game", "gameover"]);
+        assert!(tracks.iter().all(|t| !t.layers.is_empty()));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::consts::{PADDLE_HEIGHT, PADDLE_WIDTH};
+use crate::renderer::Renderer;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -7,6 +8,8 @@ pub struct Paddle {
     x: f64,
     right_pressed: bool,
     left_pressed: bool,
+    // ゲームパッドのスティックの傾き -1.0..1.0 キーが押されていなければこちらで動かす
+    axis: f64,
 }
 
 #[wasm_bindgen]
@@ -16,6 +19,7 @@ impl Paddle {
             x: x,
             right_pressed: false,
             left_pressed: false,
+            axis: 0.0,
         }
     }
 
@@ -43,11 +47,34 @@ impl Paddle {
         self.left_pressed = left_pressed;
     }
 
-    pub fn draw(&self, ctx: &web_sys::CanvasRenderingContext2d, height: f64) {
-        ctx.begin_path();
-        ctx.rect(self.x, height - PADDLE_HEIGHT, PADDLE_WIDTH, PADDLE_HEIGHT);
-        ctx.set_fill_style(&"rgb(255, 136, 0)".into());
-        ctx.fill();
-        ctx.close_path();
+    pub fn get_axis(&self) -> f64 {
+        self.axis
+    }
+
+    pub fn set_axis(&mut self, axis: f64) {
+        self.axis = axis.clamp(-1.0, 1.0);
+    }
+
+    /// キーとスティックをまとめた移動の向き -1.0..1.0
+    pub fn get_direction(&self) -> f64 {
+        if self.right_pressed {
+            1.0
+        } else if self.left_pressed {
+            -1.0
+        } else {
+            self.axis
+        }
+    }
+}
+
+impl Paddle {
+    pub fn draw<R: Renderer>(&self, renderer: &mut R, height: f64) {
+        renderer.fill_rect(
+            self.x,
+            height - PADDLE_HEIGHT,
+            PADDLE_WIDTH,
+            PADDLE_HEIGHT,
+            "rgb(255, 136, 0)",
+        );
     }
 }
@@ -0,0 +1,276 @@
+use crate::consts::{BRICK_HEIGHT, BRICK_WIDTH, MAX_PARTICLES, TRAIL_MIN_SPEED};
+use crate::renderer::Renderer;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParticleKind {
+    Debris,
+    Spark,
+    Trail,
+}
+
+#[derive(Debug, Clone)]
+struct Particle {
+    alive: bool,
+    kind: ParticleKind,
+    x: f64,
+    y: f64,
+    dx: f64,
+    dy: f64,
+    size: f64,
+    // 残りフレーム数と最初のフレーム数 透明度の計算に使う
+    life: u32,
+    max_life: u32,
+    // スロットを使い回すときにバッファも使い回すので、描画のたびに色文字列を作らない
+    color: String,
+}
+
+/// 固定長のパーティクル置き場
+/// 最初に全スロットを確保しておき、空きがなければ古いものから上書きするのでフレーム中に確保しない
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    // 次に使うスロット
+    cursor: usize,
+    rng: u32,
+}
+
+impl ParticleSystem {
+    pub fn new() -> ParticleSystem {
+        let mut particles = Vec::with_capacity(MAX_PARTICLES);
+        for _ in 0..MAX_PARTICLES {
+            particles.push(Particle {
+                alive: false,
+                kind: ParticleKind::Debris,
+                x: 0.0,
+                y: 0.0,
+                dx: 0.0,
+                dy: 0.0,
+                size: 0.0,
+                life: 0,
+                max_life: 0,
+                color: String::with_capacity(24),
+            });
+        }
+        ParticleSystem {
+            particles,
+            cursor: 0,
+            rng: 0x2545_f491,
+        }
+    }
+
+    /// xorshift32 見た目のばらつきに使うだけなので十分
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f64 / u32::MAX as f64
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn spawn(
+        &mut self,
+        kind: ParticleKind,
+        x: f64,
+        y: f64,
+        dx: f64,
+        dy: f64,
+        size: f64,
+        life: u32,
+        rgb: [u8; 3],
+    ) {
+        let p = &mut self.particles[self.cursor];
+        self.cursor = (self.cursor + 1) % MAX_PARTICLES;
+        p.alive = true;
+        p.kind = kind;
+        p.x = x;
+        p.y = y;
+        p.dx = dx;
+        p.dy = dy;
+        p.size = size;
+        p.life = life;
+        p.max_life = life;
+        p.color.clear();
+        let _ = write!(p.color, "rgb({}, {}, {})", rgb[0], rgb[1], rgb[2]);
+    }
+
+    /// ブロックの破片 colorsは壊れたブロックの画像から拾った色
+    pub fn spawn_debris(&mut self, x: f64, y: f64, colors: &[[u8; 3]]) {
+        let fallback = [[95, 95, 95]];
+        let colors = if colors.is_empty() {
+            &fallback[..]
+        } else {
+            colors
+        };
+        for i in 0..16 {
+            let angle = self.random() * std::f64::consts::PI * 2.0;
+            let speed = 0.5 + self.random() * 2.0;
+            let size = 2.0 + self.random() * 3.0;
+            let life = 30 + (self.random() * 30.0) as u32;
+            let offset_x = (self.random() - 0.5) * BRICK_WIDTH;
+            let offset_y = (self.random() - 0.5) * BRICK_HEIGHT;
+            self.spawn(
+                ParticleKind::Debris,
+                x + offset_x,
+                y + offset_y,
+                angle.cos() * speed,
+                angle.sin() * speed - 1.0,
+                size,
+                life,
+                colors[i % colors.len()],
+            );
+        }
+    }
+
+    /// パドルに当たったときの火花 上向きに散らす
+    pub fn spawn_sparks(&mut self, x: f64, y: f64) {
+        for _ in 0..10 {
+            let angle = std::f64::consts::PI * (1.1 + self.random() * 0.8);
+            let speed = 1.5 + self.random() * 2.5;
+            let g = 160 + (self.random() * 95.0) as u8;
+            let life = 10 + (self.random() * 12.0) as u32;
+            self.spawn(
+                ParticleKind::Spark,
+                x,
+                y,
+                angle.cos() * speed,
+                angle.sin() * speed,
+                1.5,
+                life,
+                [255, g, 0],
+            );
+        }
+    }
+
+    /// 速いボールの軌跡 speedはBall::get_speed
+    pub fn spawn_trail(&mut self, x: f64, y: f64, speed: usize) {
+        if speed < TRAIL_MIN_SPEED {
+            return;
+        }
+        let jitter_x = (self.random() - 0.5) * 2.0;
+        let jitter_y = (self.random() - 0.5) * 2.0;
+        self.spawn(
+            ParticleKind::Trail,
+            x + jitter_x,
+            y + jitter_y,
+            0.0,
+            0.0,
+            3.0,
+            12,
+            [255, 255, 255],
+        );
+    }
+
+    /// 1フレーム進める
+    pub fn update(&mut self) {
+        for p in self.particles.iter_mut().filter(|p| p.alive) {
+            p.life -= 1;
+            if p.life == 0 {
+                p.alive = false;
+                continue;
+            }
+            p.x += p.dx;
+            p.y += p.dy;
+            match p.kind {
+                ParticleKind::Debris => p.dy += 0.15,
+                ParticleKind::Spark => {
+                    p.dx *= 0.9;
+                    p.dy *= 0.9;
+                }
+                ParticleKind::Trail => p.size *= 0.9,
+            }
+        }
+    }
+
+    pub fn draw<R: Renderer>(&self, renderer: &mut R) {
+        for p in self.particles.iter().filter(|p| p.alive) {
+            renderer.set_global_alpha(p.life as f64 / p.max_life as f64);
+            match p.kind {
+                ParticleKind::Trail => renderer.fill_circle(p.x, p.y, p.size, &p.color),
+                _ => renderer.fill_rect(
+                    p.x - p.size / 2.0,
+                    p.y - p.size / 2.0,
+                    p.size,
+                    p.size,
+                    &p.color,
+                ),
+            }
+        }
+        renderer.set_global_alpha(1.0);
+    }
+}
+
+impl Default for ParticleSystem {
+    fn default() -> ParticleSystem {
+        ParticleSystem::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alive(system: &ParticleSystem) -> Vec<&Particle> {
+        system.particles.iter().filter(|p| p.alive).collect()
+    }
+
+    #[test]
+    fn pool_stays_full_size_and_overwrites_the_oldest() {
+        let mut system = ParticleSystem::new();
+        // 破片16個ずつでちょうど一周埋める
+        for _ in 0..MAX_PARTICLES / 16 {
+            system.spawn_debris(100.0, 100.0, &[[1, 2, 3]]);
+        }
+        assert_eq!(system.particles.len(), MAX_PARTICLES);
+        assert_eq!(alive(&system).len(), MAX_PARTICLES);
+        assert_eq!(system.cursor, 0);
+
+        // 空きがないので一番古い破片から火花で上書きされる
+        system.spawn_sparks(10.0, 10.0);
+        assert_eq!(system.particles.len(), MAX_PARTICLES);
+        assert_eq!(alive(&system).len(), MAX_PARTICLES);
+        assert!(system.particles[..10]
+            .iter()
+            .all(|p| p.kind == ParticleKind::Spark));
+        assert!(system.particles[10..]
+            .iter()
+            .all(|p| p.kind == ParticleKind::Debris));
+    }
+
+    #[test]
+    fn particles_die_when_their_life_runs_out() {
+        let mut system = ParticleSystem::new();
+        system.spawn_trail(0.0, 0.0, TRAIL_MIN_SPEED);
+        let life = system.particles[0].max_life;
+        for _ in 1..life {
+            system.update();
+        }
+        assert_eq!(alive(&system).len(), 1);
+        system.update();
+        assert!(alive(&system).is_empty());
+    }
+
+    #[test]
+    fn slow_ball_leaves_no_trail() {
+        let mut system = ParticleSystem::new();
+        system.spawn_trail(0.0, 0.0, TRAIL_MIN_SPEED - 1);
+        assert!(alive(&system).is_empty());
+    }
+
+    #[test]
+    fn debris_without_sampled_colors_is_gray() {
+        let mut system = ParticleSystem::new();
+        system.spawn_debris(0.0, 0.0, &[]);
+        let debris = alive(&system);
+        assert_eq!(debris.len(), 16);
+        assert!(debris.iter().all(|p| p.color == "rgb(95, 95, 95)"));
+    }
+
+    #[test]
+    fn debris_cycles_through_the_sampled_colors() {
+        let mut system = ParticleSystem::new();
+        system.spawn_debris(0.0, 0.0, &[[255, 0, 0], [0, 0, 255]]);
+        assert_eq!(system.particles[0].color, "rgb(255, 0, 0)");
+        assert_eq!(system.particles[1].color, "rgb(0, 0, 255)");
+        assert_eq!(system.particles[2].color, "rgb(255, 0, 0)");
+    }
+}
@@ -0,0 +1,145 @@
+use crate::consts::{TAP_MAX_DISTANCE, TAP_MAX_DURATION};
+use crate::input::Action;
+use web_sys::{Element, HtmlCanvasElement};
+
+/// パドルを動かしている指
+#[derive(Debug, Clone, Copy)]
+struct Drag {
+    id: i32,
+    start_x: f64,
+    start_time: f64,
+    // 触り始めたときのパドルの位置 指の移動量をここに足す
+    paddle_x: f64,
+    // 一番遠くまで動いた距離 タップかどうかの判定に使う
+    moved: f64,
+}
+
+/// タッチ操作
+/// 最初の指はドラッグした分だけパドルを動かし(指でパドルが隠れないように位置は合わせない)、
+/// すぐ離せばタップとして発射、最初の指を置いたまま別の指で触ると撃つ
+#[derive(Debug, Clone, Default)]
+pub struct TouchControls {
+    drag: Option<Drag>,
+}
+
+impl TouchControls {
+    pub fn new() -> TouchControls {
+        TouchControls::default()
+    }
+
+    /// xはキャンバス上の座標、timeはミリ秒
+    pub fn down(&mut self, id: i32, x: f64, time: f64, paddle_x: f64) -> Option<Action> {
+        if self.drag.is_some() {
+            return Some(Action::Fire);
+        }
+        self.drag = Some(Drag {
+            id,
+            start_x: x,
+            start_time: time,
+            paddle_x,
+            moved: 0.0,
+        });
+        None
+    }
+
+    /// ドラッグ中の指ならパドルの新しい位置を返す
+    pub fn drag(&mut self, id: i32, x: f64) -> Option<f64> {
+        let drag = self.drag.as_mut().filter(|d| d.id == id)?;
+        let delta = x - drag.start_x;
+        drag.moved = drag.moved.max(delta.abs());
+        Some(drag.paddle_x + delta)
+    }
+
+    /// 最初の指をすぐに離したらタップとして発射する
+    pub fn up(&mut self, id: i32, time: f64) -> Option<Action> {
+        let drag = self.drag.filter(|d| d.id == id)?;
+        self.drag = None;
+        if time - drag.start_time <= TAP_MAX_DURATION && drag.moved <= TAP_MAX_DISTANCE {
+            Some(Action::Launch)
+        } else {
+            None
+        }
+    }
+
+    /// pointercancelのときは何も起こさずに忘れる
+    pub fn cancel(&mut self, id: i32) {
+        if self.drag.map(|d| d.id) == Some(id) {
+            self.drag = None;
+        }
+    }
+}
+
+/// キャンバスがポインターロックされているか
+pub fn is_pointer_locked(canvas: &HtmlCanvasElement) -> bool {
+    let locked = canvas
+        .owner_document()
+        .and_then(|document| document.pointer_lock_element());
+    let canvas: &Element = canvas.as_ref();
+    locked.as_ref() == Some(canvas)
+}
+
+/// ロックしていれば解除する
+pub fn exit_pointer_lock(canvas: &HtmlCanvasElement) {
+    if !is_pointer_locked(canvas) {
+        return;
+    }
+    if let Some(document) = canvas.owner_document() {
+        document.exit_pointer_lock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quick_release_is_a_tap_that_launches() {
+        let mut touch = TouchControls::new();
+        assert_eq!(touch.down(1, 100.0, 0.0, 200.0), None);
+        assert_eq!(
+            touch.drag(1, 100.0 + TAP_MAX_DISTANCE),
+            Some(200.0 + TAP_MAX_DISTANCE)
+        );
+        assert_eq!(touch.up(1, TAP_MAX_DURATION), Some(Action::Launch));
+    }
+
+    #[test]
+    fn moving_too_far_is_a_drag() {
+        let mut touch = TouchControls::new();
+        touch.down(1, 100.0, 0.0, 200.0);
+        touch.drag(1, 100.0 + TAP_MAX_DISTANCE + 1.0);
+        // 元の位置に戻しても一度遠くまで動いていればタップではない
+        assert_eq!(touch.drag(1, 100.0), Some(200.0));
+        assert_eq!(touch.up(1, 10.0), None);
+    }
+
+    #[test]
+    fn holding_too_long_is_not_a_tap() {
+        let mut touch = TouchControls::new();
+        touch.down(1, 100.0, 0.0, 200.0);
+        assert_eq!(touch.up(1, TAP_MAX_DURATION + 1.0), None);
+    }
+
+    #[test]
+    fn second_finger_fires_without_moving_the_paddle() {
+        let mut touch = TouchControls::new();
+        touch.down(1, 100.0, 0.0, 200.0);
+        assert_eq!(touch.down(2, 300.0, 10.0, 200.0), Some(Action::Fire));
+        assert_eq!(touch.drag(2, 350.0), None);
+        assert_eq!(touch.up(2, 20.0), None);
+        // 最初の指はまだパドルを動かせる
+        assert_eq!(touch.drag(1, 90.0), Some(190.0));
+    }
+
+    #[test]
+    fn cancel_forgets_the_drag_without_launching() {
+        let mut touch = TouchControls::new();
+        touch.down(1, 100.0, 0.0, 200.0);
+        touch.cancel(1);
+        assert_eq!(touch.up(1, 10.0), None);
+        assert_eq!(touch.drag(1, 120.0), None);
+        // 次に触った指が新しいドラッグになる
+        assert_eq!(touch.down(2, 50.0, 20.0, 200.0), None);
+        assert_eq!(touch.drag(2, 60.0), Some(210.0));
+    }
+}
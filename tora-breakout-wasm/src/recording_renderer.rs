@@ -0,0 +1,189 @@
+use crate::renderer::{Renderer, TextAlign};
+
+/// RecordingRendererに記録される描画命令
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum DrawCommand {
+    ClearRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    FillRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: String,
+    },
+    StrokeRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: String,
+        line_width: f64,
+    },
+    FillCircle {
+        x: f64,
+        y: f64,
+        radius: f64,
+        color: String,
+    },
+    DrawImage {
+        image: String,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    FillText {
+        text: String,
+        x: f64,
+        y: f64,
+        font: String,
+        color: String,
+        align: TextAlign,
+    },
+    ClipRect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    Save,
+    Restore,
+    Translate {
+        x: f64,
+        y: f64,
+    },
+    Scale {
+        x: f64,
+        y: f64,
+    },
+    SetGlobalAlpha {
+        alpha: f64,
+    },
+}
+
+/// 何も描かずに描画命令を記録するだけのRenderer
+/// ブラウザなしでdrawの結果を確認するために使う 画像は名前で区別する
+#[derive(Debug, Clone, Default)]
+pub struct RecordingRenderer {
+    commands: Vec<DrawCommand>,
+}
+
+impl RecordingRenderer {
+    pub fn new() -> RecordingRenderer {
+        RecordingRenderer::default()
+    }
+
+    pub fn get_commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    type Image = String;
+
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.commands.push(DrawCommand::ClearRect {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: &str) {
+        self.commands.push(DrawCommand::FillRect {
+            x,
+            y,
+            width,
+            height,
+            color: color.to_string(),
+        });
+    }
+
+    fn stroke_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: &str,
+        line_width: f64,
+    ) {
+        self.commands.push(DrawCommand::StrokeRect {
+            x,
+            y,
+            width,
+            height,
+            color: color.to_string(),
+            line_width,
+        });
+    }
+
+    fn fill_circle(&mut self, x: f64, y: f64, radius: f64, color: &str) {
+        self.commands.push(DrawCommand::FillCircle {
+            x,
+            y,
+            radius,
+            color: color.to_string(),
+        });
+    }
+
+    fn draw_image(&mut self, image: &String, x: f64, y: f64, width: f64, height: f64) {
+        self.commands.push(DrawCommand::DrawImage {
+            image: image.clone(),
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, font: &str, color: &str, align: TextAlign) {
+        self.commands.push(DrawCommand::FillText {
+            text: text.to_string(),
+            x,
+            y,
+            font: font.to_string(),
+            color: color.to_string(),
+            align,
+        });
+    }
+
+    fn clip_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.commands.push(DrawCommand::ClipRect {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn save(&mut self) {
+        self.commands.push(DrawCommand::Save);
+    }
+
+    fn restore(&mut self) {
+        self.commands.push(DrawCommand::Restore);
+    }
+
+    fn translate(&mut self, x: f64, y: f64) {
+        self.commands.push(DrawCommand::Translate { x, y });
+    }
+
+    fn scale(&mut self, x: f64, y: f64) {
+        self.commands.push(DrawCommand::Scale { x, y });
+    }
+
+    fn set_global_alpha(&mut self, alpha: f64) {
+        self.commands.push(DrawCommand::SetGlobalAlpha { alpha });
+    }
+}
@@ -0,0 +1,120 @@
+/// fill_textの基準位置
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// 描画先の抽象化
+/// ゲームオブジェクトのdrawはこのtraitだけを使い、Canvas2D以外の描画先にも出せるようにする
+/// 色はCSSの色文字列("rgb(255, 136, 0)"や"black")で渡す
+pub trait Renderer {
+    /// 描画先ごとの画像の型 Canvas2DならImageBitmap
+    type Image;
+
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64);
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: &str);
+
+    fn stroke_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: &str,
+        line_width: f64,
+    );
+
+    fn fill_circle(&mut self, x: f64, y: f64, radius: f64, color: &str);
+
+    fn draw_image(&mut self, image: &Self::Image, x: f64, y: f64, width: f64, height: f64);
+
+    /// fontはCSSのfont指定("16px Arial")
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, font: &str, color: &str, align: TextAlign);
+
+    /// これ以降の描画を矩形の中に制限する 今のクリップとの重なりになり、restoreで元に戻る
+    fn clip_rect(&mut self, x: f64, y: f64, width: f64, height: f64);
+
+    /// 変形とアルファとクリップをスタックに積む restoreと対で使う
+    fn save(&mut self);
+
+    fn restore(&mut self);
+
+    fn translate(&mut self, x: f64, y: f64);
+
+    fn scale(&mut self, x: f64, y: f64);
+
+    fn set_global_alpha(&mut self, alpha: f64);
+}
+
+/// "rgb(r, g, b)" "rgba(r, g, b, a)" "#rrggbb" といくつかの色名を解釈する
+/// 解釈できない場合は黒
+pub fn parse_color(color: &str) -> ([u8; 3], f64) {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') {
+        if hex.len() == 6 {
+            if let Ok(v) = u32::from_str_radix(hex, 16) {
+                return ([(v >> 16) as u8, (v >> 8) as u8, v as u8], 1.0);
+            }
+        }
+        return ([0, 0, 0], 1.0);
+    }
+    let args = color
+        .strip_prefix("rgba(")
+        .or_else(|| color.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'));
+    if let Some(args) = args {
+        let values: Vec<f64> = args
+            .split(',')
+            .filter_map(|v| v.trim().parse::<f64>().ok())
+            .collect();
+        if values.len() >= 3 {
+            let alpha = values.get(3).cloned().unwrap_or(1.0);
+            return (
+                [values[0] as u8, values[1] as u8, values[2] as u8],
+                alpha.clamp(0.0, 1.0),
+            );
+        }
+        return ([0, 0, 0], 1.0);
+    }
+    match color {
+        "white" => ([255, 255, 255], 1.0),
+        "red" => ([255, 0, 0], 1.0),
+        "transparent" => ([0, 0, 0], 0.0),
+        _ => ([0, 0, 0], 1.0),
+    }
+}
+
+/// CSSのfont指定を分解したもの
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontSpec {
+    pub size: f64,
+    pub bold: bool,
+    pub family: String,
+}
+
+/// "bold 16px Arial"を分解する サイズが見つからなければCanvas2Dの既定の10px sans-serif
+pub fn parse_font(font: &str) -> FontSpec {
+    let mut spec = FontSpec {
+        size: 10.0,
+        bold: false,
+        family: "sans-serif".to_string(),
+    };
+    let mut parts = font.split_whitespace();
+    while let Some(part) = parts.next() {
+        if part == "bold" {
+            spec.bold = true;
+        } else if let Some(size) = part.strip_suffix("px").and_then(|v| v.parse().ok()) {
+            spec.size = size;
+            // サイズの後ろは全部フォント名
+            let family = parts.collect::<Vec<_>>().join(" ");
+            if !family.is_empty() {
+                spec.family = family;
+            }
+            break;
+        }
+    }
+    spec
+}
@@ -0,0 +1,224 @@
+use crate::consts::{
+    COMBO_PER_MULTIPLIER, LIFE_BONUS, MAX_MULTIPLIER, SCORE_POPUP_FRAMES, TIME_BONUS_LIMIT,
+    TIME_BONUS_PER_SECOND,
+};
+use crate::events::GameEvent;
+use crate::game_status::GameStatus;
+use crate::renderer::{Renderer, TextAlign};
+
+/// ブロックの位置に浮かび上がる得点表示
+#[derive(Debug, Clone)]
+pub struct ScorePopup {
+    x: f64,
+    y: f64,
+    text: String,
+    frames: u32,
+}
+
+/// コンボと倍率を管理してGameStatusのスコアに加算する
+#[derive(Debug, Clone, Default)]
+pub struct Scoring {
+    // 最後にパドルに触れてからの連続ヒット数
+    combo: u32,
+    popups: Vec<ScorePopup>,
+}
+
+impl Scoring {
+    pub fn new() -> Scoring {
+        Scoring::default()
+    }
+
+    pub fn get_multiplier(&self) -> u32 {
+        (1 + self.combo / COMBO_PER_MULTIPLIER).min(MAX_MULTIPLIER)
+    }
+
+    /// イベントを見てスコアを加算する ステージクリアのボーナスはnowのプレイ時間から計算
+    pub fn handle(&mut self, event: &GameEvent, status: &mut GameStatus, now: f64) {
+        match *event {
+            GameEvent::BrickBroken { x, y, kind, .. } => {
+                let multiplier = self.get_multiplier();
+                let points = kind.get_points() * multiplier;
+                status.set_score(status.get_score() + points);
+                let text = if multiplier > 1 {
+                    format!("+{} x{}", points, multiplier)
+                } else {
+                    format!("+{}", points)
+                };
+                self.push_popup(x, y, text);
+            }
+            GameEvent::BrickHit { .. } => {
+                self.combo += 1;
+            }
+            GameEvent::PaddleHit { .. } | GameEvent::LifeLost => {
+                self.combo = 0;
+            }
+            GameEvent::StageCleared => {
+                let remaining = (TIME_BONUS_LIMIT - status.get_duration(now)).max(0.0);
+                let time_bonus = remaining as u32 * TIME_BONUS_PER_SECOND;
+                let life_bonus = status.get_lives() * LIFE_BONUS;
+                status.set_score(status.get_score() + time_bonus + life_bonus);
+            }
+            _ => {}
+        }
+    }
+
+    fn push_popup(&mut self, x: f64, y: f64, text: String) {
+        self.popups.push(ScorePopup {
+            x,
+            y,
+            text,
+            frames: SCORE_POPUP_FRAMES,
+        });
+    }
+
+    /// 1フレーム分ポップアップを進めて消えたものを取り除く
+    pub fn update_popups(&mut self) {
+        for p in self.popups.iter_mut() {
+            p.frames -= 1;
+            p.y -= 0.6;
+        }
+        self.popups.retain(|p| p.frames > 0);
+    }
+
+    pub fn draw_popups<R: Renderer>(&self, renderer: &mut R) {
+        for p in self.popups.iter() {
+            renderer.set_global_alpha(p.frames as f64 / SCORE_POPUP_FRAMES as f64);
+            renderer.fill_text(
+                &p.text,
+                p.x,
+                p.y,
+                "bold 14px Arial",
+                "rgb(255, 204, 0)",
+                TextAlign::Center,
+            );
+        }
+        renderer.set_global_alpha(1.0);
+    }
+
+    /// 倍率とコンボ数をスコアの横に表示する
+    pub fn draw_multiplier<R: Renderer>(&self, renderer: &mut R, width: f64) {
+        let text = if self.combo > 1 {
+            format!("x{}  Combo {}", self.get_multiplier(), self.combo)
+        } else {
+            format!("x{}", self.get_multiplier())
+        };
+        renderer.fill_text(
+            &text,
+            width / 2.0,
+            20.0,
+            "16px Arial",
+            "rgb(0, 149, 208)",
+            TextAlign::Center,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bricks::BrickKind;
+    use crate::consts::{HARD_BRICK_POINTS, NORMAL_BRICK_POINTS};
+
+    fn broken(kind: BrickKind) -> GameEvent {
+        GameEvent::BrickBroken {
+            col: 0,
+            row: 0,
+            x: 10.0,
+            y: 20.0,
+            kind,
+        }
+    }
+
+    fn hit(scoring: &mut Scoring, status: &mut GameStatus, count: u32) {
+        for _ in 0..count {
+            scoring.handle(&GameEvent::BrickHit { col: 0, row: 0 }, status, 0.0);
+        }
+    }
+
+    #[test]
+    fn multiplier_rises_with_the_combo_up_to_the_limit() {
+        let mut scoring = Scoring::new();
+        let mut status = GameStatus::new();
+        assert_eq!(scoring.get_multiplier(), 1);
+        hit(&mut scoring, &mut status, COMBO_PER_MULTIPLIER - 1);
+        assert_eq!(scoring.get_multiplier(), 1);
+        hit(&mut scoring, &mut status, 1);
+        assert_eq!(scoring.get_multiplier(), 2);
+        hit(
+            &mut scoring,
+            &mut status,
+            COMBO_PER_MULTIPLIER * MAX_MULTIPLIER,
+        );
+        assert_eq!(scoring.get_multiplier(), MAX_MULTIPLIER);
+    }
+
+    #[test]
+    fn paddle_hit_and_lost_life_reset_the_combo() {
+        let mut scoring = Scoring::new();
+        let mut status = GameStatus::new();
+        hit(&mut scoring, &mut status, COMBO_PER_MULTIPLIER);
+        scoring.handle(&GameEvent::PaddleHit { x: 0.0, y: 0.0 }, &mut status, 0.0);
+        assert_eq!(scoring.get_multiplier(), 1);
+        hit(&mut scoring, &mut status, COMBO_PER_MULTIPLIER);
+        scoring.handle(&GameEvent::LifeLost, &mut status, 0.0);
+        assert_eq!(scoring.get_multiplier(), 1);
+    }
+
+    #[test]
+    fn broken_brick_scores_its_points_times_the_multiplier() {
+        let mut scoring = Scoring::new();
+        let mut status = GameStatus::new();
+        scoring.handle(&broken(BrickKind::Normal), &mut status, 0.0);
+        assert_eq!(status.get_score(), NORMAL_BRICK_POINTS);
+        assert_eq!(scoring.popups[0].text, format!("+{}", NORMAL_BRICK_POINTS));
+
+        hit(&mut scoring, &mut status, COMBO_PER_MULTIPLIER);
+        scoring.handle(&broken(BrickKind::Hard), &mut status, 0.0);
+        assert_eq!(
+            status.get_score(),
+            NORMAL_BRICK_POINTS + HARD_BRICK_POINTS * 2
+        );
+        assert_eq!(
+            scoring.popups[1].text,
+            format!("+{} x2", HARD_BRICK_POINTS * 2)
+        );
+    }
+
+    #[test]
+    fn stage_clear_adds_time_and_life_bonuses() {
+        let mut scoring = Scoring::new();
+        let mut status = GameStatus::new();
+        status.start_timer(1000.0);
+        // 100秒でクリア
+        scoring.handle(&GameEvent::StageCleared, &mut status, 101_000.0);
+        let time_bonus = (TIME_BONUS_LIMIT as u32 - 100) * TIME_BONUS_PER_SECOND;
+        assert_eq!(
+            status.get_score(),
+            time_bonus + status.get_lives() * LIFE_BONUS
+        );
+    }
+
+    #[test]
+    fn slow_stage_clear_gets_no_time_bonus() {
+        let mut scoring = Scoring::new();
+        let mut status = GameStatus::new();
+        status.start_timer(1000.0);
+        let late = 1000.0 + (TIME_BONUS_LIMIT + 10.0) * 1000.0;
+        scoring.handle(&GameEvent::StageCleared, &mut status, late);
+        assert_eq!(status.get_score(), status.get_lives() * LIFE_BONUS);
+    }
+
+    #[test]
+    fn popups_disappear_after_their_frames() {
+        let mut scoring = Scoring::new();
+        let mut status = GameStatus::new();
+        scoring.handle(&broken(BrickKind::Normal), &mut status, 0.0);
+        for _ in 0..SCORE_POPUP_FRAMES - 1 {
+            scoring.update_popups();
+        }
+        assert_eq!(scoring.popups.len(), 1);
+        assert!(scoring.popups[0].y < 20.0);
+        scoring.update_popups();
+        assert!(scoring.popups.is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::consts::{
+    DEFAULT_MOUSE_SENSITIVITY, DEFAULT_VOLUME, MAX_MOUSE_SENSITIVITY, MIN_MOUSE_SENSITIVITY,
+    SETTINGS_STORAGE_KEY,
+};
+use crate::input::KeyBindings;
+use crate::utils::local_storage;
+
+/// プレイヤーの設定 localStorageに保存する
+/// 項目を増やしても古い保存データを読めるように、無い項目は既定値にする
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // 画面揺れとヒットストップを止める
+    reduce_motion: bool,
+    // 全体の音量 0.0..1.0
+    volume: f64,
+    muted: bool,
+    key_bindings: KeyBindings,
+    // 発射のクリックでポインターロックし、マウスの移動量でパドルを動かす
+    pointer_lock: bool,
+    mouse_sensitivity: f64,
+}
+
+impl Settings {
+    /// 保存されていなければOSの「視差効果を減らす」設定に合わせる
+    pub fn load() -> Settings {
+        local_storage()
+            .and_then(|storage| storage.get_item(SETTINGS_STORAGE_KEY).ok()?)
+            .and_then(|json| serde_json::from_str::<Settings>(&json).ok())
+            .unwrap_or_else(|| Settings {
+                reduce_motion: prefers_reduced_motion(),
+                ..Settings::default()
+            })
+    }
+
+    pub fn save(&self) {
+        let storage = match local_storage() {
+            Some(s) => s,
+            None => return,
+        };
+        if let Ok(json) = serde_json::to_string(self) {
+            let _ = storage.set_item(SETTINGS_STORAGE_KEY, &json);
+        }
+    }
+
+    pub fn get_reduce_motion(&self) -> bool {
+        self.reduce_motion
+    }
+
+    pub fn set_reduce_motion(&mut self, reduce_motion: bool) {
+        self.reduce_motion = reduce_motion;
+    }
+
+    pub fn get_volume(&self) -> f64 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: f64) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn get_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn get_pointer_lock(&self) -> bool {
+        self.pointer_lock
+    }
+
+    pub fn set_pointer_lock(&mut self, pointer_lock: bool) {
+        self.pointer_lock = pointer_lock;
+    }
+
+    pub fn get_mouse_sensitivity(&self) -> f64 {
+        self.mouse_sensitivity
+    }
+
+    pub fn set_mouse_sensitivity(&mut self, mouse_sensitivity: f64) {
+        self.mouse_sensitivity =
+            mouse_sensitivity.clamp(MIN_MOUSE_SENSITIVITY, MAX_MOUSE_SENSITIVITY);
+    }
+
+    pub fn get_key_bindings(&self) -> &KeyBindings {
+        &self.key_bindings
+    }
+
+    pub fn get_key_bindings_mut(&mut self) -> &mut KeyBindings {
+        &mut self.key_bindings
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            reduce_motion: false,
+            volume: DEFAULT_VOLUME,
+            muted: false,
+            key_bindings: KeyBindings::default(),
+            pointer_lock: false,
+            mouse_sensitivity: DEFAULT_MOUSE_SENSITIVITY,
+        }
+    }
+}
+
+fn prefers_reduced_motion() -> bool {
+    web_sys::window()
+        .and_then(|w| w.match_media("(prefers-reduced-motion: reduce)").ok()?)
+        .map(|m| m.matches())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_does_not_reduce_motion() {
+        let settings = Settings::default();
+        assert!(!settings.get_reduce_motion());
+        assert_eq!(settings.get_volume(), DEFAULT_VOLUME);
+        assert_eq!(settings.get_mouse_sensitivity(), DEFAULT_MOUSE_SENSITIVITY);
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let mut settings = Settings::default();
+        settings.set_reduce_motion(true);
+        settings.set_volume(0.25);
+        settings.set_muted(true);
+        settings.set_pointer_lock(true);
+        settings.set_mouse_sensitivity(MAX_MOUSE_SENSITIVITY);
+
+        let json = serde_json::to_string(&settings).unwrap();
+        let loaded: Settings = serde_json::from_str(&json).unwrap();
+        assert!(loaded.get_reduce_motion());
+        assert_eq!(loaded.get_volume(), 0.25);
+        assert!(loaded.get_muted());
+        assert!(loaded.get_pointer_lock());
+        assert_eq!(loaded.get_mouse_sensitivity(), MAX_MOUSE_SENSITIVITY);
+        assert_eq!(loaded.get_key_bindings(), settings.get_key_bindings());
+    }
+
+    #[test]
+    fn missing_fields_in_old_saves_use_defaults() {
+        let loaded: Settings = serde_json::from_str(r#"{"reduce_motion":true}"#).unwrap();
+        assert!(loaded.get_reduce_motion());
+        assert_eq!(loaded.get_volume(), DEFAULT_VOLUME);
+        assert!(!loaded.get_muted());
+        assert!(!loaded.get_pointer_lock());
+        assert_eq!(loaded.get_mouse_sensitivity(), DEFAULT_MOUSE_SENSITIVITY);
+        assert_eq!(loaded.get_key_bindings(), &KeyBindings::default());
+
+        let empty: Settings = serde_json::from_str("{}").unwrap();
+        assert!(!empty.get_reduce_motion());
+    }
+}
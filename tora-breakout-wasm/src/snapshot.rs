@@ -0,0 +1,115 @@
+use crate::ball::Ball;
+use crate::bricks::{BrickKind, BrickStatus};
+use crate::game_status::{GameStatus, Status};
+use crate::paddle::Paddle;
+use crate::Container;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct BallState {
+    pub x: f64,
+    pub y: f64,
+    pub dx: f64,
+    pub dy: f64,
+    pub speed: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct BrickState {
+    pub status: BrickStatus,
+    pub kind: BrickKind,
+}
+
+/// GameHandle.getState()で返すある時点のゲームの状態
+/// TypeScriptの型はlib.rsのGameStateと合わせること
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GameState {
+    pub status: Status,
+    pub score: u32,
+    pub lives: u32,
+    pub stage: u32,
+    pub level: String,
+    pub ball: BallState,
+    pub paddle_x: f64,
+    // bricks[col][row]
+    pub bricks: Vec<Vec<BrickState>>,
+}
+
+impl GameState {
+    pub fn new(status: &GameStatus, ball: &Ball, paddle: &Paddle, bricks: &Container) -> GameState {
+        GameState {
+            status: status.get_status(),
+            score: status.get_score(),
+            lives: status.get_lives(),
+            stage: status.get_stage(),
+            level: bricks.get_level().get_name().to_string(),
+            ball: BallState {
+                x: ball.get_x(),
+                y: ball.get_y(),
+                dx: ball.get_dx(),
+                dy: ball.get_dy(),
+                speed: ball.get_speed(),
+            },
+            paddle_x: paddle.get_x(),
+            bricks: (0..bricks.get_col_len())
+                .map(|col| {
+                    (0..bricks.get_row_len(col))
+                        .map(|row| {
+                            let brick = bricks.get_brick(col, row);
+                            BrickState {
+                                status: brick.get_status(),
+                                kind: brick.get_kind(),
+                            }
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{BRICK_COLUMN_COUNT, BRICK_ROW_COUNT, INIT_SPEED};
+    use crate::level::Level;
+    use serde_json::json;
+
+    /// lib.rsのTypeScriptのGameStateと同じ形になっていること
+    #[test]
+    fn serializes_to_the_shape_declared_for_typescript() {
+        let mut status = GameStatus::new();
+        status.set_status(Status::StageClear);
+        status.set_score(50);
+        status.set_lives(2);
+        let ball = Ball::new(1.0, -2.0, 100.0, 200.0);
+        let paddle = Paddle::new(30.0);
+        let mut bricks = Container::new(String::new());
+        bricks.set_level(Level::for_stage(1));
+        bricks.set_status(0, 5, BrickStatus::Dead);
+
+        let mut value =
+            serde_json::to_value(GameState::new(&status, &ball, &paddle, &bricks)).unwrap();
+        let cells = value.as_object_mut().unwrap().remove("bricks").unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "status": "StageClear",
+                "score": 50,
+                "lives": 2,
+                "stage": status.get_stage(),
+                "level": "flip",
+                "ball": { "x": 100.0, "y": 200.0, "dx": 1.0, "dy": -2.0, "speed": INIT_SPEED },
+                "paddleX": 30.0,
+            })
+        );
+
+        let columns = cells.as_array().unwrap();
+        assert_eq!(columns.len(), BRICK_COLUMN_COUNT);
+        assert!(columns
+            .iter()
+            .all(|c| c.as_array().unwrap().len() == BRICK_ROW_COUNT));
+        assert_eq!(cells[0][0], json!({ "status": "Live", "kind": "Hard" }));
+        assert_eq!(cells[0][5], json!({ "status": "Dead", "kind": "Normal" }));
+    }
+}
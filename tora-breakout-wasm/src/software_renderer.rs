@@ -0,0 +1,393 @@
+use crate::renderer::{parse_color, parse_font, Renderer, TextAlign};
+
+/// RGBA8(ストレートアルファ)の画像 SoftwareRendererの描画先と画像の両方に使う
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// 透明で初期化する
+    pub fn new(width: u32, height: u32) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    /// pixelsはwidth * height * 4バイト 足りない場合はNone
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Option<RgbaImage> {
+        if pixels.len() != (width * height * 4) as usize {
+            return None;
+        }
+        Some(RgbaImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        let i = ((y * self.width + x) * 4) as usize;
+        self.pixels[i..i + 4].copy_from_slice(&rgba);
+    }
+
+    /// source-overで色を重ねる alphaは0.0..1.0
+    fn blend(&mut self, x: i64, y: i64, rgb: [u8; 3], alpha: f64) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || alpha <= 0.0 {
+            return;
+        }
+        let dst = self.get_pixel(x as u32, y as u32);
+        let sa = alpha.min(1.0);
+        let da = dst[3] as f64 / 255.0;
+        let out_a = sa + da * (1.0 - sa);
+        let mut out = [0u8; 4];
+        for i in 0..3 {
+            let c = (rgb[i] as f64 * sa + dst[i] as f64 * da * (1.0 - sa)) / out_a;
+            out[i] = c.round() as u8;
+        }
+        out[3] = (out_a * 255.0).round() as u8;
+        self.set_pixel(x as u32, y as u32, out);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct State {
+    scale_x: f64,
+    scale_y: f64,
+    translate_x: f64,
+    translate_y: f64,
+    alpha: f64,
+    // 描画できるデバイス座標の範囲(x0, y0, x1, y1) ピクセル中心が入っているところだけ描く
+    clip: Option<(f64, f64, f64, f64)>,
+}
+
+impl State {
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            x * self.scale_x + self.translate_x,
+            y * self.scale_y + self.translate_y,
+        )
+    }
+}
+
+/// CPUでRgbaImageに描画するRenderer
+/// Canvas2Dと完全に同じ絵にはならないが、ブラウザなしで描画の変化を検出するのには十分
+pub struct SoftwareRenderer {
+    target: RgbaImage,
+    state: State,
+    stack: Vec<State>,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32) -> SoftwareRenderer {
+        SoftwareRenderer {
+            target: RgbaImage::new(width, height),
+            state: State {
+                scale_x: 1.0,
+                scale_y: 1.0,
+                translate_x: 0.0,
+                translate_y: 0.0,
+                alpha: 1.0,
+                clip: None,
+            },
+            stack: Vec::new(),
+        }
+    }
+
+    pub fn get_image(&self) -> &RgbaImage {
+        &self.target
+    }
+
+    pub fn into_image(self) -> RgbaImage {
+        self.target
+    }
+
+    /// 変形済みの矩形を返す 負のスケールでも左上/右下の順にする
+    fn device_rect(&self, x: f64, y: f64, width: f64, height: f64) -> (f64, f64, f64, f64) {
+        let (x0, y0) = self.state.apply(x, y);
+        let (x1, y1) = self.state.apply(x + width, y + height);
+        (x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1))
+    }
+
+    fn is_clipped(&self, px: i64, py: i64) -> bool {
+        match self.state.clip {
+            Some((x0, y0, x1, y1)) => {
+                let (cx, cy) = (px as f64 + 0.5, py as f64 + 0.5);
+                cx < x0 || cx >= x1 || cy < y0 || cy >= y1
+            }
+            None => false,
+        }
+    }
+
+    fn plot(&mut self, px: i64, py: i64, rgb: [u8; 3], alpha: f64) {
+        if !self.is_clipped(px, py) {
+            self.target.blend(px, py, rgb, alpha);
+        }
+    }
+
+    /// ピクセルと矩形の重なった面積でアンチエイリアスして塗る
+    fn fill_device_rect(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, rgb: [u8; 3], alpha: f64) {
+        let alpha = alpha * self.state.alpha;
+        for py in y0.floor() as i64..y1.ceil() as i64 {
+            let cover_y = (y1.min(py as f64 + 1.0) - y0.max(py as f64)).max(0.0);
+            for px in x0.floor() as i64..x1.ceil() as i64 {
+                let cover_x = (x1.min(px as f64 + 1.0) - x0.max(px as f64)).max(0.0);
+                self.plot(px, py, rgb, alpha * cover_x * cover_y);
+            }
+        }
+    }
+
+    fn fill_user_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: &str) {
+        let (rgb, alpha) = parse_color(color);
+        let (x0, y0, x1, y1) = self.device_rect(x, y, width, height);
+        self.fill_device_rect(x0, y0, x1, y1, rgb, alpha);
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    type Image = RgbaImage;
+
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        let (x0, y0, x1, y1) = self.device_rect(x, y, width, height);
+        let w = self.target.width as i64;
+        let h = self.target.height as i64;
+        for py in (y0.round() as i64).max(0)..(y1.round() as i64).min(h) {
+            for px in (x0.round() as i64).max(0)..(x1.round() as i64).min(w) {
+                if !self.is_clipped(px, py) {
+                    self.target.set_pixel(px as u32, py as u32, [0, 0, 0, 0]);
+                }
+            }
+        }
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: &str) {
+        self.fill_user_rect(x, y, width, height, color);
+    }
+
+    fn stroke_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: &str,
+        line_width: f64,
+    ) {
+        // Canvas2Dと同じく線は辺の中心に乗る
+        let half = line_width / 2.0;
+        self.fill_user_rect(x - half, y - half, width + line_width, line_width, color);
+        self.fill_user_rect(
+            x - half,
+            y + height - half,
+            width + line_width,
+            line_width,
+            color,
+        );
+        self.fill_user_rect(x - half, y + half, line_width, height - line_width, color);
+        self.fill_user_rect(
+            x + width - half,
+            y + half,
+            line_width,
+            height - line_width,
+            color,
+        );
+    }
+
+    fn fill_circle(&mut self, x: f64, y: f64, radius: f64, color: &str) {
+        let (rgb, alpha) = parse_color(color);
+        let alpha = alpha * self.state.alpha;
+        let (cx, cy) = self.state.apply(x, y);
+        let rx = radius * self.state.scale_x.abs();
+        let ry = radius * self.state.scale_y.abs();
+        // 4x4のスーパーサンプリングで覆っている割合を求める
+        const SAMPLES: i64 = 4;
+        for py in (cy - ry).floor() as i64..(cy + ry).ceil() as i64 {
+            for px in (cx - rx).floor() as i64..(cx + rx).ceil() as i64 {
+                let mut inside = 0;
+                for sy in 0..SAMPLES {
+                    for sx in 0..SAMPLES {
+                        let dx = (px as f64 + (sx as f64 + 0.5) / SAMPLES as f64 - cx) / rx;
+                        let dy = (py as f64 + (sy as f64 + 0.5) / SAMPLES as f64 - cy) / ry;
+                        if dx * dx + dy * dy <= 1.0 {
+                            inside += 1;
+                        }
+                    }
+                }
+                let coverage = inside as f64 / (SAMPLES * SAMPLES) as f64;
+                self.plot(px, py, rgb, alpha * coverage);
+            }
+        }
+    }
+
+    fn draw_image(&mut self, image: &RgbaImage, x: f64, y: f64, width: f64, height: f64) {
+        if image.width == 0 || image.height == 0 {
+            return;
+        }
+        let (x0, y0, x1, y1) = self.device_rect(x, y, width, height);
+        // ピクセル中心が入っているところだけ最近傍でサンプリングする
+        for py in (y0 - 0.5).ceil() as i64..(y1 - 0.5).ceil() as i64 {
+            let v = ((py as f64 + 0.5 - y0) / (y1 - y0) * image.height as f64) as u32;
+            for px in (x0 - 0.5).ceil() as i64..(x1 - 0.5).ceil() as i64 {
+                let u = ((px as f64 + 0.5 - x0) / (x1 - x0) * image.width as f64) as u32;
+                let src = image.get_pixel(u.min(image.width - 1), v.min(image.height - 1));
+                let alpha = src[3] as f64 / 255.0 * self.state.alpha;
+                self.plot(px, py, [src[0], src[1], src[2]], alpha);
+            }
+        }
+    }
+
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, font: &str, color: &str, align: TextAlign) {
+        // 文字は内蔵の5x7ドットフォントで描く 1ドットはフォントサイズの1/12
+        let dot = parse_font(font).size / 12.0;
+        let advance = GLYPH_ADVANCE * dot;
+        let text_width = text.chars().count() as f64 * advance;
+        let left = match align {
+            TextAlign::Left => x,
+            TextAlign::Center => x - text_width / 2.0,
+            TextAlign::Right => x - text_width,
+        };
+        // yはベースライン
+        let top = y - GLYPH_HEIGHT as f64 * dot;
+        for (i, c) in text.chars().enumerate() {
+            let rows = match glyph(c) {
+                Some(rows) => rows,
+                None => continue,
+            };
+            let origin_x = left + i as f64 * advance;
+            for (row, bits) in rows.iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                        self.fill_user_rect(
+                            origin_x + col as f64 * dot,
+                            top + row as f64 * dot,
+                            dot,
+                            dot,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn clip_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        let (x0, y0, x1, y1) = self.device_rect(x, y, width, height);
+        self.state.clip = Some(match self.state.clip {
+            Some((cx0, cy0, cx1, cy1)) => (x0.max(cx0), y0.max(cy0), x1.min(cx1), y1.min(cy1)),
+            None => (x0, y0, x1, y1),
+        });
+    }
+
+    fn save(&mut self) {
+        self.stack.push(self.state);
+    }
+
+    fn restore(&mut self) {
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        }
+    }
+
+    fn translate(&mut self, x: f64, y: f64) {
+        self.state.translate_x += x * self.state.scale_x;
+        self.state.translate_y += y * self.state.scale_y;
+    }
+
+    fn scale(&mut self, x: f64, y: f64) {
+        self.state.scale_x *= x;
+        self.state.scale_y *= y;
+    }
+
+    fn set_global_alpha(&mut self, alpha: f64) {
+        self.state.alpha = alpha.clamp(0.0, 1.0);
+    }
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+// 1文字の送り幅(ドット) 文字の間に1ドット空ける
+const GLYPH_ADVANCE: f64 = 6.0;
+
+/// 5x7ドットの字形 各行の下位5bitが左から右 小文字は大文字で描く
+fn glyph(c: char) -> Option<[u8; 7]> {
+    let rows = match c.to_ascii_uppercase() {
+        'A' => [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1e],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0a, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '?' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+        '+' => [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '=' => [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00],
+        '*' => [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => return None,
+    };
+    Some(rows)
+}
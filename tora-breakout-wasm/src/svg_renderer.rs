@@ -0,0 +1,282 @@
+use crate::renderer::{parse_color, parse_font, Renderer, TextAlign};
+
+#[derive(Debug, Clone, Copy)]
+struct State {
+    scale_x: f64,
+    scale_y: f64,
+    translate_x: f64,
+    translate_y: f64,
+    alpha: f64,
+    // 今のクリップのclipPathの番号
+    clip: Option<usize>,
+}
+
+impl State {
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            x * self.scale_x + self.translate_x,
+            y * self.scale_y + self.translate_y,
+        )
+    }
+}
+
+/// SVG文書を組み立てるRenderer 画像はhref(URL)で指定する
+/// 変形は要素ごとに座標へ適用済みで出力するので、出力はテキストとして差分を取りやすい
+pub struct SvgRenderer {
+    width: f64,
+    height: f64,
+    elements: Vec<String>,
+    state: State,
+    stack: Vec<State>,
+    clip_count: usize,
+}
+
+impl SvgRenderer {
+    pub fn new(width: f64, height: f64) -> SvgRenderer {
+        SvgRenderer {
+            width,
+            height,
+            elements: Vec::new(),
+            state: State {
+                scale_x: 1.0,
+                scale_y: 1.0,
+                translate_x: 0.0,
+                translate_y: 0.0,
+                alpha: 1.0,
+                clip: None,
+            },
+            stack: Vec::new(),
+            clip_count: 0,
+        }
+    }
+
+    /// ここまでの描画をSVG文書にする 1要素1行
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+            w = num(self.width),
+            h = num(self.height)
+        );
+        for element in self.elements.iter() {
+            svg.push_str("  ");
+            svg.push_str(element);
+            svg.push('\n');
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn device_rect(&self, x: f64, y: f64, width: f64, height: f64) -> (f64, f64, f64, f64) {
+        let (x0, y0) = self.state.apply(x, y);
+        let (x1, y1) = self.state.apply(x + width, y + height);
+        (x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs())
+    }
+
+    /// クリップ中ならタグ名の直後にclip-path属性を足して追加する
+    fn push(&mut self, element: String) {
+        match (self.state.clip, element.find(' ')) {
+            (Some(id), Some(i)) => self.elements.push(format!(
+                "{} clip-path=\"url(#clip{})\"{}",
+                &element[..i],
+                id,
+                &element[i..]
+            )),
+            _ => self.elements.push(element),
+        }
+    }
+
+    /// fill/strokeの色と不透明度の属性 SVG1.1はrgba()を受け付けないので分ける
+    fn paint(&self, attr: &str, color: &str) -> String {
+        let (rgb, alpha) = parse_color(color);
+        let opacity = alpha * self.state.alpha;
+        let mut paint = format!("{}=\"rgb({},{},{})\"", attr, rgb[0], rgb[1], rgb[2]);
+        if opacity < 1.0 {
+            paint.push_str(&format!(" {}-opacity=\"{}\"", attr, num(opacity)));
+        }
+        paint
+    }
+}
+
+impl Renderer for SvgRenderer {
+    type Image = String;
+
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        // SVGでは消せないので、全面を消す場合だけそれまでの要素を捨てる
+        let (x, y, width, height) = self.device_rect(x, y, width, height);
+        if self.state.clip.is_none()
+            && x <= 0.0
+            && y <= 0.0
+            && x + width >= self.width
+            && y + height >= self.height
+        {
+            self.elements.clear();
+        }
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: &str) {
+        let (x, y, width, height) = self.device_rect(x, y, width, height);
+        let paint = self.paint("fill", color);
+        self.push(format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+            num(x),
+            num(y),
+            num(width),
+            num(height),
+            paint
+        ));
+    }
+
+    fn stroke_rect(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: &str,
+        line_width: f64,
+    ) {
+        let (x, y, width, height) = self.device_rect(x, y, width, height);
+        let paint = self.paint("stroke", color);
+        self.push(format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" {} stroke-width=\"{}\"/>",
+            num(x),
+            num(y),
+            num(width),
+            num(height),
+            paint,
+            num(line_width * self.state.scale_x.abs())
+        ));
+    }
+
+    fn fill_circle(&mut self, x: f64, y: f64, radius: f64, color: &str) {
+        let (cx, cy) = self.state.apply(x, y);
+        let rx = radius * self.state.scale_x.abs();
+        let ry = radius * self.state.scale_y.abs();
+        let paint = self.paint("fill", color);
+        if rx == ry {
+            self.push(format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {}/>",
+                num(cx),
+                num(cy),
+                num(rx),
+                paint
+            ));
+        } else {
+            self.push(format!(
+                "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" {}/>",
+                num(cx),
+                num(cy),
+                num(rx),
+                num(ry),
+                paint
+            ));
+        }
+    }
+
+    fn draw_image(&mut self, image: &String, x: f64, y: f64, width: f64, height: f64) {
+        let (x, y, width, height) = self.device_rect(x, y, width, height);
+        let mut element = format!(
+            "<image href=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"",
+            escape(image),
+            num(x),
+            num(y),
+            num(width),
+            num(height)
+        );
+        if self.state.alpha < 1.0 {
+            element.push_str(&format!(" opacity=\"{}\"", num(self.state.alpha)));
+        }
+        element.push_str("/>");
+        self.push(element);
+    }
+
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, font: &str, color: &str, align: TextAlign) {
+        let (x, y) = self.state.apply(x, y);
+        let font = parse_font(font);
+        let anchor = match align {
+            TextAlign::Left => "start",
+            TextAlign::Center => "middle",
+            TextAlign::Right => "end",
+        };
+        let weight = if font.bold {
+            " font-weight=\"bold\""
+        } else {
+            ""
+        };
+        let paint = self.paint("fill", color);
+        self.push(format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\"{} text-anchor=\"{}\" {}>{}</text>",
+            num(x),
+            num(y),
+            escape(&font.family),
+            num(font.size * self.state.scale_y.abs()),
+            weight,
+            anchor,
+            paint,
+            escape(text)
+        ));
+    }
+
+    fn clip_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        let (x, y, width, height) = self.device_rect(x, y, width, height);
+        let id = self.clip_count;
+        self.clip_count += 1;
+        // 重ねてクリップした場合は前のクリップをclipPathの中の矩形に掛ける
+        let parent = match self.state.clip {
+            Some(parent) => format!(" clip-path=\"url(#clip{})\"", parent),
+            None => String::new(),
+        };
+        self.elements.push(format!(
+            "<clipPath id=\"clip{}\"><rect{} x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath>",
+            id,
+            parent,
+            num(x),
+            num(y),
+            num(width),
+            num(height)
+        ));
+        self.state.clip = Some(id);
+    }
+
+    fn save(&mut self) {
+        self.stack.push(self.state);
+    }
+
+    fn restore(&mut self) {
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        }
+    }
+
+    fn translate(&mut self, x: f64, y: f64) {
+        self.state.translate_x += x * self.state.scale_x;
+        self.state.translate_y += y * self.state.scale_y;
+    }
+
+    fn scale(&mut self, x: f64, y: f64) {
+        self.state.scale_x *= x;
+        self.state.scale_y *= y;
+    }
+
+    fn set_global_alpha(&mut self, alpha: f64) {
+        self.state.alpha = alpha.clamp(0.0, 1.0);
+    }
+}
+
+/// 小数点以下3桁までにして余計な0を落とす 浮動小数点の誤差で差分が出ないようにする
+fn num(v: f64) -> String {
+    let s = format!("{:.3}", v);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,151 @@
+/// 補間のかかり方 tは0.0..1.0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseInQuad,
+    EaseOutQuad,
+    EaseInOutQuad,
+    EaseInOutCubic,
+}
+
+impl Easing {
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInQuad => t * t,
+            Easing::EaseOutQuad => t * (2.0 - t),
+            Easing::EaseInOutQuad => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + (4.0 - 2.0 * t) * t
+                }
+            }
+            Easing::EaseInOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    let u = -2.0 * t + 2.0;
+                    1.0 - u * u * u / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// fromからtoまでをフレーム数で補間する
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tween {
+    from: f64,
+    to: f64,
+    duration: u32,
+    elapsed: u32,
+    easing: Easing,
+}
+
+impl Tween {
+    pub fn new(from: f64, to: f64, duration: u32, easing: Easing) -> Tween {
+        Tween {
+            from,
+            to,
+            duration,
+            elapsed: 0,
+            easing,
+        }
+    }
+
+    /// 1フレーム進める
+    pub fn update(&mut self) {
+        if self.elapsed < self.duration {
+            self.elapsed += 1;
+        }
+    }
+
+    pub fn get_value(&self) -> f64 {
+        if self.duration == 0 {
+            return self.to;
+        }
+        let t = self.elapsed as f64 / self.duration as f64;
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 5] = [
+        Easing::Linear,
+        Easing::EaseInQuad,
+        Easing::EaseOutQuad,
+        Easing::EaseInOutQuad,
+        Easing::EaseInOutCubic,
+    ];
+
+    #[test]
+    fn easings_start_at_zero_and_end_at_one() {
+        for easing in EASINGS.iter() {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-12, "{:?}", easing);
+            // 範囲外は端に丸める
+            assert_eq!(easing.apply(-0.5), 0.0, "{:?}", easing);
+            assert!((easing.apply(1.5) - 1.0).abs() < 1e-12, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn easings_never_go_backwards() {
+        for easing in EASINGS.iter() {
+            let mut previous = easing.apply(0.0);
+            for i in 1..=1000 {
+                let value = easing.apply(i as f64 / 1000.0);
+                assert!(value >= previous, "{:?} at {}", easing, i);
+                assert!(value <= 1.0 + 1e-12, "{:?} at {}", easing, i);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn in_out_easings_pass_the_middle_at_half() {
+        for easing in [Easing::EaseInOutQuad, Easing::EaseInOutCubic].iter() {
+            assert!((easing.apply(0.5) - 0.5).abs() < 1e-12, "{:?}", easing);
+            // 前半と後半で対称
+            let a = easing.apply(0.2);
+            let b = easing.apply(0.8);
+            assert!((a + b - 1.0).abs() < 1e-12, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn ease_in_is_slow_first_and_ease_out_is_fast_first() {
+        assert!(Easing::EaseInQuad.apply(0.25) < 0.25);
+        assert!(Easing::EaseOutQuad.apply(0.25) > 0.25);
+    }
+
+    #[test]
+    fn tween_moves_from_start_to_end_over_its_duration() {
+        let mut tween = Tween::new(10.0, 0.0, 4, Easing::Linear);
+        assert_eq!(tween.get_value(), 10.0);
+        tween.update();
+        assert_eq!(tween.get_value(), 7.5);
+        assert!(!tween.is_finished());
+        for _ in 0..10 {
+            tween.update();
+        }
+        assert!(tween.is_finished());
+        assert_eq!(tween.get_value(), 0.0);
+    }
+
+    #[test]
+    fn zero_length_tween_is_finished_at_the_end() {
+        let tween = Tween::new(0.0, 1.0, 0, Easing::EaseInOutCubic);
+        assert!(tween.is_finished());
+        assert_eq!(tween.get_value(), 1.0);
+    }
+}
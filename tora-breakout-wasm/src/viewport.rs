@@ -0,0 +1,223 @@
+use wasm_bindgen::JsValue;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+/// 論理サイズ(FIELD_WIDTH x FIELD_HEIGHT)のプレイフィールドを画面に合わせて表示する
+/// 縦横比を保ったまま親要素に収まる一番大きいサイズにして余白は黒帯にし、
+/// 描画バッファはdevicePixelRatio倍にしてRetinaでもぼやけないようにする
+pub struct Viewport {
+    canvas: HtmlCanvasElement,
+    width: f64,
+    height: f64,
+    // 論理1ピクセルあたりのCSSピクセル
+    scale: f64,
+    // 論理1ピクセルあたりの描画バッファのピクセル
+    pixel_ratio: f64,
+    // fitしてからまだtake_resizedされていない
+    resized: bool,
+}
+
+/// 親要素の大きさとdevicePixelRatioから決まるキャンバスの置き方
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Layout {
+    // 論理1ピクセルあたりのCSSピクセル
+    scale: f64,
+    css_width: f64,
+    css_height: f64,
+    // 親要素の中での位置 余った分を左右・上下に等分する
+    left: f64,
+    top: f64,
+    buffer_width: u32,
+    buffer_height: u32,
+    // 論理1ピクセルあたりの描画バッファのピクセル
+    pixel_ratio: f64,
+}
+
+impl Layout {
+    fn new(
+        width: f64,
+        height: f64,
+        available_width: f64,
+        available_height: f64,
+        device_pixel_ratio: f64,
+    ) -> Layout {
+        let scale = (available_width / width).min(available_height / height);
+        let css_width = (width * scale).floor();
+        let css_height = (height * scale).floor();
+        let device_pixel_ratio = device_pixel_ratio.max(1.0);
+        let buffer_width = (css_width * device_pixel_ratio).round() as u32;
+        let buffer_height = (css_height * device_pixel_ratio).round() as u32;
+        Layout {
+            scale,
+            css_width,
+            css_height,
+            left: ((available_width - css_width) / 2.0).floor(),
+            top: ((available_height - css_height) / 2.0).floor(),
+            buffer_width,
+            buffer_height,
+            pixel_ratio: buffer_width as f64 / width,
+        }
+    }
+}
+
+/// クライアント座標をキャンバスの左上(origin)からの論理座標にする
+fn to_logical(client: f64, origin: f64, scale: f64) -> f64 {
+    (client - origin) / scale
+}
+
+impl Viewport {
+    pub fn new(canvas: HtmlCanvasElement, width: u32, height: u32) -> Viewport {
+        let mut viewport = Viewport {
+            canvas,
+            width: width as f64,
+            height: height as f64,
+            scale: 1.0,
+            pixel_ratio: 1.0,
+            resized: false,
+        };
+        viewport.fit();
+        viewport
+    }
+
+    /// 今の親要素の大きさとdevicePixelRatioに合わせ直す resizeのたびに呼ぶ
+    pub fn fit(&mut self) {
+        let window = web_sys::window().unwrap();
+        let (mut available_width, mut available_height) = match self.canvas.parent_element() {
+            Some(parent) => (parent.client_width() as f64, parent.client_height() as f64),
+            None => (0.0, 0.0),
+        };
+        if available_width <= 0.0 || available_height <= 0.0 {
+            available_width = window
+                .inner_width()
+                .ok()
+                .and_then(|v| v.as_f64())
+                .unwrap_or(self.width);
+            available_height = window
+                .inner_height()
+                .ok()
+                .and_then(|v| v.as_f64())
+                .unwrap_or(self.height);
+        }
+        let layout = Layout::new(
+            self.width,
+            self.height,
+            available_width,
+            available_height,
+            window.device_pixel_ratio(),
+        );
+        self.scale = layout.scale;
+        // widthを変えるとコンテキストの状態も消えるので、変換はapplyで毎フレームかけ直す
+        self.canvas.set_width(layout.buffer_width);
+        self.canvas.set_height(layout.buffer_height);
+        self.pixel_ratio = layout.pixel_ratio;
+
+        let style = self.canvas.style();
+        let _ = style.set_property("width", &format!("{}px", layout.css_width));
+        let _ = style.set_property("height", &format!("{}px", layout.css_height));
+        let _ = style.set_property("left", &format!("{}px", layout.left));
+        let _ = style.set_property("top", &format!("{}px", layout.top));
+
+        let rect = self.canvas.get_bounding_client_rect();
+        if rect.width() > 0.0 {
+            self.scale = rect.width() / self.width;
+        }
+        self.resized = true;
+    }
+
+    pub fn get_pixel_ratio(&self) -> f64 {
+        self.pixel_ratio
+    }
+
+    /// 大きさが変わっていたらtrue オフスクリーンのキャンバスを作り直すのに使う
+    pub fn take_resized(&mut self) -> bool {
+        std::mem::replace(&mut self.resized, false)
+    }
+
+    /// 論理座標で描けるように描画バッファの倍率をかける
+    pub fn apply(&self, ctx: &CanvasRenderingContext2d) -> Result<(), JsValue> {
+        ctx.set_transform(self.pixel_ratio, 0.0, 0.0, self.pixel_ratio, 0.0, 0.0)
+    }
+
+    /// CSSピクセルの移動量を論理ピクセルにする
+    pub fn to_logical_distance(&self, distance: f64) -> f64 {
+        distance / self.scale
+    }
+
+    /// クライアント座標のxを論理座標にする
+    /// キャンバスの位置はスクロールやページのレイアウトで変わるので毎回取り直す
+    pub fn to_logical_x(&self, client_x: i32) -> f64 {
+        let rect = self.canvas.get_bounding_client_rect();
+        to_logical(client_x as f64, rect.left(), self.scale)
+    }
+
+    /// クライアント座標のyを論理座標にする
+    pub fn to_logical_y(&self, client_y: i32) -> f64 {
+        let rect = self.canvas.get_bounding_client_rect();
+        to_logical(client_y as f64, rect.top(), self.scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: f64 = 480.0;
+    const HEIGHT: f64 = 720.0;
+
+    #[test]
+    fn wide_screen_gets_bars_on_the_left_and_right() {
+        let layout = Layout::new(WIDTH, HEIGHT, 1000.0, 720.0, 1.0);
+        assert_eq!(layout.scale, 1.0);
+        assert_eq!((layout.css_width, layout.css_height), (480.0, 720.0));
+        assert_eq!((layout.left, layout.top), (260.0, 0.0));
+    }
+
+    #[test]
+    fn tall_screen_gets_bars_on_the_top_and_bottom() {
+        let layout = Layout::new(WIDTH, HEIGHT, 240.0, 600.0, 1.0);
+        assert_eq!(layout.scale, 0.5);
+        assert_eq!((layout.css_width, layout.css_height), (240.0, 360.0));
+        assert_eq!((layout.left, layout.top), (0.0, 120.0));
+    }
+
+    #[test]
+    fn buffer_follows_the_device_pixel_ratio() {
+        let layout = Layout::new(WIDTH, HEIGHT, 480.0, 720.0, 1.0);
+        assert_eq!((layout.buffer_width, layout.buffer_height), (480, 720));
+        assert_eq!(layout.pixel_ratio, 1.0);
+
+        let layout = Layout::new(WIDTH, HEIGHT, 480.0, 720.0, 2.0);
+        assert_eq!((layout.css_width, layout.css_height), (480.0, 720.0));
+        assert_eq!((layout.buffer_width, layout.buffer_height), (960, 1440));
+        assert_eq!(layout.pixel_ratio, 2.0);
+
+        // 1未満のときも描画バッファは減らさない
+        let layout = Layout::new(WIDTH, HEIGHT, 480.0, 720.0, 0.5);
+        assert_eq!(layout.pixel_ratio, 1.0);
+    }
+
+    #[test]
+    fn pixel_ratio_includes_the_scale() {
+        let layout = Layout::new(WIDTH, HEIGHT, 240.0, 360.0, 2.0);
+        assert_eq!((layout.buffer_width, layout.buffer_height), (480, 720));
+        assert_eq!(layout.pixel_ratio, 1.0);
+    }
+
+    #[test]
+    fn client_position_maps_into_the_letterboxed_field() {
+        let layout = Layout::new(WIDTH, HEIGHT, 1000.0, 1440.0, 2.0);
+        assert_eq!(layout.scale, 2.0);
+        let left = layout.left;
+        assert_eq!(to_logical(left, left, layout.scale), 0.0);
+        assert_eq!(
+            to_logical(left + layout.css_width, left, layout.scale),
+            WIDTH
+        );
+        assert_eq!(to_logical(left + 100.0, left, layout.scale), 50.0);
+        assert_eq!(
+            to_logical(layout.css_height, layout.top, layout.scale),
+            HEIGHT
+        );
+        // 黒帯の中は範囲外になる
+        assert!(to_logical(0.0, left, layout.scale) < 0.0);
+    }
+}
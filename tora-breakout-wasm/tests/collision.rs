@@ -0,0 +1,47 @@
+//! ブロックに当たったときのボールの速さとイベントを確認する
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use tora_breakout_wasm::ball::Ball;
+use tora_breakout_wasm::bitmap_container::BitmapContainer;
+use tora_breakout_wasm::bricks::{BrickKind, BrickStatus};
+use tora_breakout_wasm::events::GameEvent;
+use tora_breakout_wasm::game_status::GameStatus;
+use tora_breakout_wasm::level::{Level, RevealEffect};
+use tora_breakout_wasm::Container;
+
+/// 左上のマスだけkindで、ボールをその真ん中に置いて1回当たり判定をする
+fn hit(kind: BrickKind) -> (Container, Ball, Vec<GameEvent>) {
+    let mut level = Level::new("test", "27_devil", "26_angel", RevealEffect::Flip);
+    level.set_cell(0, 0, Some(kind));
+    let mut bricks = Container::new(String::new());
+    bricks.set_level(level);
+    let brick = bricks.get_brick(0, 0);
+    let mut ball = Ball::new(2.0, -2.0, brick.get_x() + 10.0, brick.get_y() + 8.0);
+    ball.init_speed();
+    let images = Rc::new(RefCell::new(BitmapContainer::new()));
+    let mut events = Vec::new();
+    bricks.collision_detection(&mut GameStatus::new(), &mut ball, &images, &mut events);
+    (bricks, ball, events)
+}
+
+#[test]
+fn breakable_brick_speeds_up_the_ball_and_counts_as_a_hit() {
+    let (bricks, ball, events) = hit(BrickKind::Hard);
+    let mut fresh = Ball::new(0.0, 0.0, 0.0, 0.0);
+    fresh.init_speed();
+    assert_eq!(ball.get_speed(), fresh.get_speed() + 1);
+    assert_eq!(events, vec![GameEvent::BrickHit { col: 0, row: 0 }]);
+    assert_eq!(bricks.get_status(0, 0), BrickStatus::Live);
+}
+
+#[test]
+fn unbreakable_brick_only_bounces_the_ball() {
+    let (bricks, ball, events) = hit(BrickKind::Unbreakable);
+    let mut fresh = Ball::new(0.0, 0.0, 0.0, 0.0);
+    fresh.init_speed();
+    assert_eq!(ball.get_speed(), fresh.get_speed());
+    assert!(events.is_empty());
+    assert_eq!(bricks.get_status(0, 0), BrickStatus::Live);
+    assert!(ball.get_dx() != 2.0 || ball.get_dy() != -2.0);
+}
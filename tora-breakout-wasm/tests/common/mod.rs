@@ -0,0 +1,156 @@
+//! 描画テストで共通に使う盤面
+// テストのバイナリごとに使うものが違うので、使われないものがあっても警告しない
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use tora_breakout_wasm::ball::Ball;
+use tora_breakout_wasm::bitmap_container::BitmapContainer;
+use tora_breakout_wasm::bricks::{BrickKind, BrickStatus};
+use tora_breakout_wasm::consts::{
+    BALL_RADIUS, BRICK_COLUMN_COUNT, BRICK_ROW_COUNT, PADDLE_WIDTH, SPEED,
+};
+use tora_breakout_wasm::events::GameEvent;
+use tora_breakout_wasm::game_status::{GameStatus, Status};
+use tora_breakout_wasm::level::{Level, RevealEffect};
+use tora_breakout_wasm::paddle::Paddle;
+use tora_breakout_wasm::renderer::Renderer;
+use tora_breakout_wasm::scoring::Scoring;
+use tora_breakout_wasm::{draw_frame, Container};
+use web_sys::ImageBitmap;
+
+pub const WIDTH: u32 = 480;
+pub const HEIGHT: u32 = 720;
+
+pub struct World<I> {
+    pub bricks: Container,
+    pub images: BitmapContainer<I>,
+    pub ball: Ball,
+    pub paddle: Paddle,
+    pub status: GameStatus,
+    pub scoring: Scoring,
+}
+
+/// tileは(列, 行, 壊れているか)からそのセルの画像を作る
+pub fn initial_world<I>(tile: impl Fn(usize, usize, bool) -> I) -> World<I> {
+    let mut images = BitmapContainer::new();
+    for c in 0..BRICK_COLUMN_COUNT {
+        for r in 0..BRICK_ROW_COUNT {
+            images.insert(c, r, tile(c, r, false));
+        }
+    }
+    // ゲームと同じく最初のステージを置く 上の2行が硬いブロック
+    let mut bricks = Container::new(String::new());
+    bricks.set_level(Level::default());
+    World {
+        bricks,
+        images,
+        ball: Ball::new(
+            2.0 * SPEED,
+            -2.0 * SPEED,
+            WIDTH as f64 / 2.0,
+            HEIGHT as f64 - BALL_RADIUS * 2.0,
+        ),
+        paddle: Paddle::new((WIDTH as f64 - PADDLE_WIDTH) / 2.0),
+        status: GameStatus::new(),
+        scoring: Scoring::new(),
+    }
+}
+
+/// 何個かブロックを壊してコンボ中の盤面
+pub fn mid_game_world<I>(tile: impl Fn(usize, usize, bool) -> I) -> World<I> {
+    let mut world = initial_world(&tile);
+    let broken = [(0, 23), (1, 23), (2, 22), (3, 23), (4, 21), (5, 23), (9, 0)];
+    for &(c, r) in broken.iter() {
+        world.bricks.set_status(c, r, BrickStatus::Dead);
+        world.images.insert(c, r, tile(c, r, true));
+    }
+    // 硬いブロックに1回当てて傷をつける
+    let brick = world.bricks.get_brick(6, 1);
+    let mut ball = Ball::new(2.0, -2.0, brick.get_x() + 10.0, brick.get_y() + 8.0);
+    let images = Rc::new(RefCell::new(BitmapContainer::<ImageBitmap>::new()));
+    world
+        .bricks
+        .collision_detection(&mut world.status, &mut ball, &images, &mut Vec::new());
+    for _ in 0..5 {
+        world.scoring.handle(
+            &GameEvent::BrickHit { col: 4, row: 21 },
+            &mut world.status,
+            0.0,
+        );
+    }
+    world.scoring.handle(
+        &GameEvent::BrickBroken {
+            col: 4,
+            row: 21,
+            x: 216.0,
+            y: 480.0,
+            kind: BrickKind::Normal,
+        },
+        &mut world.status,
+        0.0,
+    );
+    world.status.set_status(Status::Start);
+    world.status.set_lives(2);
+    // 速くなったボールが左下から右上へ進んでいる
+    for _ in 0..30 {
+        world.ball.add_speed();
+    }
+    for i in (1..=8).rev() {
+        world.ball.set_x(230.0 - 6.0 * i as f64);
+        world.ball.set_y(560.0 + 6.0 * i as f64);
+        world.ball.record_trail();
+    }
+    world.ball.set_x(230.0);
+    world.ball.set_y(560.0);
+    world.paddle.set_x(120.0);
+    world
+}
+
+/// 壊したブロックが裏の絵へ切り替わる途中の盤面
+pub fn revealing_world<I>(
+    tile: impl Fn(usize, usize, bool) -> I,
+    effect: RevealEffect,
+    frames: u32,
+) -> World<I> {
+    let mut world = initial_world(&tile);
+    world
+        .bricks
+        .set_level(Level::new("test", "27_devil", "26_angel", effect));
+    for &(c, r) in [(3, 23), (4, 23), (5, 22)].iter() {
+        world.bricks.set_status(c, r, BrickStatus::Dead);
+        world.images.replace(c, r, tile(c, r, true));
+    }
+    world.bricks.start_reveals(world.images.take_replaced());
+    for _ in 0..frames {
+        world.bricks.update_reveals();
+    }
+    world
+}
+
+pub fn render<R: Renderer>(world: &World<R::Image>, renderer: &mut R) {
+    draw_frame(
+        renderer,
+        &world.bricks,
+        &world.images,
+        &world.ball,
+        &world.paddle,
+        &world.status,
+        &world.scoring,
+        WIDTH as f64,
+        HEIGHT as f64,
+    );
+}
+
+/// tests/golden/以下のファイル
+pub fn golden_path(file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(file_name)
+}
+
+/// UPDATE_GOLDEN=1のときは比較せずに期待値を書き換える
+pub fn update_golden() -> bool {
+    matches!(std::env::var("UPDATE_GOLDEN").as_deref(), Ok(v) if !v.is_empty() && v != "0")
+}
@@ -0,0 +1,155 @@
+//! 決まった盤面をRecordingRendererで描いて、記録された描画命令を確認する
+
+mod common;
+
+use common::{initial_world, mid_game_world, render, revealing_world, HEIGHT, WIDTH};
+use tora_breakout_wasm::consts::{
+    BALL_RADIUS, BRICK_COLUMN_COUNT, BRICK_HEIGHT, BRICK_OFFSET_TOP, BRICK_ROW_COUNT, BRICK_WIDTH,
+};
+use tora_breakout_wasm::level::RevealEffect;
+use tora_breakout_wasm::recording_renderer::{DrawCommand, RecordingRenderer};
+
+/// 表の絵は"cover 列 行"、裏の絵は"hidden 列 行"
+fn tile(col: usize, row: usize, revealed: bool) -> String {
+    let set = if revealed { "hidden" } else { "cover" };
+    format!("{} {} {}", set, col, row)
+}
+
+fn record(world: &common::World<String>) -> Vec<DrawCommand> {
+    let mut renderer = RecordingRenderer::new();
+    render(world, &mut renderer);
+    renderer.get_commands().to_vec()
+}
+
+fn position_of(commands: &[DrawCommand], expected: &DrawCommand) -> usize {
+    commands
+        .iter()
+        .position(|c| c == expected)
+        .unwrap_or_else(|| panic!("{:?} was not drawn", expected))
+}
+
+fn text(text: &str) -> impl Fn(&DrawCommand) -> bool + '_ {
+    move |c| matches!(c, DrawCommand::FillText { text: t, .. } if t == text)
+}
+
+#[test]
+fn frame_starts_by_clearing_the_field() {
+    let commands = record(&initial_world(tile));
+    assert_eq!(
+        commands[0],
+        DrawCommand::ClearRect {
+            x: 0.0,
+            y: 0.0,
+            width: WIDTH as f64,
+            height: HEIGHT as f64,
+        }
+    );
+}
+
+#[test]
+fn every_live_brick_is_drawn_with_its_cover_image_and_outline() {
+    let commands = record(&initial_world(tile));
+    let images = commands
+        .iter()
+        .filter(|c| matches!(c, DrawCommand::DrawImage { .. }))
+        .count();
+    assert_eq!(images, BRICK_COLUMN_COUNT * BRICK_ROW_COUNT);
+
+    let col = 3;
+    let row = 5;
+    let x = col as f64 * BRICK_WIDTH;
+    let y = BRICK_OFFSET_TOP + row as f64 * BRICK_HEIGHT;
+    let image = position_of(
+        &commands,
+        &DrawCommand::DrawImage {
+            image: tile(col, row, false),
+            x,
+            y,
+            width: BRICK_WIDTH,
+            height: BRICK_HEIGHT,
+        },
+    );
+    assert!(matches!(
+        commands[image + 1],
+        DrawCommand::StrokeRect { x: sx, y: sy, .. } if sx == x && sy == y
+    ));
+}
+
+#[test]
+fn broken_brick_shows_the_hidden_image_without_outline() {
+    let commands = record(&mid_game_world(tile));
+    let image = position_of(
+        &commands,
+        &DrawCommand::DrawImage {
+            image: tile(4, 21, true),
+            x: 4.0 * BRICK_WIDTH,
+            y: BRICK_OFFSET_TOP + 21.0 * BRICK_HEIGHT,
+            width: BRICK_WIDTH,
+            height: BRICK_HEIGHT,
+        },
+    );
+    assert!(!matches!(
+        commands[image + 1],
+        DrawCommand::StrokeRect { .. }
+    ));
+    assert!(!commands.iter().any(|c| matches!(
+        c,
+        DrawCommand::DrawImage { image, .. } if *image == tile(4, 21, false)
+    )));
+}
+
+#[test]
+fn ball_and_hud_are_drawn_over_the_bricks() {
+    let commands = record(&mid_game_world(tile));
+    let last_brick = commands
+        .iter()
+        .rposition(|c| matches!(c, DrawCommand::DrawImage { .. }))
+        .unwrap();
+    let ball = position_of(
+        &commands,
+        &DrawCommand::FillCircle {
+            x: 230.0,
+            y: 560.0,
+            radius: BALL_RADIUS,
+            color: "rgb(255, 255, 255)".to_string(),
+        },
+    );
+    assert!(ball > last_brick);
+    for hud in ["Score: 20", "Lives: 2", "x2  Combo 5", "+20 x2"].iter() {
+        let index = commands
+            .iter()
+            .position(text(hud))
+            .unwrap_or_else(|| panic!("{} was not drawn", hud));
+        assert!(index > last_brick, "{} is under the bricks", hud);
+    }
+}
+
+#[test]
+fn wipe_reveal_clips_inside_save_and_restore() {
+    let commands = record(&revealing_world(tile, RevealEffect::Wipe, 6));
+    let saves = commands.iter().filter(|c| **c == DrawCommand::Save).count();
+    let restores = commands
+        .iter()
+        .filter(|c| **c == DrawCommand::Restore)
+        .count();
+    assert!(saves > 0);
+    assert_eq!(saves, restores);
+    let clip = commands
+        .iter()
+        .position(|c| matches!(c, DrawCommand::ClipRect { .. }))
+        .unwrap();
+    let save = commands[..clip]
+        .iter()
+        .rposition(|c| *c == DrawCommand::Save)
+        .unwrap();
+    assert!(!commands[save..clip].contains(&DrawCommand::Restore));
+}
+
+#[test]
+fn clear_forgets_recorded_commands() {
+    let mut renderer = RecordingRenderer::new();
+    render(&initial_world(tile), &mut renderer);
+    assert!(!renderer.get_commands().is_empty());
+    renderer.clear();
+    assert!(renderer.get_commands().is_empty());
+}
@@ -0,0 +1,102 @@
+//! 決まった盤面をSoftwareRendererで描いてtests/golden/*.pngと比較する
+//! 描画を変えた場合は `UPDATE_GOLDEN=1 cargo test` で画像を作り直して差分を確認すること
+
+mod common;
+
+use common::{golden_path, initial_world, mid_game_world, render, update_golden, HEIGHT, WIDTH};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tora_breakout_wasm::software_renderer::{RgbaImage, SoftwareRenderer};
+
+/// 画像ファイルの代わりにセルごとに色を変えたタイルを作る
+fn tile(col: usize, row: usize, revealed: bool) -> RgbaImage {
+    let mut image = RgbaImage::new(12, 5);
+    for y in 0..5 {
+        for x in 0..12 {
+            let shade = if (x + y) % 2 == 0 { 0 } else { 24 };
+            let rgba = if revealed {
+                [
+                    230 - shade,
+                    200 + (row * 2) as u8,
+                    120 + (col * 10) as u8,
+                    255,
+                ]
+            } else {
+                [60 + (col * 15) as u8, 20 + shade, 80 + (row * 6) as u8, 255]
+            };
+            image.set_pixel(x, y, rgba);
+        }
+    }
+    image
+}
+
+fn render_image(world: common::World<RgbaImage>) -> RgbaImage {
+    let mut renderer = SoftwareRenderer::new(WIDTH, HEIGHT);
+    render(&world, &mut renderer);
+    renderer.into_image()
+}
+
+fn write_png(path: &Path, image: &RgbaImage) {
+    let file = File::create(path).unwrap();
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(file), image.get_width(), image.get_height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(image.get_pixels()).unwrap();
+}
+
+fn read_png(path: &Path) -> RgbaImage {
+    let decoder = png::Decoder::new(File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba, "{:?}", path);
+    assert_eq!(info.bit_depth, png::BitDepth::Eight, "{:?}", path);
+    buf.truncate(info.buffer_size());
+    RgbaImage::from_pixels(info.width, info.height, buf).unwrap()
+}
+
+fn assert_golden(name: &str, actual: &RgbaImage) {
+    let golden = golden_path(&format!("{}.png", name));
+    if update_golden() {
+        write_png(&golden, actual);
+        return;
+    }
+    assert!(
+        golden.exists(),
+        "{:?} does not exist, run with UPDATE_GOLDEN=1 to create it",
+        golden
+    );
+    let expected = read_png(&golden);
+    let differs = expected.get_width() != actual.get_width()
+        || expected.get_height() != actual.get_height()
+        || expected.get_pixels() != actual.get_pixels();
+    if differs {
+        let out = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.actual.png", name));
+        write_png(&out, actual);
+        let changed = expected
+            .get_pixels()
+            .chunks(4)
+            .zip(actual.get_pixels().chunks(4))
+            .filter(|(e, a)| e != a)
+            .count();
+        panic!(
+            "{} differs from golden in {} pixels, actual image written to {:?}",
+            name, changed, out
+        );
+    }
+}
+
+#[test]
+fn initial_frame_matches_golden() {
+    let image = render_image(initial_world(tile));
+    assert_golden("initial", &image);
+}
+
+#[test]
+fn mid_game_frame_matches_golden() {
+    let image = render_image(mid_game_world(tile));
+    assert_golden("mid_game", &image);
+}
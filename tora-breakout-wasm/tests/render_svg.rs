@@ -0,0 +1,113 @@
+//! 決まった盤面をSvgRendererで描いてtests/golden/*.svgと比較する
+//! 描画を変えた場合は `UPDATE_GOLDEN=1 cargo test` で作り直してdiffを確認すること
+
+mod common;
+
+use common::{
+    golden_path, initial_world, mid_game_world, render, revealing_world, update_golden, HEIGHT,
+    WIDTH,
+};
+use std::fs;
+use tora_breakout_wasm::bricks::BrickKind;
+use tora_breakout_wasm::editor::Editor;
+use tora_breakout_wasm::level::{Level, RevealEffect};
+use tora_breakout_wasm::svg_renderer::SvgRenderer;
+
+/// get_imageと同じ名前の画像ファイルを参照する
+fn tile(col: usize, row: usize, revealed: bool) -> String {
+    let set = if revealed { "26_angel" } else { "27_devil" };
+    format!("imgs/{}_{}_{}.png", set, row + 1, col + 1)
+}
+
+fn render_svg(world: common::World<String>) -> String {
+    let mut renderer = SvgRenderer::new(WIDTH as f64, HEIGHT as f64);
+    render(&world, &mut renderer);
+    renderer.to_svg()
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let golden = golden_path(&format!("{}.svg", name));
+    if update_golden() {
+        fs::write(&golden, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap_or_else(|_| {
+        panic!(
+            "{:?} does not exist, run with UPDATE_GOLDEN=1 to create it",
+            golden
+        )
+    });
+    // 最初に違う行を出して原因を追いやすくする
+    for (i, (e, a)) in expected.lines().zip(actual.lines()).enumerate() {
+        assert_eq!(e, a, "{}.svg differs at line {}", name, i + 1);
+    }
+    assert_eq!(
+        expected.lines().count(),
+        actual.lines().count(),
+        "{}.svg has a different number of lines",
+        name
+    );
+}
+
+#[test]
+fn initial_frame_matches_svg_golden() {
+    assert_golden("initial", &render_svg(initial_world(tile)));
+}
+
+#[test]
+fn mid_game_frame_matches_svg_golden() {
+    assert_golden("mid_game", &render_svg(mid_game_world(tile)));
+}
+
+#[test]
+fn reveal_effects_match_svg_golden() {
+    let effects = [
+        ("reveal_flip", RevealEffect::Flip),
+        ("reveal_dissolve", RevealEffect::Dissolve),
+        ("reveal_wipe", RevealEffect::Wipe),
+    ];
+    for &(name, effect) in effects.iter() {
+        // 切り替えのちょうど途中
+        let world = revealing_world(tile, effect, 9);
+        assert_golden(name, &render_svg(world));
+    }
+}
+
+#[test]
+fn editor_matches_svg_golden() {
+    let mut editor = Editor::new();
+    editor.open(Level::new(
+        "test",
+        "27_devil",
+        "26_angel",
+        RevealEffect::Wipe,
+    ));
+    // 盤面を縮めて、硬いブロックの列と空いたマスを作る
+    for _ in 0..2 {
+        editor.handle_key("ArrowLeft", false, false);
+    }
+    for _ in 0..12 {
+        editor.handle_key("ArrowUp", false, false);
+    }
+    editor.handle_key("Digit2", false, false);
+    editor.pointer_down(10.0, 40.0, false);
+    for col in 0..8 {
+        editor.pointer_move(10.0 + col as f64 * 48.0, 40.0);
+    }
+    editor.pointer_up();
+    editor.handle_key("Digit3", false, false);
+    editor.pointer_down(100.0, 60.0, false);
+    editor.pointer_up();
+    editor.pointer_down(200.0, 150.0, true);
+    editor.pointer_move(250.0, 150.0);
+    editor.pointer_up();
+    assert_eq!(editor.get_level().get_cell(0, 0), Some(BrickKind::Hard));
+    assert_eq!(
+        editor.get_level().get_cell(2, 1),
+        Some(BrickKind::Unbreakable)
+    );
+    assert_eq!(editor.get_level().get_cell(4, 6), None);
+    let mut renderer = SvgRenderer::new(WIDTH as f64, HEIGHT as f64);
+    editor.draw(&mut renderer, WIDTH as f64, HEIGHT as f64);
+    assert_golden("editor", &renderer.to_svg());
+}
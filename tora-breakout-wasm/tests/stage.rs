@@ -0,0 +1,60 @@
+//! ステージをクリアしたあと次のステージへ進むことを確認する
+
+use tora_breakout_wasm::game_status::{GameStatus, Status};
+use tora_breakout_wasm::level::{Level, RevealEffect};
+use tora_breakout_wasm::{next_level, Container};
+
+fn cleared_stage_one() -> GameStatus {
+    let mut status = GameStatus::new();
+    status.set_score(1200);
+    status.set_lives(2);
+    status.start_timer(1000.0);
+    status.set_status(Status::StageClear);
+    status
+}
+
+#[test]
+fn clearing_stage_one_loads_stage_two_with_its_reveal() {
+    let mut status = cleared_stage_one();
+    let level = next_level(&mut status, None);
+    assert_eq!(status.get_stage(), 2);
+    assert_eq!(level, Level::builtin()[1]);
+
+    let mut bricks = Container::new(String::new());
+    bricks.set_level(level);
+    assert_eq!(bricks.get_level().get_name(), "dissolve");
+    assert_eq!(bricks.get_level().get_reveal(), RevealEffect::Dissolve);
+    assert!(!bricks.is_cleared());
+}
+
+#[test]
+fn next_stage_keeps_score_and_lives_and_waits_for_launch() {
+    let mut status = cleared_stage_one();
+    next_level(&mut status, None);
+    assert_eq!(status.get_score(), 1200);
+    assert_eq!(status.get_lives(), 2);
+    assert_eq!(status.get_status(), Status::Stop);
+    // クリアタイムはステージごとに測り直す
+    assert_eq!(status.get_duration(5000.0), 0.0);
+}
+
+#[test]
+fn stages_after_the_last_built_in_one_start_over_and_custom_levels_repeat() {
+    let mut status = cleared_stage_one();
+    let levels: Vec<RevealEffect> = (0..3)
+        .map(|_| next_level(&mut status, None).get_reveal())
+        .collect();
+    assert_eq!(
+        levels,
+        vec![
+            RevealEffect::Dissolve,
+            RevealEffect::Wipe,
+            RevealEffect::Flip
+        ]
+    );
+    assert_eq!(status.get_stage(), 4);
+
+    let custom = Level::new("custom", "26_angel", "27_devil", RevealEffect::Wipe);
+    assert_eq!(next_level(&mut status, Some(&custom)), custom);
+    assert_eq!(status.get_stage(), 5);
+}
@@ -4,6 +4,7 @@ const HtmlWebpackPlugin = require("html-webpack-plugin");
 const webpack = require("webpack");
 const WasmPackPlugin = require("@wasm-tool/wasm-pack-plugin");
 const ASSET_PATH = process.env.ASSET_PATH || "";
+const ASSET_MANIFEST = process.env.ASSET_MANIFEST || "";
 
 module.exports = {
   resolve: {
@@ -43,7 +44,8 @@ module.exports = {
     }),
     new webpack.DefinePlugin({
       "process.env.ASSET_PATH": JSON.stringify(ASSET_PATH),
-      ASSET_URL: JSON.stringify(ASSET_PATH)
+      ASSET_URL: JSON.stringify(ASSET_PATH),
+      ASSET_MANIFEST: JSON.stringify(ASSET_MANIFEST)
     })
   ]
 };